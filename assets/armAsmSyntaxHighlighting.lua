@@ -8,6 +8,12 @@ registers = {"AX", "BX", "CX", "DX", "SI", "DI", "BP", "SP",
              "CS", "DS", "ES", "SS", "IP", "FLAGS", "DB"
 }
 
+-- the brackets and quotes that get closed automatically
+autoPairs = {"[]", "\"\"", "''"}
+
+-- the comment syntax the comment toggle uses
+lineComment = ";"
+
 -- checks if a value is in an array
 function Contains (array, query)
     for index = 1, #array do
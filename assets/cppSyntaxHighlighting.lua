@@ -8,6 +8,13 @@ mathLogicTokens = {"=", "<", ">", "!", "-", "+", "/", "*"}
 logicTokens = {"=", "<", ">", "!"}
 mathTokens = {"-", "+", "/", "*"}
 
+-- the brackets and quotes that get closed automatically
+autoPairs = {"()", "[]", "{}", "\"\"", "''"}
+
+-- the comment syntax the comment toggle uses
+lineComment = "//"
+blockComment = {"/*", "*/"}
+
 -- checks if a value is in an array
 function Contains (array, query)
     for index = 1, #array do
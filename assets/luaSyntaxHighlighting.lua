@@ -6,6 +6,20 @@ logicTokens = {"=", "<", ">", "~"}
 mathTokens = {"-", "+", "/", "*"}
 
 
+-- the brackets and quotes that get closed automatically
+autoPairs = {"()", "[]", "{}", "\"\"", "''"}
+
+-- the comment syntax the comment toggle uses
+lineComment = "--"
+blockComment = {"--[[", "]]"}
+
+-- the snippets expanded by their prefix (with the completion key); tabs in them are a level of indentation
+snippets = {
+    ["function"] = "function ${1:Name} (${2})\n\t$0\nend",
+    ["for"] = "for ${1:index} = ${2:1}, ${3:count} do\n\t$0\nend",
+    ["if"] = "if ${1:condition} then\n\t$0\nend",
+}
+
 -- checks if a value is in an array
 function Contains (array, query)
     for index = 1, #array do
@@ -104,3 +118,21 @@ function ParseTokenType (lastTokenType, lastToken, nextToken, token)
     end
 end
 
+
+
+-- the number of levels a new line indents by after previousLine (nil falls back on the bracket rules)
+function GetIndentChange (previousLine, nextLine)
+    local line = string.gsub(string.gsub(previousLine, "%-%-.*$", ""), "%s+$", "")
+    local change = 0
+    local lastWord = string.match(line, "(%a+)$")
+    if lastWord == "then" or lastWord == "do" or lastWord == "else" or lastWord == "repeat"
+        or string.match(line, "function%s*[%w_.:]*%s*%b()$") or string.match(line, "[{%(%[]$") then
+        change = 1
+    end
+    local nextWord = string.match(nextLine, "^%s*(%a+)")
+    if nextWord == "end" or nextWord == "else" or nextWord == "elseif" or nextWord == "until"
+        or string.match(nextLine, "^%s*[}%)%]]") then
+        change = change - 1
+    end
+    return change
+end
@@ -7,6 +7,20 @@ mathLogicTokens = {"=", "<", ">", "!", "-", "+", "/", "*", "and", "or"}
 logicTokens = {"=", "<", ">", "!"}
 mathTokens = {"-", "+", "/", "*", "and", "or"}
 
+-- the brackets and quotes that get closed automatically
+autoPairs = {"()", "[]", "{}", "\"\"", "''"}
+
+-- the comment syntax the comment toggle uses
+lineComment = "#"
+
+-- the snippets expanded by their prefix (with the completion key); tabs in them are a level of indentation
+snippets = {
+    def = "def ${1:name}(${2}):\n\t${0:pass}",
+    class = "class ${1:Name}:\n\tdef __init__(self${2}):\n\t\t${0:pass}",
+    ["for"] = "for ${1:item} in ${2:iterable}:\n\t${0:pass}",
+    main = "if __name__ == \"__main__\":\n\t${0:main()}",
+}
+
 -- checks if a value is in an array
 function Contains (array, query)
     for index = 1, #array do
@@ -169,3 +183,17 @@ function CalculateMember (lastTokenType, lastToken, nextToken, token)
     return "Member"
 end
 
+
+
+-- the number of levels a new line indents by after previousLine (nil falls back on the bracket rules)
+function GetIndentChange (previousLine, nextLine)
+    local line = string.gsub(string.gsub(previousLine, "#.*$", ""), "%s+$", "")
+    if string.sub(line, -1) == ":" then
+        return 1
+    end
+    local firstWord = string.match(line, "^%s*(%a+)")
+    if firstWord == "return" or firstWord == "pass" or firstWord == "break" or firstWord == "continue" or firstWord == "raise" then
+        return -1
+    end
+    return nil
+end
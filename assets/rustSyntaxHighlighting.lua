@@ -13,6 +13,24 @@ mathLogicTokens = {"=", "<", ">", "!", "-", "+", "/", "*"}
 logicTokens = {"=", "<", ">", "!"}
 mathTokens = {"-", "+", "/", "*"}
 
+-- the brackets and quotes that get closed automatically (no ' since it starts lifetimes)
+autoPairs = {"()", "[]", "{}", "\"\""}
+
+-- the comment syntax the comment toggle uses
+lineComment = "//"
+blockComment = {"/*", "*/"}
+
+-- the snippets expanded by their prefix (with the completion key); tabs in them are a level of indentation
+snippets = {
+    fn = "fn ${1:name} (${2}) {\n\t$0\n}",
+    ["if"] = "if ${1:condition} {\n\t$0\n}",
+    ["for"] = "for ${1:item} in ${2:iterator} {\n\t$0\n}",
+    ["match"] = "match ${1:value} {\n\t${2:_} => $0,\n}",
+    impl = "impl ${1:Type} {\n\t$0\n}",
+    test = "#[test]\nfn ${1:name} () {\n\t$0\n}",
+    derive = "#[derive(${1:Debug, Clone})]",
+}
+
 -- checks if a value is in an array
 function Contains (array, query)
     for index = 1, #array do
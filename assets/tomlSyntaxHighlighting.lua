@@ -1,5 +1,11 @@
 keywords = {"true", "false"}
 
+-- the brackets and quotes that get closed automatically
+autoPairs = {"[]", "{}", "\"\"", "''"}
+
+-- the comment syntax the comment toggle uses
+lineComment = "#"
+
 -- checks if a value is in an array
 function Contains (array, query)
     for index = 1, #array do
@@ -27,7 +27,7 @@ pub fn load_lua_script (input: TokenStream) -> TokenStream {
 
         #hashmap.lock().insert(
             #language,
-                lua.globals().get("GetTokens").unwrap()
+            LanguageScript::Load(&lua)
         );
     })
 }
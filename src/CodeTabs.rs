@@ -4,14 +4,50 @@ use crate::TokenInfo::*;
 use crate::LuaScripts;
 use crate::Tokens::*;
 use crate::Colors;
+use crate::Snippets::{ActiveSnippet, Snippet};
 use crate::color;
 
 // the bounds from the screen edge at which the cursor will begin scrolling
 const SCROLL_BOUNDS: usize = 12;
 const CENTER_BOUNDS: usize = 0;
+// the rows of the terminal that aren't code lines (the tab bar, the code window's border, and everything below it)
+pub const NON_CODE_ROWS: usize = 11;
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+// stands in for the end of the line in the highlighted ranges (so it still works on lines scrolled far sideways)
+const LINE_END: usize = usize::MAX;
 
 pub type RustAnalyzerLsp <'a> = &'a Option <std::sync::Arc <parking_lot::RwLock <RustAnalyzer>>>;
 
+// the number of code lines drawn on a terminal of that height
+pub fn CodeRows (height: u16) -> usize {  (height as usize).saturating_sub(NON_CODE_ROWS)  }
+
+// the spaces and tabs a line starts with
+pub fn LeadingWhitespace (line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+
+const BRACKET_PAIRS: [(u8, u8); 3] = [(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+
+// whether a token is part of a string or comment (going off of its type and the flags it was lexed with)
+fn IsQuoted (token: &LuaTuple, flags: Option <&Vec <LineTokenFlags>>) -> bool {
+    flags.is_some_and(|flags| flags.contains(&LineTokenFlags::String) || flags.contains(&LineTokenFlags::Comment)) ||
+        matches!(token.token, TokenType::String | TokenType::Comment | TokenType::CommentLong)
+}
+
+// the text with its tabs swapped for spaces out to the next tab stop (starting at column)
+fn ExpandTabs (text: &str, column: usize, tabWidth: usize) -> String {
+    if !text.contains('\t') {  return text.to_string();  }
+    let mut expanded = String::new();
+    for chr in text.chars() {
+        if chr == '\t' {
+            expanded.push_str(&" ".repeat(tabWidth - (column + expanded.chars().count()) % tabWidth));
+        } else {
+            expanded.push(chr);
+        }
+    }
+    expanded
+}
 
 pub mod Edits {
     pub type RustAnalyzerLsp <'a> = &'a Option <std::sync::Arc <parking_lot::RwLock <RustAnalyzer>>>;
@@ -38,6 +74,7 @@ pub mod Edits {
             let numBetween = start.0 - end.0 - 1;
             for _ in 0..numBetween {
                 tab.lines.remove(end.0 + 1);
+                tab.LinesRemoved(end.0 + 1, 1);
                 tab.lineTokens.write().remove(end.0 + 1);
                 tab.lineTokenFlags.write().remove(end.0 + 1);
             }
@@ -46,6 +83,7 @@ pub mod Edits {
             tab.lines[end.0].push_str(nextLine.as_str());
             tab.RecalcTokens(end.0, 0, luaSyntaxHighlightScripts).await;
             tab.lines.remove(end.0 + 1);
+            tab.LinesRemoved(end.0 + 1, 1);
             tab.lineTokens.write().remove(end.0 + 1);
             tab.lineTokenFlags.write().remove(end.0 + 1);
         }
@@ -67,6 +105,7 @@ pub mod Edits {
         for (i, line) in splitText.enumerate() {
             if line.is_empty() {
                 tab.lines.insert(end.0 + i, "".to_string());
+                tab.LinesInserted(end.0 + i, 1);
                 tab.lineTokens.write().insert(end.0 + i, vec![]);
                 tab.lineTokenFlags.write().insert(end.0 + i, vec![]);
                 tab.RecalcTokens(end.0 + i, 0, luaSyntaxHighlightScripts).await;
@@ -82,6 +121,7 @@ pub mod Edits {
                 tab.RecalcTokens(end.0, 0, luaSyntaxHighlightScripts).await;
             } else {
                 tab.lines.insert(end.0 + i, line.to_string());
+                tab.LinesInserted(end.0 + i, 1);
                 tab.lineTokenFlags.write().insert(end.0 + i, vec![]);
                 tab.lineTokens.write().insert(end.0 + i, vec![]);
                 tab.RecalcTokens(end.0 + i, 0, luaSyntaxHighlightScripts).await;
@@ -155,6 +195,7 @@ pub mod Edits {
                                 rustAnalyzer: RustAnalyzerLsp<'a>,
         ) {
             let text = tab.lines.remove(self.position.0 + 1);
+            tab.LinesRemoved(self.position.0 + 1, 1);
             tab.lineTokens.write().remove(self.position.0 + 1);
             tab.lineTokenFlags.write().remove(self.position.0 + 1);
             tab.lines[self.position.0].push_str(text.as_str());
@@ -174,6 +215,7 @@ pub mod Edits {
             let rightText = tab.lines[self.position.0]
                 .split_off(self.position.1);
             tab.lines.insert(self.position.0 + 1, rightText.to_string());
+            tab.LinesInserted(self.position.0 + 1, 1);
             tab.lineTokens.write().insert(self.position.0 + 1, vec![]);
             tab.lineTokenFlags.write().insert(self.position.0 + 1, vec![]);
             tab.RecalcTokens(self.position.0 + 1, 0, luaSyntaxHighlightScripts).await;
@@ -202,6 +244,7 @@ pub mod Edits {
             let rightText = tab.lines[self.position.0]
                 .split_off(self.position.1);
             tab.lines.insert(self.position.0 + 1, rightText.to_string());
+            tab.LinesInserted(self.position.0 + 1, 1);
             tab.lineTokens.write().insert(self.position.0 + 1, vec![]);
             tab.lineTokenFlags.write().insert(self.position.0 + 1, vec![]);
             tab.RecalcTokens(self.position.0 + 1, 0, luaSyntaxHighlightScripts).await;
@@ -221,6 +264,7 @@ pub mod Edits {
                                 rustAnalyzer: RustAnalyzerLsp<'a>,
         ) {
             let text = tab.lines.remove(self.position.0 + 1);
+            tab.LinesRemoved(self.position.0 + 1, 1);
             tab.lineTokens.write().remove(self.position.0 + 1);
             tab.lineTokenFlags.write().remove(self.position.0 + 1);
             tab.lines[self.position.0].push_str(text.as_str());
@@ -249,6 +293,16 @@ pub mod Edits {
 
 // this isn't nearly as long as I thought it would be lol (too lazy to inline it)
 type ScopeHandle = std::thread::JoinHandle <()>;
+// each line's row starts, with the wrap width and tab width they were worked out for
+type WrapCache = parking_lot::Mutex <std::collections::HashMap <usize, (usize, usize, Vec <usize>)>>;
+
+// what a line is drawn with besides its own text
+#[derive(Clone, Copy)]
+struct SliceExtras <'e> {
+    width: usize,  // the column the line stops being drawn at
+    suggested: &'e str,  // the suggested auto-complete (drawn after the cursor)
+    marks: &'e [(usize, ColorType)],  // colors for the characters at those columns (the brackets)
+}
 
 #[derive(Debug)]
 pub struct CodeTab {
@@ -285,6 +339,17 @@ pub struct CodeTab {
 
     pub saved: bool,
     pub path: String,
+    pub tabWidth: usize,  // the number of spaces a tab indents by (and the width a tab character is drawn at)
+    pub useTabs: bool,  // indents with a tab character instead
+    pub folds: Vec <(usize, usize)>,  // (first line, last line) of each folded range
+    pub softWrap: bool,  // long lines are wrapped onto more rows instead of being cut off
+    pub wrapWidth: usize,  // the columns a row has for text (from the last render; the cursor gets one more)
+    pub horizontalScroll: usize,  // the columns scrolled past on the left (always 0 when wrapping)
+    pub horizontalScrollFlt: f64,
+    pub snippet: Option <ActiveSnippet>,  // the tab stops of the snippet being filled in
+    pub completions: Vec <Snippet>,  // the lsp's last completions for this file
+    pub unmatchedBrackets: Option <Vec <(usize, usize)>>,  // cleared whenever the tokens change
+    pub wrapCache: WrapCache,  // cleared whenever lines are added or taken out, and per line when it's edited
 
     pub scrollCache: Vec <Span>,
     pub resetCache: Vec <bool>,
@@ -333,6 +398,12 @@ impl CodeTab {
 
         let rustAnalyzer = rustAnalyzer.clone();
         let charIndex = self.cursor.1;
+        // the lsp counts columns in utf-16 units, and gets the whole text since it's only told about the saved file
+        let completion = rustAnalyzer.is_some().then(|| {
+            let line = &self.lines[self.cursor.0];
+            let column = line[..std::cmp::min(charIndex, line.len())].encode_utf16().count();
+            RustEvents::Completion(self.cursor.0, column, self.lines.join("\n"))
+        });
 
         self.scopeGenerationHandles.push((
             std::thread::spawn(move || {
@@ -340,13 +411,12 @@ impl CodeTab {
 
                 // put in a request for a lsp update between start and end
                 // maybe poll this to make sure it doesn't block anything? idk
-                //let completion = false;  // do something with this at some point
                 if rustAnalyzer.is_some() {
-                    rustAnalyzer.as_ref()
-                                .unwrap()
-                                .write()
-                                .NewEvent(RustEvents::UpdatedLines(start, end, charIndex, String::from(""))
-                    );
+                    let mut analyzer = rustAnalyzer.as_ref().unwrap().write();
+                    analyzer.NewEvent(RustEvents::UpdatedLines(start, end, charIndex, String::from("")));
+                    if let Some(completion) = completion {
+                        analyzer.NewEvent(completion);
+                    }
                 }
 
                 let (newScopes, newJumps, newLinear) =
@@ -438,6 +508,139 @@ impl CodeTab {
         } // lineTokensRead is dropped naturally
     }
 
+    // The brackets on a line that aren't in a string or comment, as (column, bracket)
+    pub fn LineBrackets (&self, line: usize) -> Vec <(usize, u8)> {
+        let lineTokens = self.lineTokens.read();
+        let lineTokenFlags = self.lineTokenFlags.read();
+        let mut brackets = vec![];
+        let mut column = 0;
+        for (index, token) in lineTokens.get(line).map(Vec::as_slice).unwrap_or_default().iter().enumerate() {
+            if !IsQuoted(token, lineTokenFlags.get(line).and_then(|flags| flags.get(index))) {
+                brackets.extend(token.text.bytes()
+                    .enumerate()
+                    .filter(|(_, byte)| BRACKET_PAIRS.iter().any(|(open, close)| byte == open || byte == close))
+                    .map(|(offset, byte)| (column + offset, byte)));
+            }
+            column += token.text.len();
+        }
+        brackets
+    }
+
+    // Finds the bracket paired with the one at the position (or the next one after it on the line)
+    // Brackets in strings and comments are skipped
+    pub fn FindMatchingBracket (&self, position: (usize, usize)) -> Option <(usize, usize)> {
+        let mut brackets = self.LineBrackets(position.0);
+        let (column, bracket) = brackets.iter().copied().find(|(column, _)| *column >= position.1)?;
+        let (open, close) = BRACKET_PAIRS.iter().copied().find(|(open, close)| bracket == *open || bracket == *close)?;
+        let forward = bracket == open;
+
+        let mut depth = 0usize;
+        let mut line = position.0;
+        loop {
+            if !forward {  brackets.reverse();  }
+            for (current, chr) in brackets {
+                // starting from the bracket itself
+                if line == position.0 && (forward && current < column || !forward && current > column) {  continue;  }
+                if chr != open && chr != close {  continue;  }
+                if (chr == open) == forward {
+                    depth += 1;
+                } else {
+                    depth -= 1;
+                    if depth == 0 {  return Some((line, current));  }
+                }
+            }
+
+            // stepping to the next line
+            if forward {
+                line += 1;
+                if line >= self.lines.len() {  return None;  }
+            } else {
+                line = line.checked_sub(1)?;
+            }
+            brackets = self.LineBrackets(line);
+        }
+    }
+
+    // The bracket touching the cursor (the one after it first) and the bracket it pairs with
+    pub fn BracketsAtCursor (&self) -> Option <((usize, usize), (usize, usize))> {
+        let brackets = self.LineBrackets(self.cursor.0);
+        let column = brackets.iter()
+            .find(|(column, _)| *column == self.cursor.1)
+            .or(brackets.iter().find(|(column, _)| *column + 1 == self.cursor.1))?.0;
+        let position = (self.cursor.0, column);
+        Some((position, self.FindMatchingBracket(position)?))
+    }
+
+    // Every bracket without a partner (kept until the tokens change, since it goes over the whole file)
+    pub fn UnmatchedBrackets (&mut self) -> &[(usize, usize)] {
+        if self.unmatchedBrackets.is_none() {
+            self.unmatchedBrackets = Some(self.FindUnmatchedBrackets());
+        }
+        self.unmatchedBrackets.as_deref().unwrap_or_default()
+    }
+
+    // a closer skipping over other openers to reach its own leaves those unmatched
+    fn FindUnmatchedBrackets (&self) -> Vec <(usize, usize)> {
+        let mut unmatched = vec![];
+        let mut open: Vec <((usize, usize), u8)> = vec![];
+        for line in 0..self.lines.len() {
+            for (column, bracket) in self.LineBrackets(line) {
+                if let Some((_, close)) = BRACKET_PAIRS.iter().find(|(open, _)| *open == bracket) {
+                    open.push(((line, column), *close));
+                } else if let Some(index) = open.iter().rposition(|(_, close)| *close == bracket) {
+                    unmatched.extend(open.drain(index..).skip(1).map(|(position, _)| position));
+                } else {
+                    unmatched.push((line, column));
+                }
+            }
+        }
+        unmatched.extend(open.into_iter().map(|(position, _)| position));
+        unmatched
+    }
+
+    // the colors for the brackets that stand out: the pair by the cursor, and anything unmatched
+    fn BracketMarks (&mut self, editingCode: bool, colorMode: &Colors::ColorMode) -> Vec <((usize, usize), ColorType)> {
+        let error = colorMode.colorBindings.errorCol.Get(&colorMode.colorType);
+        let matching = colorMode.colorBindings.matchingBracket.Get(&colorMode.colorType);
+        let mut marks: Vec <((usize, usize), ColorType)> = self.UnmatchedBrackets().iter()
+            .map(|position| (*position, error))
+            .collect();
+        if editingCode && let Some((bracket, pair)) = self.BracketsAtCursor() {
+            marks.push((bracket, matching));
+            marks.push((pair, matching));
+        }
+        marks
+    }
+
+    // Folds every undo group made since the depth into a single group
+    // The newest edits go first since that's the order they get undone in
+    pub fn MergeChanges (&mut self, undoDepth: usize) {
+        let mut edits = vec![];
+        while self.changeBuffer.len() > undoDepth {
+            if let Some(group) = self.changeBuffer.pop() {
+                edits.extend(group);
+            }
+        }
+        if !edits.is_empty() {
+            self.changeBuffer.push(edits);
+        }
+    }
+
+    // everywhere the token shows up, as the position just after it (only whole tokens count, so len won't find length)
+    pub fn FindReferences (&self, token: &str) -> Vec <(usize, usize)> {
+        let mut references = vec![];
+        for (lineNumber, tokens) in self.lineTokens.read().iter().enumerate() {
+            let mut accumulate = 0;
+            for other in tokens {
+                accumulate += other.text.len();
+                if other.text == token {
+                    references.push((lineNumber, accumulate));
+                }
+            }
+        }
+        references
+    }
+
     // doesn't update the tokens or scopes; requires that to be done elsewhere
     pub fn RemoveCurrentToken_NonUpdate (&mut self) {
         let mut accumulate = 0;
@@ -500,7 +703,8 @@ impl CodeTab {
         self.highlighting = false;
 
         if let Some(edits) = self.redoneBuffer.pop() {
-            for edit in &edits {
+            // the group is stored newest first, so it's replayed backwards
+            for edit in edits.iter().rev() {
                 match edit {
                     Edits::Edit::Addition (action)      => {
                         action.Redo(self, luaSyntaxHighlightScripts, rustAnalyzer).await;
@@ -521,7 +725,11 @@ impl CodeTab {
     }
 
     pub fn Save (&mut self) {
-        self.saved = true;
+        self.TrySave().expect("Unable to write file");
+    }
+
+    // writes the file out, leaving it marked as unsaved if that failed
+    pub fn TrySave (&mut self) -> std::io::Result <()> {
         let mut fileContents = String::new();
         for line in &self.lines {
             fileContents.push_str(line.as_str());
@@ -529,7 +737,36 @@ impl CodeTab {
         }
         fileContents.pop();  // popping the final \n so it doesn't gradually expand over time
 
-        std::fs::write(&self.path, fileContents).expect("Unable to write file");
+        std::fs::write(&self.path, fileContents)?;
+        self.saved = true;
+        Ok(())
+    }
+
+    // a single level of indentation
+    pub fn Indent (&self) -> String {
+        if self.useTabs {  String::from("\t")  }
+        else {  " ".repeat(self.tabWidth)  }
+    }
+
+    // the column a position in the line is drawn at (tabs go out to the next tab stop)
+    pub fn DisplayColumn (&self, line: usize, index: usize) -> usize {
+        let text = &self.lines[line];
+        let mut column = 0;
+        for chr in text.get(..index).unwrap_or(text).chars() {
+            column += if chr == '\t' {  self.tabWidth - column % self.tabWidth  } else {  1  };
+        }
+        column + index.saturating_sub(text.len())
+    }
+
+    // the position in the line that's drawn at a column (the end of the line if it's past it)
+    pub fn IndexAtColumn (&self, line: usize, column: usize) -> usize {
+        let text = &self.lines[line];
+        let mut current = 0;
+        for (index, chr) in text.char_indices() {
+            current += if chr == '\t' {  self.tabWidth - current % self.tabWidth  } else {  1  };
+            if current > column {  return index;  }
+        }
+        text.len()
     }
 
     pub fn MoveCursorLeftToken (&mut self) {
@@ -622,6 +859,39 @@ impl CodeTab {
         newCursor - self.cursor.1
     }
     
+    // the (start, end) of the token at the position; past the end of a line is the last token
+    pub fn TokenRange (&self, position: (usize, usize)) -> Option <(usize, usize)> {
+        let lineTokensRead = self.lineTokens.read();
+        let tokens = lineTokensRead.get(position.0)?;
+        let mut range = (0, 0);
+        for token in tokens {
+            range = (range.1, range.1 + token.text.len());
+            if range.1 > position.1 {  break;  }
+        }
+        drop(lineTokensRead);
+
+        let end = std::cmp::min(range.1, self.lines[position.0].len());
+        if range.0 >= end {  return None;  }
+        Some((range.0, end))
+    }
+
+    // selects the token under the cursor (double clicking)
+    pub fn SelectToken (&mut self) {
+        let Some((start, end)) = self.TokenRange(self.cursor) else {  return;  };
+        self.cursorEnd = (self.cursor.0, start);
+        self.cursor.1 = end;
+        self.highlighting = true;
+    }
+
+    // selects the whole line the cursor is on, including its line break (triple clicking)
+    pub fn SelectLine (&mut self) {
+        self.cursorEnd = (self.cursor.0, 0);
+        self.cursor =
+            if self.cursor.0 + 1 < self.lines.len() {  (self.cursor.0 + 1, 0)  }
+            else {  (self.cursor.0, self.lines[self.cursor.0].len())  };
+        self.highlighting = true;
+    }
+
     pub fn MoveCursorRightToken (&mut self) {
         self.scrolled = std::cmp::max(
             self.mouseScrolledFlt as isize + self.scrolled as isize,
@@ -769,29 +1039,158 @@ impl CodeTab {
         ) as usize;
         self.mouseScrolled = 0;
         self.mouseScrolledFlt = 0.0;
-        // checking for 4 spaces at the start
-        if let Some(charSet) = &self.lines[self.cursor.0].get(..4) {
-            if *charSet == "    " {
-                self.changeBuffer.push(
-                    vec![
-                        Edits::Edit::Deletion(Edits::Deletion {
-                            start: (self.cursor.0, 3),  // I think it should be 3
-                            end: (self.cursor.0, 0),
-                            text: "    ".to_string()
-                        })
-                    ]
-                );
+        // a level off of the start; either a tab or up to a tab width of spaces
+        let line = &self.lines[self.cursor.0];
+        let removed =
+            if line.starts_with('\t') {  1  }
+            else {  std::cmp::min(line.len() - line.trim_start_matches(' ').len(), self.tabWidth)  };
+        if removed == 0 {  return;  }
+
+        let text = self.lines[self.cursor.0].drain(..removed).collect::<String>();
+        self.changeBuffer.push(
+            vec![
+                Edits::Edit::Deletion(Edits::Deletion {
+                    start: (self.cursor.0, removed),
+                    end: (self.cursor.0, 0),
+                    text
+                })
+            ]
+        );
+        self.cursor.1 = self.cursor.1.saturating_sub(removed);
 
-                for _ in 0..4 {  self.lines[self.cursor.0].remove(0);  }
-                self.cursor.1 = self.cursor.1.saturating_sub(4);
+        self.RecalcTokens(self.cursor.0, 0, luaSyntaxHighlightScripts).await;
 
-                self.RecalcTokens(self.cursor.0, 0, luaSyntaxHighlightScripts).await;
+        self.CreateScopeThread(self.cursor.0, self.cursor.0, rustAnalyzer);
+    }
 
-                self.CreateScopeThread(self.cursor.0, self.cursor.0, rustAnalyzer);
-                //(self.scopes, self.scopeJumps, self.linearScopes) =
-                //    GenerateScopes(&self.lineTokens, &self.lineTokenFlags, &mut self.outlineKeywords);
+    // indents or unindents every line from start to end (blank lines aren't indented) as one undo group;
+    // the cursor and selection stay on the same text
+    pub async fn IndentLines <'a> (&mut self,
+                                   start: usize,
+                                   end: usize,
+                                   unindent: bool,
+                                   luaSyntaxHighlightScripts: &LuaScripts,
+                                   rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let undoDepth = self.changeBuffer.len();
+        let (cursor, cursorEnd, highlighting) = (self.cursor, self.cursorEnd, self.highlighting);
+        self.highlighting = false;
+
+        let mut shifts = vec![];
+        for line in start..=end {
+            let length = self.lines[line].len();
+            self.cursor = (line, 0);
+            if unindent {
+                self.UnIndent(luaSyntaxHighlightScripts, rustAnalyzer).await;
+            } else if !self.lines[line].trim().is_empty() {
+                self.InsertChars(self.Indent(), luaSyntaxHighlightScripts, rustAnalyzer).await;
             }
+            shifts.push(self.lines[line].len() as isize - length as isize);
         }
+        self.MergeChanges(undoDepth);
+
+        // anything at the very start of a line stays there, so a selection of whole lines still covers the indent
+        let Shift = |position: (usize, usize)| {
+            if position.0 < start || position.0 > end || position.1 == 0 {  return position;  }
+            (position.0, (position.1 as isize + shifts[position.0 - start]).max(0) as usize)
+        };
+        self.cursor = Shift(cursor);
+        self.cursorEnd = Shift(cursorEnd);
+        self.highlighting = highlighting;
+    }
+
+    // the language's (line comment, block comment), from its Lua script
+    pub fn CommentSyntax (&self, luaSyntaxHighlightScripts: &LuaScripts) -> (Option <String>, Option <(String, String)>) {
+        let ending = self.fileName.split('.').next_back().unwrap_or("");
+        luaSyntaxHighlightScripts.lock()
+            .get(&Languages::FromExtension(ending))
+            .map(|script| (script.lineComment.clone(), script.blockComment.clone()))
+            .unwrap_or((None, None))
+    }
+
+    // comments out the lines from start to end (or uncomments them if they already all are) as one undo group.
+    // line comments go in at the lines' shared indent with blank lines left alone, and a language with only
+    // block comments gets the whole range wrapped instead. False if the language has no comment syntax
+    pub async fn ToggleComment <'a> (&mut self,
+                                     start: usize,
+                                     end: usize,
+                                     luaSyntaxHighlightScripts: &LuaScripts,
+                                     rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) -> bool {
+        let (lineComment, blockComment) = self.CommentSyntax(luaSyntaxHighlightScripts);
+        if lineComment.is_none() && blockComment.is_none() {  return false;  }
+        let IndentOf = |line: &str| line.len() - line.trim_start().len();
+        let lines: Vec <usize> = (start..=end).filter(|line| !self.lines[*line].trim().is_empty()).collect();
+        let (Some(&first), Some(&last)) = (lines.first(), lines.last()) else {  return true;  };
+
+        let undoDepth = self.changeBuffer.len();
+        let (cursor, cursorEnd, highlighting) = (self.cursor, self.cursorEnd, self.highlighting);
+        self.highlighting = false;
+
+        // (line, column, change in length) for each edit, to keep the cursor and selection on the same text
+        let mut shifts: Vec <(usize, usize, isize)> = vec![];
+        if let Some(token) = lineComment {
+            let commented = lines.iter().all(|line| self.lines[*line].trim_start().starts_with(&token));
+            let column = lines.iter().map(|line| IndentOf(&self.lines[*line])).min().unwrap_or(0);
+            for line in lines {
+                if commented {
+                    let at = IndentOf(&self.lines[line]);
+                    let length = token.len() + self.lines[line][at + token.len()..].starts_with(' ') as usize;
+                    self.cursor = (line, at + length);
+                    self.DelChars(length, 0, luaSyntaxHighlightScripts, rustAnalyzer).await;
+                    shifts.push((line, at, -(length as isize)));
+                } else {
+                    self.cursor = (line, column);
+                    self.InsertChars(format!("{token} "), luaSyntaxHighlightScripts, rustAnalyzer).await;
+                    shifts.push((line, column, token.len() as isize + 1));
+                }
+            }
+        } else if let Some((open, close)) = blockComment {
+            let at = IndentOf(&self.lines[first]);
+            let lineEnd = self.lines[last].trim_end().len();
+            let commented = self.lines[first].trim_start().starts_with(&open) &&
+                self.lines[last].trim_end().ends_with(&close) &&
+                (first != last || lineEnd >= at + open.len() + close.len());
+            // the closing half goes first so the opening half doesn't move it
+            if commented {
+                let closeLength = close.len() + self.lines[last][..lineEnd - close.len()].ends_with(' ') as usize;
+                self.cursor = (last, lineEnd);
+                self.DelChars(closeLength, 0, luaSyntaxHighlightScripts, rustAnalyzer).await;
+                let openLength = open.len() + self.lines[first][at + open.len()..].starts_with(' ') as usize;
+                self.cursor = (first, at + openLength);
+                self.DelChars(openLength, 0, luaSyntaxHighlightScripts, rustAnalyzer).await;
+                shifts.push((last, lineEnd - closeLength, -(closeLength as isize)));
+                shifts.push((first, at, -(openLength as isize)));
+            } else {
+                self.cursor = (last, lineEnd);
+                self.InsertChars(format!(" {close}"), luaSyntaxHighlightScripts, rustAnalyzer).await;
+                self.cursor = (first, at);
+                self.InsertChars(format!("{open} "), luaSyntaxHighlightScripts, rustAnalyzer).await;
+                shifts.push((last, lineEnd, close.len() as isize + 1));
+                shifts.push((first, at, open.len() as isize + 1));
+            }
+        }
+        self.MergeChanges(undoDepth);
+
+        // anything at the very start of a line stays there, so a selection of whole lines still covers the comments
+        let Shift = |position: (usize, usize)| {
+            let change: isize = shifts.iter()
+                .filter(|(line, column, _)| *line == position.0 && position.1 >= *column && position.1 > 0)
+                .map(|(_, column, change)| (*change).max(*column as isize - position.1 as isize))
+                .sum();
+            (position.0, (position.1 as isize + change).max(0) as usize)
+        };
+        self.cursor = Shift(cursor);
+        self.cursorEnd = Shift(cursorEnd);
+        self.highlighting = highlighting;
+        true
+    }
+
+    // the lines the selection covers (a selection ending at the start of a line doesn't include it)
+    pub fn SelectedLines (&self) -> (usize, usize) {
+        let (start, end) = if self.cursor <= self.cursorEnd {  (self.cursor, self.cursorEnd)  } else {  (self.cursorEnd, self.cursor)  };
+        if end.0 > start.0 && end.1 == 0 {  (start.0, end.0 - 1)  }
+        else {  (start.0, end.0)  }
     }
 
     pub fn CursorUp (&mut self, highlight: bool) {
@@ -809,10 +1208,9 @@ impl CodeTab {
         ) as usize;
         self.mouseScrolled = 0;
         self.mouseScrolledFlt = 0.0;
-        self.cursor = (
-            self.cursor.0.saturating_sub(1),
-            self.cursor.1
-        );
+        self.cursor =
+            if self.softWrap {  self.StepRow(self.cursor, true)  }
+            else {  (self.StepVisibleLines(self.cursor.0, 1, true), self.cursor.1)  };
     }
 
     pub fn CursorDown (&mut self, highlight: bool) {
@@ -832,13 +1230,22 @@ impl CodeTab {
         ) as usize;
         self.mouseScrolled = 0;
         self.mouseScrolledFlt = 0.0;
-        self.cursor = (
-            std::cmp::min(
-                self.cursor.0.saturating_add(1),
-                self.lines.len() - 1
-            ),
-            self.cursor.1
-        );
+        self.cursor =
+            if self.softWrap {  self.StepRow(self.cursor, false)  }
+            else {  (self.StepVisibleLines(self.cursor.0, 1, false), self.cursor.1)  };
+    }
+
+    // moves the cursor and the view together by a page, so the cursor stays in the same spot on screen
+    pub fn PageUp (&mut self, pageHeight: usize, highlight: bool) {
+        self.CursorUp(highlight);  // deals with the selection and any mouse scrolling
+        self.cursor.0 = self.StepVisibleLines(self.cursor.0, pageHeight.saturating_sub(1), true);
+        self.scrolled = self.StepVisibleLines(self.scrolled, pageHeight, true);
+    }
+
+    pub fn PageDown (&mut self, pageHeight: usize, highlight: bool) {
+        self.CursorDown(highlight);
+        self.cursor.0 = self.StepVisibleLines(self.cursor.0, pageHeight.saturating_sub(1), false);
+        self.scrolled = self.StepVisibleLines(self.scrolled, pageHeight, false);
     }
 
     pub fn JumpCursor (&mut self, position: usize, scalar01: usize) {
@@ -888,6 +1295,7 @@ impl CodeTab {
 
         if length == 0 {
             self.lines.insert(self.cursor.0, "".to_string());
+            self.LinesInserted(self.cursor.0, 1);
             let mut lineTokensWrite = self.lineTokens.write();
             lineTokensWrite[self.cursor.0].clear();
             lineTokensWrite.insert(self.cursor.0, vec!());
@@ -897,8 +1305,10 @@ impl CodeTab {
             self.CreateScopeThread(self.cursor.0, self.cursor.0 + 1, rustAnalyzer);
             //(self.scopes, self.scopeJumps, self.linearScopes) = GenerateScopes(&self.lineTokens, &self.lineTokenFlags, &mut self.outlineKeywords);
 
-            self.cursor.1 = 0;
+            // stepping down from the end of the line so all of its rows are passed when it's wrapped
+            self.cursor.1 = self.lines[self.cursor.0].len();
             self.CursorDown(highlight);
+            self.cursor.1 = 0;
             return;
         }
 
@@ -908,10 +1318,13 @@ impl CodeTab {
             length
         ));
 
+        // the new line would otherwise go inside a fold on this line
+        self.Unfold(self.cursor.0);
         self.lines.insert(
             self.cursor.0 + 1,
             rightSide,
         );
+        self.LinesInserted(self.cursor.0 + 1, 1);
         self.lineTokens.write().insert(
             self.cursor.0 + 1,
             vec!(),
@@ -920,14 +1333,241 @@ impl CodeTab {
         
         self.RecalcTokens(self.cursor.0, 0, luaSyntaxHighlightScripts).await;
         self.RecalcTokens(self.cursor.0 + 1, 0, luaSyntaxHighlightScripts).await;
-        self.cursor.1 = 0;
+        self.cursor.1 = self.lines[self.cursor.0].len();
         self.CursorDown(highlight);
+        self.cursor.1 = 0;
 
         self.CreateScopeThread(self.cursor.0, self.cursor.0 + 1, rustAnalyzer);
         //(self.scopes, self.scopeJumps, self.linearScopes) = GenerateScopes(&self.lineTokens, &self.lineTokenFlags, &mut self.outlineKeywords);
 
     }
 
+    // how many levels the line after this one indents by, going off of the brackets left open on it
+    // (strings and comments are skipped) and whether the next line starts by closing one
+    fn BracketIndentChange (&self, line: usize, nextLine: &str) -> isize {
+        let lineTokens = self.lineTokens.read();
+        let lineTokenFlags = self.lineTokenFlags.read();
+        let mut unclosed = 0usize;
+        for (index, token) in lineTokens.get(line).map(Vec::as_slice).unwrap_or_default().iter().enumerate() {
+            if IsQuoted(token, lineTokenFlags.get(line).and_then(|flags| flags.get(index))) {  continue;  }
+            match token.text.as_str() {
+                "{" | "(" | "[" => unclosed += 1,
+                // closing brackets from earlier lines were already dedented for
+                "}" | ")" | "]" => unclosed = unclosed.saturating_sub(1),
+                _ => {},
+            }
+        }
+        let closes = nextLine.trim_start().starts_with(['}', ')', ']']);
+        (unclosed > 0) as isize - closes as isize
+    }
+
+    // the indentation a new line after this one starts with; the language's script can give its own rules
+    pub fn NewLineIndent (&self, line: usize, nextLine: &str, luaSyntaxHighlightScripts: &LuaScripts) -> String {
+        let previous = &self.lines[line];
+        let ending = self.fileName.split('.').next_back().unwrap_or("");
+        let scriptChange = luaSyntaxHighlightScripts.lock()
+            .get(&Languages::FromExtension(ending))
+            .and_then(|script| script.getIndentChange.as_ref())
+            .and_then(|function| function.call::<Option <isize>>((previous.clone(), nextLine.to_string())).ok().flatten());
+        let change = scriptChange.unwrap_or_else(|| self.BracketIndentChange(line, nextLine));
+
+        let mut indent = LeadingWhitespace(previous).to_string();
+        for _ in 0..change.max(0) {
+            indent.push_str(&self.Indent());
+        }
+        for _ in 0..(-change).max(0) {
+            if indent.ends_with('\t') {
+                indent.pop();
+            } else {
+                let spaces = indent.len() - indent.trim_end_matches(' ').len();
+                indent.truncate(indent.len() - std::cmp::min(spaces, self.tabWidth));
+            }
+        }
+        indent
+    }
+
+    // shifts a pasted block over to the cursor line's indent, keeping the lines' indents relative to each other
+    // (the first line is left alone when it's going in after code on the line)
+    pub fn ReindentPaste (&self, text: &str) -> String {
+        let pasted: Vec <&str> = text.split('\n').collect();
+        if pasted.len() < 2 {  return text.to_string();  }
+        let target = LeadingWhitespace(&self.lines[self.cursor.0]);
+        let atIndent = self.cursor.1 <= target.len();
+
+        // a first line without any indent was copied from partway through its line, so it doesn't say anything
+        let skip = LeadingWhitespace(pasted[0]).is_empty() as usize;
+        let common = pasted.iter()
+            .skip(skip)
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| LeadingWhitespace(line).len())
+            .min()
+            .unwrap_or(0);
+        let Strip = |line: &str| line.get(std::cmp::min(common, LeadingWhitespace(line).len())..).unwrap_or("").to_string();
+
+        let mut lines = vec![if atIndent {  Strip(pasted[0])  } else {  pasted[0].to_string()  }];
+        for line in &pasted[1..] {
+            if line.trim().is_empty() {
+                lines.push(String::new());
+            } else {
+                lines.push(format!("{target}{}", Strip(line)));
+            }
+        }
+        lines.join("\n")
+    }
+
+    // breaks the line like LineBreakIn, then indents the new line (dropping any whitespace that was after the cursor)
+    // between a pair of brackets, the closing one goes down another line back at the outer indent
+    pub async fn LineBreakIndented <'a> (&mut self,
+                                         luaSyntaxHighlightScripts: &LuaScripts,
+                                         rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let undoDepth = self.changeBuffer.len();
+        self.LineBreakIn(false, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        let line = self.cursor.0;
+
+        let whitespace = LeadingWhitespace(&self.lines[line]).len();
+        if whitespace > 0 {
+            self.cursorEnd = (line, 0);
+            self.cursor = (line, whitespace);
+            self.highlighting = true;
+            self.DelChars(0, 0, luaSyntaxHighlightScripts, rustAnalyzer).await;
+            self.highlighting = false;
+        }
+
+        let opened = self.lines[line - 1].trim_end().chars().next_back();
+        let closed = self.lines[line].chars().next();
+        if matches!((opened, closed), (Some('{'), Some('}')) | (Some('('), Some(')')) | (Some('['), Some(']'))) {
+            let outer = LeadingWhitespace(&self.lines[line - 1]).to_string();
+            self.cursor = (line, 0);
+            if !outer.is_empty() {
+                self.InsertChars(outer, luaSyntaxHighlightScripts, rustAnalyzer).await;
+            }
+            self.cursor = (line, 0);
+            self.LineBreakIn(false, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        }
+
+        let next = self.lines[line].clone();
+        let indent = self.NewLineIndent(line - 1, &next, luaSyntaxHighlightScripts);
+        self.cursor = (line, 0);
+        if !indent.is_empty() {
+            self.InsertChars(indent, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        }
+        self.MergeChanges(undoDepth);
+    }
+
+    // the brackets and quotes that get closed automatically in the tab's language
+    pub fn Pairs (&self, luaSyntaxHighlightScripts: &LuaScripts) -> Vec <(char, char)> {
+        let ending = self.fileName.split('.').next_back().unwrap_or("");
+        luaSyntaxHighlightScripts.lock()
+            .get(&Languages::FromExtension(ending))
+            .map(|script| script.pairs.clone())
+            .unwrap_or(DEFAULT_PAIRS.to_vec())
+    }
+
+    // goes off of the flags of the token just before the position (or the end of the last line when it's at the start)
+    pub fn InStringOrComment (&self, position: (usize, usize)) -> bool {
+        let lineTokens = self.lineTokens.read();
+        let lineTokenFlags = self.lineTokenFlags.read();
+        let Quoted = |flags: Option <&Vec <LineTokenFlags>>| {
+            flags.is_some_and(|flags| flags.contains(&LineTokenFlags::String) || flags.contains(&LineTokenFlags::Comment))
+        };
+
+        let tokens = lineTokens.get(position.0).map(Vec::as_slice).unwrap_or_default();
+        let mut end = 0;
+        let mut before = None;
+        for (index, token) in tokens.iter().enumerate() {
+            end += token.text.len();
+            if end > position.1 {  break;  }
+            before = Some(index);
+        }
+        match before {
+            // a line comment runs to the end of the line
+            Some(index) => matches!(tokens[index].token, TokenType::Comment) ||
+                Quoted(lineTokenFlags.get(position.0).and_then(|flags| flags.get(index))),
+            None => position.0 > 0 && Quoted(lineTokenFlags.get(position.0 - 1).and_then(|flags| flags.last())),
+        }
+    }
+
+    // the characters on either side of the cursor
+    fn CharsAroundCursor (&self) -> (Option <char>, Option <char>) {
+        let line = &self.lines[self.cursor.0];
+        (
+            line.get(..self.cursor.1).and_then(|text| text.chars().next_back()),
+            line.get(self.cursor.1..).and_then(|text| text.chars().next()),
+        )
+    }
+
+    // the pair the cursor sits inside of, if there's nothing between the opener and its closer
+    pub fn InEmptyPair (&self, luaSyntaxHighlightScripts: &LuaScripts) -> Option <(char, char)> {
+        if self.highlighting {  return None;  }
+        let (Some(previous), Some(next)) = self.CharsAroundCursor() else {  return None;  };
+        self.Pairs(luaSyntaxHighlightScripts).into_iter().find(|pair| *pair == (previous, next))
+    }
+
+    // types a character, pairing brackets and quotes: an opener also gets its closer (unless it's in a string or
+    // comment, or right before a word), typing the closer that's already next just steps over it, and an opener
+    // with text selected wraps the selection in the pair
+    pub async fn TypeChar <'a> (&mut self,
+                                chr: char,
+                                luaSyntaxHighlightScripts: &LuaScripts,
+                                rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let pairs = self.Pairs(luaSyntaxHighlightScripts);
+        let closer = pairs.iter().find(|(open, _)| *open == chr).map(|(_, close)| *close);
+        let selected = self.highlighting && self.cursor != self.cursorEnd;
+        if let Some(close) = closer && selected {
+            self.WrapSelection(chr, close, luaSyntaxHighlightScripts, rustAnalyzer).await;
+            return;
+        }
+
+        let (previous, next) = self.CharsAroundCursor();
+        let IsCloser = |chr: char| pairs.iter().any(|(_, close)| *close == chr);
+        if !selected && next == Some(chr) && IsCloser(chr) {
+            self.highlighting = false;
+            self.cursor.1 += chr.len_utf8();
+            return;
+        }
+
+        let paired = closer.is_some_and(|close| {
+            !selected &&
+            next.is_none_or(|next| next.is_whitespace() || IsCloser(next) || matches!(next, ',' | ';')) &&
+            // a quote straight after a word is more likely an apostrophe or a prefix
+            (close != chr || previous.is_none_or(|previous| !previous.is_alphanumeric())) &&
+            !self.InStringOrComment(self.cursor)
+        });
+        match closer {
+            Some(close) if paired => {
+                self.InsertChars(format!("{chr}{close}"), luaSyntaxHighlightScripts, rustAnalyzer).await;
+                self.cursor.1 -= close.len_utf8();
+            },
+            _ => self.InsertChars(chr.to_string(), luaSyntaxHighlightScripts, rustAnalyzer).await,
+        }
+    }
+
+    // puts the pair around the selection as one undo group, keeping the same text selected
+    async fn WrapSelection <'a> (&mut self,
+                                 open: char,
+                                 close: char,
+                                 luaSyntaxHighlightScripts: &LuaScripts,
+                                 rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let undoDepth = self.changeBuffer.len();
+        let forward = self.cursorEnd <= self.cursor;
+        let (start, end) = if forward {  (self.cursorEnd, self.cursor)  } else {  (self.cursor, self.cursorEnd)  };
+        self.highlighting = false;
+
+        self.cursor = end;
+        self.InsertChars(close.to_string(), luaSyntaxHighlightScripts, rustAnalyzer).await;
+        self.cursor = start;
+        self.InsertChars(open.to_string(), luaSyntaxHighlightScripts, rustAnalyzer).await;
+        self.MergeChanges(undoDepth);
+
+        let start = (start.0, start.1 + open.len_utf8());
+        let end = if end.0 == start.0 {  (end.0, end.1 + open.len_utf8())  } else {  end  };
+        (self.cursorEnd, self.cursor) = if forward {  (start, end)  } else {  (end, start)  };
+        self.highlighting = true;
+    }
+
     pub async fn HandleHighlight <'a> (&mut self,
                                        changeBuff: &mut Vec <Edits::Edit>,
                                        luaSyntaxHighlightScripts: &LuaScripts,
@@ -969,6 +1609,7 @@ impl CodeTab {
                     );
                     accumulative.push('\n');
                     self.lines.remove(self.cursorEnd.0 + 1);
+                    self.LinesRemoved(self.cursorEnd.0 + 1, 1);
                     self.lineTokens.write().remove(self.cursorEnd.0 + 1);
                     self.lineTokenFlags.write().remove(self.cursorEnd.0 + 1);
                 }
@@ -989,6 +1630,7 @@ impl CodeTab {
                 self.lines[self.cursorEnd.0].push_str(nextLine.as_str());
                 self.RecalcTokens(self.cursorEnd.0, 0, luaSyntaxHighlightScripts).await;
                 self.lines.remove(self.cursorEnd.0 + 1);
+                self.LinesRemoved(self.cursorEnd.0 + 1, 1);
                 self.lineTokens.write().remove(self.cursorEnd.0 + 1);
                 self.lineTokenFlags.write().remove(self.cursorEnd.0 + 1);
 
@@ -1115,6 +1757,7 @@ impl CodeTab {
             let remaining = self.lines[self.cursor.0].split_off(self.cursor.1);
 
             self.lines.remove(self.cursor.0);
+            self.LinesRemoved(self.cursor.0, 1);
             self.lineTokens.write().remove(self.cursor.0);
             self.lineTokenFlags.write().remove(self.cursor.0);
             self.cursor.0 = self.cursor.0.saturating_sub(1);
@@ -1210,9 +1853,13 @@ impl CodeTab {
     ) {
         self.saved = false;
         if lineNumber >= self.lines.len() {  return;  }
+        self.unmatchedBrackets = None;
+        self.wrapCache.get_mut().remove(&lineNumber);
 
+        // a wrapped line can take up more or fewer rows after the edit, moving everything below it
+        if self.softWrap {  self.ClearRenderCache();  }
         // proper error handling actually fixed it.... who could have imagined?
-        if let Some(cacheReset) = self.resetCache.get_mut(lineNumber.saturating_sub(self.lastScroll)) {
+        else if let Some(cacheReset) = self.resetCache.get_mut(lineNumber.saturating_sub(self.lastScroll)) {
             *cacheReset = true;
         }
 
@@ -1478,25 +2125,26 @@ impl CodeTab {
                           charIndexStart: usize,
                           tokenCharCount: usize,
                           highlight: (usize, usize),
-                          lineInfo: (usize, usize, bool)
+                          lineInfo: (usize, usize, bool),
+                          selection: ColorType,
     ) -> Vec <Colored> {
         // checking if the highlight range falls within the word
         if highlight.0 < charIndexStart+tokenCharCount && highlight.1 > charIndexStart {
             return
                 if highlight.0 <= charIndexStart && highlight.1 >= charIndexStart + tokenCharCount {
                     CodeTab::CheckUnderline(vec![
-                        color![text, OnBrightBlack],
+                        text.Colorize(selection),
                     ], lineInfo)  // full token
                 } else if highlight.0 <= charIndexStart && highlight.1 >= charIndexStart {  // if highlight.1 was greater, the previous statement would have caught it
                     let split = text.Split(highlight.1 - charIndexStart);
-                    CodeTab::CheckUnderline(vec![color![split.0, OnBrightBlack], split.1], lineInfo)  // highlight(start -> point) + point -> end
+                    CodeTab::CheckUnderline(vec![split.0.Colorize(selection), split.1], lineInfo)  // highlight(start -> point) + point -> end
                 } else if highlight.0 > charIndexStart && highlight.1 >= charIndexStart {
                     let split = text.Split(highlight.0 - charIndexStart);
-                    CodeTab::CheckUnderline(vec![split.0, color![split.1, OnBrightBlack]], lineInfo)  // start -> point + highlight(point -> end)
+                    CodeTab::CheckUnderline(vec![split.0, split.1.Colorize(selection)], lineInfo)  // start -> point + highlight(point -> end)
                 } else {
                     let split = text.Split(highlight.0 - charIndexStart);
                     let split2 = split.1.Split(highlight.1 - highlight.0 - charIndexStart);
-                    CodeTab::CheckUnderline(vec![split.0, color![split2.0, OnBrightBlack], split2.1], lineInfo)  // start -> point1 + highlight(point1 -> point2) + point2 -> end
+                    CodeTab::CheckUnderline(vec![split.0, split2.0.Colorize(selection), split2.1], lineInfo)  // start -> point1 + highlight(point1 -> point2) + point2 -> end
             };
         } CodeTab::CheckUnderline(vec![text], lineInfo)  // not a valid highlight
     }
@@ -1507,6 +2155,7 @@ impl CodeTab {
                                     suggested: &str,  // the suggested auto-complete (for inline rendering)
                                     padding: u16,
     ) -> Vec <Span> {
+        self.RevealCursor();
         self.UpdateScrollingRender(area);
 
         // the cumulative render
@@ -1526,26 +2175,50 @@ impl CodeTab {
         // iterating over every line one by one
         //    -- (maybe change this to a buffer that can be shifted as it's moved around)
         let mut i = 0;
-        // the magic number of 11 is the size of the code-tab window (kinda ugly having it here but I'm too lazy to move it)
-        let windowHeight = area.height as usize - 11;
+        let windowHeight = CodeRows(area.height);
+        let width = area.width - padding - 2 - maxLineNumberSize as u16;
         // checking the reset and scroll cache should fix any memory leaks from edge cases
         let currentMouse = (self.cursor.0, self.cursor.1, self.cursorEnd.0, self.cursorEnd.1);
+        let cursorMoved = self.lastMouse != currentMouse || self.wrapWidth != width as usize - 1;
         if self.resetCache.len() != windowHeight ||
            self.scrollCache.len() != windowHeight ||
-           self.lastMouse != currentMouse
+           self.lastMouse != currentMouse ||
+           self.wrapWidth != width as usize - 1 ||
+           // the rows don't line up with the lines when folded or wrapped
+           self.shiftCache != 0 && (!self.folds.is_empty() || self.softWrap)
         {
             self.resetCache = vec![true; windowHeight];
             self.scrollCache.clear();
             self.shiftCache = 0;
             self.lastMouse = currentMouse;
+            self.wrapWidth = width as usize - 1;  // leaving a column for the cursor
         } else {
             self.UpdateCache();
         }
-        for lineNumber in scroll..(scroll + windowHeight) {
-            if !self.resetCache[lineNumber - scroll] {
-                tabRender.push(self.scrollCache[lineNumber - scroll].clone());
+        // only following the cursor once it's moved, so scrolling sideways away from it sticks
+        if cursorMoved {  self.FollowCursorSideways();  }
+        // only worked out when there's something to redraw since it goes over the whole file
+        let bracketMarks =
+            if self.resetCache.contains(&true) {  self.BracketMarks(editingCode, colorMode)  }
+            else {  vec![]  };
+        let foldable: Vec <usize> =
+            if self.resetCache.contains(&true) {  self.FoldRanges().iter().map(|(start, ..)| *start).collect()  }
+            else {  vec![]  };
+        // the lines hidden in folds are skipped over, so each row is the next visible line (or the next row of a wrapped one)
+        let mut lineNumber = self.VisibleLine(scroll);
+        let mut wrapRow = 0;
+        for row in 0..windowHeight {
+            if row > 0 {
+                if wrapRow + 1 < self.RowCount(lineNumber) {  wrapRow += 1;  }
+                else {
+                    lineNumber = self.NextVisibleLine(lineNumber);
+                    wrapRow = 0;
+                }
+            }
+            if !self.resetCache[row] {
+                tabRender.push(self.scrollCache[row].clone());
                 continue;
-            } self.resetCache[lineNumber - scroll] = false;
+            } self.resetCache[row] = false;
 
             if lineNumber >= self.lines.len() {
                 let mut text = " ".repeat(maxLineNumberSize - 2);
@@ -1559,17 +2232,49 @@ impl CodeTab {
                 continue;
             }
             // getting the text for the line number
-            let lineNumberText = self.GetLineNumberText(lineNumber, maxLineNumberSize);
+            let folded = self.FoldedLength(lineNumber);
+            let marker =
+                if folded.is_some() {  '▸'  }
+                else if foldable.contains(&lineNumber) {  '▾'  }
+                else {  ':'  };
+            let lineNumberText = self.GetLineNumberText(lineNumber, maxLineNumberSize, marker);
             let colors =
-                if lineNumber == self.cursor.0 {  vec![ColorType::Red, ColorType::Underline, ColorType::Bold]  }
-                else {  vec![ColorType::White, ColorType::Italic]  };  // no additional coloring
+                if lineNumber == self.cursor.0 {  vec![
+                    colorMode.colorBindings.gutterCursor.Get(&colorMode.colorType), ColorType::Underline, ColorType::Bold
+                ]  }
+                else {  vec![colorMode.colorBindings.gutter.Get(&colorMode.colorType), ColorType::Italic]  };  // no additional coloring
 
             let mut lineText = vec![];
-            lineText.push(lineNumberText.Colorizes(colors));
+            // only the first row of a wrapped line gets its number
+            if wrapRow > 0 {  lineText.push(color![" ".repeat(maxLineNumberSize)]);  }
+            else {  lineText.push(lineNumberText.Colorizes(colors));  }
 
             let mut charIndex = 0;
-            let width = area.width - padding - 2 - maxLineNumberSize as u16;
-            self.RenderSlice(&mut charIndex, &mut lineText, lineNumber, colorMode, editingCode, width as usize, suggested);
+            let marks: Vec <(usize, ColorType)> = bracketMarks.iter()
+                .filter(|(position, _)| position.0 == lineNumber)
+                .map(|(position, color)| (position.1, *color))
+                .collect();
+            // a wrapped line is drawn whole and cut down to the row, otherwise it's only drawn up to the right
+            // edge of the view (and a column past it, so the cut knows there's more) before being cut down
+            let limit =
+                if self.softWrap {  usize::MAX / 2  }
+                else {  self.horizontalScroll + width as usize  };
+            let mut rendered = lineText.clone();
+            let extras = SliceExtras {  width: limit, suggested, marks: &marks  };
+            self.RenderSlice(&mut charIndex, &mut rendered, lineNumber, colorMode, editingCode, &extras);
+            let rendered = rendered.split_off(lineText.len());
+            let (rowText, rowWidth) =
+                if self.softWrap {  self.WrappedRow(rendered, lineNumber, wrapRow, editingCode)  }
+                else {  self.ScrolledRow(rendered)  };
+            lineText.extend(rowText);
+            charIndex = rowWidth;
+            if let Some(hidden) = folded && wrapRow + 1 == self.RowCount(lineNumber) {
+                let summary = format!(" ... {} lines", hidden);
+                if charIndex + summary.len() < width as usize {
+                    charIndex += summary.len();
+                    lineText.push(color![summary, BrightBlack, Italic]);
+                }
+            }
 
             self.HandleScrollBar(&mut lineText, area, width, charIndex, i);
 
@@ -1615,8 +2320,8 @@ impl CodeTab {
         // the edge
         let scrollPercent = f64::min(std::cmp::max(
             self.scrolled as isize + self.mouseScrolled, 0
-        ) as f64 / self.lines.len() as f64 * (area.height as f64 - 10.0),
-                                     area.height as f64 - 12.0
+        ) as f64 / self.lines.len() as f64 * CodeRows(area.height) as f64,
+                                     CodeRows(area.height).saturating_sub(1) as f64
         ) as usize;
 
         let pinnedPercents = self.GetPinnedPercents(area);
@@ -1648,8 +2353,8 @@ impl CodeTab {
         for line in &self.pinedLines {
             let scrollPercent = f64::min(std::cmp::max(
                 line.0, 0
-            ) as f64 / self.lines.len() as f64 * (area.height as f64 - 10.0),
-                                         area.height as f64 - 12.0
+            ) as f64 / self.lines.len() as f64 * CodeRows(area.height) as f64,
+                                         CodeRows(area.height).saturating_sub(1) as f64
             ) as usize;
             pinned.push((scrollPercent, line.1));
         } pinned
@@ -1661,67 +2366,79 @@ impl CodeTab {
                     lineNumber: usize,
                     colorMode: &Colors::ColorMode,
                     editingCode: bool,
-                    width: usize,
-                    suggested: &str
+                    extras: &SliceExtras,
     ) {
+        let SliceExtras {  width, suggested, marks  } = *extras;
+        // everything's worked out in drawn columns, so tabs can be spread out to their tab stops
+        let Column = |index: usize| if index == LINE_END {  LINE_END  } else {  self.DisplayColumn(lineNumber, index)  };
         let highlighted = self.CheckHighlight(lineNumber);
+        let highlighted = (Column(highlighted.0), Column(highlighted.1));
+        let cursorColumn = if lineNumber == self.cursor.0 {  Column(self.cursor.1)  } else {  0  };
+        let selection = colorMode.colorBindings.selection.Get(&colorMode.colorType);
         let tokensRead = self.lineTokens.read();
+        let mut column = 0;  // the same as charIndex, but without the cursor
+        let mut lineIndex = 0;  // where the token starts in the line
         for token in &tokensRead[lineNumber] {
-            let tokenCharCount = token.text.chars().count();
+            let mark = marks.iter().find(|(markIndex, _)| *markIndex == lineIndex).map(|(_, color)| *color);
+            let Mark = |colored: Colored| match mark {
+                Some(color) => colored.Colorize(color),
+                None => colored,
+            };
+            lineIndex += token.text.len();
+            let text = ExpandTabs(&token.text, column, self.tabWidth);
+            let tokenCharCount = text.chars().count();
+            column += tokenCharCount;
 
             // rendering the cursor and the split half's
             if editingCode &&
                lineNumber == self.cursor.0 &&
-               self.cursor.1 >= *charIndex &&
-               self.cursor.1 < *charIndex+tokenCharCount
+               cursorColumn >= *charIndex &&
+               cursorColumn < *charIndex+tokenCharCount
             {
-                let middle = self.cursor.1 - *charIndex;
-                let left = self.GenerateColor(&token.token,
-                                                       token.text[0..middle].to_string(),
+                let middle = cursorColumn - *charIndex;
+                let left = Mark(self.GenerateColor(&token.token,
+                                                       text[0..middle].to_string(),
                                                        colorMode
-                );
-                let right = self.GenerateColor(&token.token,
-                                                        token.text[middle..].to_string(),
+                ));
+                let right = Mark(self.GenerateColor(&token.token,
+                                                        text[middle..].to_string(),
                                                         colorMode
-                );
+                ));
 
                 lineText.append(&mut CodeTab::HighlightText(left,
                                                             *charIndex,
                                                             middle,
                                                             highlighted,
-                                                            (lineNumber, self.cursor.0, editingCode)
+                                                            (lineNumber, self.cursor.0, editingCode),
+                                                            selection,
                 ));
                 lineText.push(color!["|"]);
                 lineText.append(&mut CodeTab::HighlightText(right,
                                                             *charIndex+middle,
                                                             tokenCharCount-middle,
                                                             highlighted,
-                                                            (lineNumber, self.cursor.0, editingCode)
+                                                            (lineNumber, self.cursor.0, editingCode),
+                                                            selection,
                 ));
                 *charIndex += 1;
             } else {
                 lineText.append(&mut CodeTab::HighlightText(
-                    self.GenerateColor(&token.token, token.text.clone(), colorMode),
+                    Mark(self.GenerateColor(&token.token, text, colorMode)),
                     *charIndex,
                     tokenCharCount,
                     highlighted,
                     (lineNumber, self.cursor.0, editingCode),
+                    selection,
                 ));
             }
 
             *charIndex += tokenCharCount;
 
-            // checking the current size; making sure the text is pruned to the edge
-            let overShoot = *charIndex as isize - width as isize;
-            if overShoot < 0 {  continue;  }
-            while *charIndex >= width {
-                let token = lineText.pop().unwrap_or_default();
-                let tokenSize = token.GetSize();
-                *charIndex -= tokenSize;
-            } break;
+            // stopping once it's past the edge (the caller cuts it down to the exact column)
+            if *charIndex >= width {  break;  }
         }
 
-        if self.cursor.0 == lineNumber && self.cursor.1 >= *charIndex && editingCode && *charIndex+1 < width {
+        if self.cursor.0 == lineNumber && cursorColumn >= *charIndex && editingCode && *charIndex+1 < width {
             let padded = lineText[lineText.len() - 1].GetSize();
             lineText.push(color!["|"]);
             *charIndex += 1;
@@ -1740,19 +2457,19 @@ impl CodeTab {
     ) -> (usize, usize) {
         if lineNumber == start.0 {
             if start.0 == end.0 {  (start.1, end.1)  }
-            else {  (start.1, 999)  }
+            else {  (start.1, LINE_END)  }
         } else if lineNumber == end.0 {
             (0, end.1)
         } else if lineNumber > start.0 && lineNumber < end.0 {
-            (0, 999)
+            (0, LINE_END)
         } else {
-            (999, 999)
+            (LINE_END, LINE_END)
         }
     }
 
     // returns start, end
     fn CheckHighlight (&self, lineNumber: usize) -> (usize, usize) {
-        if !self.highlighting {  return (999, 999);  }
+        if !self.highlighting {  return (LINE_END, LINE_END);  }
         if self.cursor.0 == self.cursorEnd.0 {
             if self.cursor.1 < self.cursorEnd.1 {
                 // left
@@ -1770,13 +2487,14 @@ impl CodeTab {
         }
     }
 
-    fn GetLineNumberText (&self, lineNumber: usize, maxSize: usize) -> String {
+    // the marker takes the place of the colon (showing whether the line is folded or can be)
+    fn GetLineNumberText (&self, lineNumber: usize, maxSize: usize, marker: char) -> String {
         // choosing between the line number and lines from cursor
         let mut lineNumberText =
-            if self.cursor.0 == lineNumber {  format!("{}: ", lineNumber + 1)  }  // current line number
-            else {  format!("{}: ", (lineNumber as isize - self.cursor.0 as isize)
-                        .unsigned_abs())  };  // distance from cursor
-        lineNumberText.insert_str(0, &" ".repeat(maxSize - lineNumberText.len()));
+            if self.cursor.0 == lineNumber {  format!("{}{} ", lineNumber + 1, marker)  }  // current line number
+            else {  format!("{}{} ", (lineNumber as isize - self.cursor.0 as isize)
+                        .unsigned_abs(), marker)  };  // distance from cursor
+        lineNumberText.insert_str(0, &" ".repeat(maxSize - lineNumberText.chars().count()));
         lineNumberText
     }
 
@@ -1788,11 +2506,15 @@ impl CodeTab {
             .as_millis();
         if currentTime.saturating_sub(self.pauseScroll) <= 125 {  return;  }
 
-        if self.scrolled + SCROLL_BOUNDS >= self.cursor.0 {
+        // where the cursor would be if the folded lines above it (but below the top) weren't there, and wrapped ones took a single row
+        let cursorRow =
+            if self.cursor.0 > self.scrolled {  self.scrolled + self.RowsBetween(self.scrolled, self.cursor.0) + self.RowOf(self.cursor)  }
+            else {  self.cursor.0  };
+        if self.scrolled + SCROLL_BOUNDS >= cursorRow {
             self.ScrollBranchOne(area);
         }
-        if (self.scrolled + area.height as usize - 12)
-            .saturating_sub(SCROLL_BOUNDS) <= self.cursor.0
+        if (self.scrolled + CodeRows(area.height))
+            .saturating_sub(SCROLL_BOUNDS) <= cursorRow
         {
             self.ScrollBranchTwo(area);
         }
@@ -1804,14 +2526,13 @@ impl CodeTab {
             self.cursor.0 && !self.highlighting
         {
             let center = std::cmp::min(
-                self.cursor.0
-                    .saturating_sub((area.height as usize)
-                        .saturating_sub(10) / 2),
+                self.StepVisibleLines(self.cursor.0, (area.height as usize)
+                    .saturating_sub(10) / 2, true),
                 self.lines.len() - 1
             );
             self.scrolled = center;
         } else {
-            self.scrolled = self.cursor.0.saturating_sub(SCROLL_BOUNDS);
+            self.scrolled = self.StepVisibleLines(self.cursor.0, SCROLL_BOUNDS, true);
             if self.highlighting {  // making sure the highlighting doesn't scroll at light speed
                 // even though this is all embedded into async stuff (earlier in the call stack/depth)
                 // it's fine as the async-manager uses unique threads for every async tasks.
@@ -1827,15 +2548,16 @@ impl CodeTab {
             self.cursor.0 && !self.highlighting
         {
             let center = std::cmp::min(
-                self.cursor.0
-                    .saturating_sub((area.height as usize)
-                        .saturating_sub(10) / 2),
+                self.StepVisibleLines(self.cursor.0, (area.height as usize)
+                    .saturating_sub(10) / 2, true),
                 self.lines.len() - 1
             );
             self.scrolled = center;
         } else {
-            self.scrolled = (self.cursor.0 + SCROLL_BOUNDS)
-                .saturating_sub(area.height as usize - 12);
+            self.scrolled =
+                if self.softWrap {  self.TopLineFor(self.cursor, CodeRows(area.height).saturating_sub(SCROLL_BOUNDS))  }
+                else if self.folds.is_empty() {  (self.cursor.0 + SCROLL_BOUNDS).saturating_sub(CodeRows(area.height))  }
+                else {  self.StepVisibleLines(self.cursor.0, CodeRows(area.height).saturating_sub(SCROLL_BOUNDS), true)  };
             if self.highlighting {  // making sure the highlighting doesn't scroll at light speed
                 std::thread::sleep(std::time::Duration::from_millis(25));  // this.... probably needs to be better....
             }
@@ -1876,6 +2598,17 @@ impl Default for CodeTab {
              scopeGenerationHandles: vec!(),
              saved: true,
              path: String::new(),
+             tabWidth: DEFAULT_TAB_WIDTH,
+             useTabs: false,
+             folds: vec![],
+             softWrap: false,
+             wrapWidth: 0,
+             horizontalScroll: 0,
+             horizontalScrollFlt: 0.0,
+             snippet: None,
+             completions: vec![],
+             unmatchedBrackets: None,
+             wrapCache: parking_lot::Mutex::new(std::collections::HashMap::new()),
              scrollCache: vec![],
              resetCache: vec![],
              shiftCache: 0,
@@ -2005,8 +2738,10 @@ impl CodeTabs {
         }
     }
 
-    pub fn GetColoredNames (&self, onTabs: bool) -> Vec <Colored> {
+    pub fn GetColoredNames (&self, onTabs: bool, colorMode: &Colors::ColorMode) -> Vec <Colored> {
         let mut colored = vec!();
+        let tabBar = colorMode.colorBindings.tabBar.Get(&colorMode.colorType);
+        let tabBarFocused = colorMode.colorBindings.tabBarFocused.Get(&colorMode.colorType);
 
         if onTabs {
             for (index, tab) in self.tabFileNames.iter().enumerate() {
@@ -2017,16 +2752,15 @@ impl CodeTabs {
                             format!(" ({}) ", index + 1),
                             BrightYellow,
                             Bold,
-                            OnBlue,
                             Underline
-                        ]
+                        ].Colorize(tabBarFocused)
                     );
                     colored.push(
-                        color![tab, White, Italic, OnBlue, Underline]
+                        color![tab, White, Italic, Underline].Colorize(tabBarFocused)
                     );
-                    colored.push(color![savedText.clone(), OnBlue, Underline]);
+                    colored.push(color![savedText.clone(), Underline].Colorize(tabBarFocused));
                     colored.push(
-                        color![" |", White, Bold, OnBlue, Underline]
+                        color![" |", White, Bold, Underline].Colorize(tabBarFocused)
                     );
                     continue;
                 }
@@ -2048,14 +2782,14 @@ impl CodeTabs {
             let savedText = self.GetSavedText(index);
             if index == self.currentTab {
                 colored.push(
-                    color![format!(" ({}) ", index + 1), BrightYellow, Bold, OnBrightBlack]
+                    color![format!(" ({}) ", index + 1), BrightYellow, Bold].Colorize(tabBar)
                 );
                 colored.push(
-                    color![tab, White, Italic, OnBrightBlack]
+                    color![tab, White, Italic].Colorize(tabBar)
                 );
-                colored.push(color![savedText.clone(), OnBrightBlack]);
+                colored.push(savedText.Colorize(tabBar));
                 colored.push(
-                    color![" |", White, Bold, OnBrightBlack]
+                    color![" |", White, Bold].Colorize(tabBar)
                 );
                 continue;
             }
@@ -2074,3 +2808,93 @@ impl CodeTabs {
         colored
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macros::load_lua_script;
+
+    fn Tab (text: &str) -> CodeTab {
+        let tab = CodeTab {  lines: text.split('\n').map(String::from).collect(), fileName: "test.txt".into(), ..Default::default()  };
+        for _ in 0..tab.lines.len() {
+            tab.lineTokens.write().push(vec![]);
+            tab.lineTokenFlags.write().push(vec![]);
+        }
+        tab
+    }
+
+    #[test]
+    fn RedoReplaysMergedGroupInOrder () -> Result <(), mlua::Error> {
+        // the script's lua instance has to live as long as the test, so it's loaded here
+        let scripts: LuaScripts = std::sync::Arc::new(parking_lot::Mutex::new(std::collections::HashMap::new()));
+        load_lua_script!(scripts, Languages::Null, "assets/nullSyntaxHighlighting.lua",);
+        let rustAnalyzer = None;
+        let mut tab = Tab("one\ntwo");
+        futures::executor::block_on(async {
+            // three edits that depend on each other, merged into one group
+            let undoDepth = tab.changeBuffer.len();
+            tab.cursor = (0, 3);
+            tab.InsertChars(String::from(" more"), &scripts, &rustAnalyzer).await;
+            tab.LineBreakIn(false, &scripts, &rustAnalyzer).await;
+            tab.InsertChars(String::from("inserted"), &scripts, &rustAnalyzer).await;
+            tab.MergeChanges(undoDepth);
+            let edited = tab.lines.clone();
+            assert_eq!(edited, ["one more", "inserted", "two"]);
+            assert_eq!(tab.changeBuffer.len(), undoDepth + 1);
+
+            tab.Undo(&scripts, &rustAnalyzer).await;
+            assert_eq!(tab.lines, ["one", "two"]);
+            tab.Redo(&scripts, &rustAnalyzer).await;
+            assert_eq!(tab.lines, edited);
+            tab.Undo(&scripts, &rustAnalyzer).await;
+            assert_eq!(tab.lines, ["one", "two"]);
+        });
+        Ok(())
+    }
+    #[test]
+    fn UnmatchedBracketsFollowEdits () -> Result <(), mlua::Error> {
+        let scripts: LuaScripts = std::sync::Arc::new(parking_lot::Mutex::new(std::collections::HashMap::new()));
+        load_lua_script!(scripts, Languages::Null, "assets/nullSyntaxHighlighting.lua",);
+        let rustAnalyzer = None;
+        let mut tab = Tab("fn a () {\n");
+        futures::executor::block_on(async {
+            for line in 0..tab.lines.len() {
+                tab.RecalcTokens(line, 0, &scripts).await;
+            }
+            assert_eq!(tab.UnmatchedBrackets(), [(0, 8)]);
+
+            // the cached set has to be redone once the closer is typed
+            tab.cursor = (1, 0);
+            tab.InsertChars(String::from("}"), &scripts, &rustAnalyzer).await;
+            assert!(tab.UnmatchedBrackets().is_empty());
+            tab.cursor = (0, 0);
+            tab.LineBreakIn(false, &scripts, &rustAnalyzer).await;
+            tab.InsertChars(String::from(")"), &scripts, &rustAnalyzer).await;
+            assert_eq!(tab.UnmatchedBrackets(), [(1, 0)]);
+        });
+        Ok(())
+    }
+    #[test]
+    fn ExpandsCompletionsAsSnippets () -> Result <(), mlua::Error> {
+        let scripts: LuaScripts = std::sync::Arc::new(parking_lot::Mutex::new(std::collections::HashMap::new()));
+        load_lua_script!(scripts, Languages::Null, "assets/nullSyntaxHighlighting.lua",);
+        let rustAnalyzer = None;
+        let mut tab = Tab("  le");
+        tab.completions = vec![Snippet {  prefix: String::from("letm"), body: String::from("let mut ${1:x} = $0;"), description: String::new()  }];
+        futures::executor::block_on(async {
+            // the typed word is swapped for the whole item, with its first stop selected
+            let undoDepth = tab.changeBuffer.len();
+            tab.cursor = (0, 4);
+            assert!(tab.ExpandSnippet(&scripts, &rustAnalyzer).await);
+            assert_eq!(tab.lines, ["  let mut x = ;"]);
+            assert_eq!((tab.cursorEnd, tab.cursor, tab.highlighting), ((0, 10), (0, 11), true));
+            assert!(tab.snippet.is_some());
+
+            assert_eq!(tab.changeBuffer.len(), undoDepth + 1);
+            tab.Undo(&scripts, &rustAnalyzer).await;
+            assert_eq!(tab.lines, ["  le"]);
+        });
+        Ok(())
+    }
+}
@@ -1,115 +1,208 @@
 
 use crate::TermRender::*;
 use crate::TokenInfo::*;
+use crate::DataManager;
+use serde_json::Value;
 
-// the types of color modes (some aren't supported by certain terminals...)
-#[derive(Debug, Default, PartialEq, Eq, Hash)]
-pub enum ColorTypes {
-    #[default] True,
-    Partial,
-    Basic
+// the color modes live with the renderer, since it quantizes rgb colors down to them
+pub use crate::TermRender::ColorTypes;
+
+// the bundled fallback theme; other themes are layered on top of this one so they don't need every token
+static DEFAULT_THEME: &str = include_str!("../assets/themes/default.json");
+// the themes built into the binary (so they're there no matter where the editor is run from)
+static BUNDLED_THEMES: [(&str, &str); 3] = [
+    ("default", DEFAULT_THEME),
+    ("gruvbox", include_str!("../assets/themes/gruvbox.json")),
+    ("solarized-dark", include_str!("../assets/themes/solarized-dark.json")),
+];
+
+// A single color which can differ between the color modes
+// (a 24-bit color isn't much help on a terminal with only 16 colors)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ThemeColor {
+    pub trueColor: ColorType,
+    pub partial: ColorType,
+    pub basic: ColorType,
+}
+
+impl ThemeColor {
+    pub fn Get (&self, colorType: &ColorTypes) -> ColorType {
+        match colorType {
+            ColorTypes::True => self.trueColor,
+            ColorTypes::Partial => self.partial,
+            ColorTypes::Basic => self.basic,
+        }
+    }
+
+    pub fn ToBackground (&self) -> ThemeColor {
+        ThemeColor {
+            trueColor: self.trueColor.ToBackground(),
+            partial: self.partial.ToBackground(),
+            basic: self.basic.ToBackground(),
+        }
+    }
+
+    // A color is either a name (or #rrggbb hex code), an [r, g, b] array, or an index into the 256 color palette
+    fn ParseColor (value: &Value) -> Option <ColorType> {
+        match value {
+            Value::String(name) => ColorType::FromName(name),
+            Value::Number(index) => Some(ColorType::Ansi(u8::try_from(index.as_u64()?).ok()?)),
+            Value::Array(channels) if channels.len() == 3 => {
+                let channel = |index: usize| u8::try_from(channels[index].as_u64()?).ok();
+                Some(ColorType::Rgb(channel(0)?, channel(1)?, channel(2)?))
+            },
+            _ => None,
+        }
+    }
+
+    // An entry is either a single color (used for every mode), or an object with "true", "256", and "16" colors
+    // Any missing modes fall back onto the higher ones first (the renderer quantizes those down), and then the lower ones
+    pub fn FromJson (value: &Value) -> Result <ThemeColor, String> {
+        if !value.is_object() {
+            let color = ThemeColor::ParseColor(value).ok_or(format!("Invalid color: {value}"))?;
+            return Ok(ThemeColor {
+                trueColor: color,
+                partial: color,
+                basic: color,
+            });
+        }
+
+        let mut colors = [None; 3];
+        for (index, key) in ["true", "256", "16"].iter().enumerate() {
+            if let Some(color) = value.get(key) {
+                colors[index] = Some(ThemeColor::ParseColor(color).ok_or(format!("Invalid color: {color}"))?);
+            }
+        }
+        let pick = |order: [usize; 3]| {
+            order.iter().find_map(|index| colors[*index]).ok_or(format!("No colors given: {value}"))
+        };
+        Ok(ThemeColor {
+            trueColor: pick([0, 1, 2])?,
+            partial: pick([1, 0, 2])?,
+            basic: pick([2, 0, 1])?,
+        })
+    }
 }
 
 #[derive(Debug)]
 pub struct ColorBindings <'a> {
+    pub name: String,
+
     // for error text
-    pub errorCol: ColorType,
-    pub suggestion: ColorType,
-    pub command: ColorType,
-    pub default: ColorType,  // for default items (usually white)
-    pub highlight: ColorType,
+    pub errorCol: ThemeColor,
+    pub suggestion: ThemeColor,
+    pub command: ThemeColor,
+    pub default: ThemeColor,  // for default items (usually white)
+    pub highlight: ThemeColor,
+    pub tabBar: ThemeColor,  // the background of the current tab
+    pub tabBarFocused: ThemeColor,  // the background of the current tab while the tab bar is selected
+    pub gutter: ThemeColor,  // the line numbers
+    pub gutterCursor: ThemeColor,  // the line number of the cursor's line
+    pub selection: ThemeColor,
+    pub matchingBracket: ThemeColor,  // behind the bracket by the cursor and its pair
 
     // for syntax highlighting
     pub syntaxHighlighting: std::collections::HashMap<(&'a TokenType, &'a ColorTypes), ColorType>,
 }
 
-// the default is truecolor; other settings are available
+// the default is the bundled default theme
 impl <'a> Default for ColorBindings <'a> {
     fn default() -> ColorBindings <'a> {
-        ColorBindings {
-            errorCol: ColorType::Red,
-            suggestion: ColorType::White,
-            command: ColorType::White,
-            default: ColorType::White,
-            highlight: ColorType::Yellow,
-
-            syntaxHighlighting: std::collections::HashMap::from([
-                ((&TokenType::Bracket, &ColorTypes::Basic), ColorType::BrightBlue),
-                ((&TokenType::SquirlyBracket, &ColorTypes::Basic), ColorType::Magenta),
-                ((&TokenType::Parentheses, &ColorTypes::Basic), ColorType::Magenta),
-                ((&TokenType::Variable, &ColorTypes::Basic), ColorType::White),
-                ((&TokenType::Member, &ColorTypes::Basic), ColorType::BrightCyan),
-                ((&TokenType::Object, &ColorTypes::Basic), ColorType::BrightRed),
-                ((&TokenType::Function, &ColorTypes::Basic), ColorType::BrightMagenta),
-                ((&TokenType::Method, &ColorTypes::Basic), ColorType::BrightBlue),
-                ((&TokenType::Number, &ColorTypes::Basic), ColorType::BrightYellow),
-                ((&TokenType::Logic, &ColorTypes::Basic), ColorType::BrightYellow),
-                ((&TokenType::Math, &ColorTypes::Basic), ColorType::BrightYellow),
-                ((&TokenType::Assignment, &ColorTypes::Basic), ColorType::BrightBlue),
-                ((&TokenType::Endl, &ColorTypes::Basic), ColorType::White),
-                ((&TokenType::Macro, &ColorTypes::Basic), ColorType::BrightMagenta),
-                ((&TokenType::Const, &ColorTypes::Basic), ColorType::Cyan),
-                ((&TokenType::Barrow, &ColorTypes::Basic), ColorType::BrightGreen),
-                ((&TokenType::Lifetime, &ColorTypes::Basic), ColorType::BrightBlue),
-                ((&TokenType::String, &ColorTypes::Basic), ColorType::Yellow),
-                ((&TokenType::Comment, &ColorTypes::Basic), ColorType::Green),
-                ((&TokenType::CommentLong, &ColorTypes::Basic), ColorType::Green),
-                ((&TokenType::Null, &ColorTypes::Basic), ColorType::White),
-                ((&TokenType::Primitive, &ColorTypes::Basic), ColorType::BrightYellow),
-                ((&TokenType::Keyword, &ColorTypes::Basic), ColorType::BrightRed),
-                ((&TokenType::Unsafe, &ColorTypes::Basic), ColorType::BrightRed),
-
-                ((&TokenType::Bracket, &ColorTypes::Partial), ColorType::BrightBlue),
-                ((&TokenType::SquirlyBracket, &ColorTypes::Partial), ColorType::Magenta),
-                ((&TokenType::Parentheses, &ColorTypes::Partial), ColorType::Magenta),
-                ((&TokenType::Variable, &ColorTypes::Partial), ColorType::White),
-                ((&TokenType::Member, &ColorTypes::Partial), ColorType::BrightCyan),
-                ((&TokenType::Object, &ColorTypes::Partial), ColorType::BrightRed),
-                ((&TokenType::Function, &ColorTypes::Partial), ColorType::BrightMagenta),
-                ((&TokenType::Method, &ColorTypes::Partial), ColorType::BrightBlue),
-                ((&TokenType::Number, &ColorTypes::Partial), ColorType::BrightYellow),
-                ((&TokenType::Logic, &ColorTypes::Partial), ColorType::BrightYellow),
-                ((&TokenType::Math, &ColorTypes::Partial), ColorType::BrightYellow),
-                ((&TokenType::Assignment, &ColorTypes::Partial), ColorType::BrightBlue),
-                ((&TokenType::Endl, &ColorTypes::Partial), ColorType::White),
-                ((&TokenType::Macro, &ColorTypes::Partial), ColorType::BrightMagenta),
-                ((&TokenType::Const, &ColorTypes::Partial), ColorType::Cyan),
-                ((&TokenType::Barrow, &ColorTypes::Partial), ColorType::BrightGreen),
-                ((&TokenType::Lifetime, &ColorTypes::Partial), ColorType::BrightBlue),
-                ((&TokenType::String, &ColorTypes::Partial), ColorType::Yellow),
-                ((&TokenType::Comment, &ColorTypes::Partial), ColorType::Green),
-                ((&TokenType::CommentLong, &ColorTypes::Partial), ColorType::Green),
-                ((&TokenType::Null, &ColorTypes::Partial), ColorType::White),
-                ((&TokenType::Primitive, &ColorTypes::Partial), ColorType::BrightYellow),
-                ((&TokenType::Keyword, &ColorTypes::Partial), ColorType::BrightRed),
-                ((&TokenType::Unsafe, &ColorTypes::Partial), ColorType::BrightRed),
-
-                ((&TokenType::Bracket, &ColorTypes::True), ColorType::Rgb(125, 180, 255)),
-                ((&TokenType::SquirlyBracket, &ColorTypes::True), ColorType::Rgb(175, 50, 175)),
-                ((&TokenType::Parentheses, &ColorTypes::True), ColorType::Rgb(175, 50, 175)),
-                ((&TokenType::Variable, &ColorTypes::True), ColorType::Rgb(225, 225, 225)),
-                ((&TokenType::Member, &ColorTypes::True), ColorType::BrightCyan),
-                ((&TokenType::Object, &ColorTypes::True), ColorType::Rgb(225, 145, 110)),
-                ((&TokenType::Function, &ColorTypes::True), ColorType::BrightMagenta),
-                ((&TokenType::Method, &ColorTypes::True), ColorType::Rgb(125, 180, 255)),
-                ((&TokenType::Number, &ColorTypes::True), ColorType::BrightYellow),
-                ((&TokenType::Logic, &ColorTypes::True), ColorType::Rgb(225, 225, 150)),
-                ((&TokenType::Math, &ColorTypes::True), ColorType::Rgb(225, 225, 150)),
-                ((&TokenType::Assignment, &ColorTypes::True), ColorType::BrightBlue),
-                ((&TokenType::Endl, &ColorTypes::True), ColorType::Rgb(225, 225, 225)),
-                ((&TokenType::Macro, &ColorTypes::True), ColorType::BrightMagenta),
-                ((&TokenType::Const, &ColorTypes::True), ColorType::Cyan),
-                ((&TokenType::Barrow, &ColorTypes::True), ColorType::Rgb(225, 225, 150)),
-                ((&TokenType::Lifetime, &ColorTypes::True), ColorType::BrightBlue),  // make this a different rgb?
-                ((&TokenType::String, &ColorTypes::True), ColorType::Yellow),
-                ((&TokenType::Comment, &ColorTypes::True), ColorType::Rgb(35, 150, 45)),
-                ((&TokenType::CommentLong, &ColorTypes::True), ColorType::Rgb(35, 150, 45)),
-                ((&TokenType::Null, &ColorTypes::True), ColorType::Rgb(225, 225, 225)),
-                ((&TokenType::Primitive, &ColorTypes::True), ColorType::BrightYellow),
-                ((&TokenType::Keyword, &ColorTypes::True), ColorType::BrightRed),
-                ((&TokenType::Unsafe, &ColorTypes::True), ColorType::BrightRed),  // make its background less standing out?
-            ]),
+        let mut bindings = ColorBindings {
+            name: String::new(),
+            errorCol: ThemeColor::default(),
+            suggestion: ThemeColor::default(),
+            command: ThemeColor::default(),
+            default: ThemeColor::default(),
+            highlight: ThemeColor::default(),
+            tabBar: ThemeColor::default(),
+            tabBarFocused: ThemeColor::default(),
+            gutter: ThemeColor::default(),
+            gutterCursor: ThemeColor::default(),
+            selection: ThemeColor::default(),
+            matchingBracket: ThemeColor::default(),
+            syntaxHighlighting: std::collections::HashMap::new(),
+        };
+        let theme = serde_json::from_str(DEFAULT_THEME).expect("The bundled default theme isn't valid json");
+        bindings.Overlay(&theme).expect("The bundled default theme is invalid");
+        bindings
+    }
+}
+
+impl <'a> ColorBindings <'a> {
+    // Loads a theme by name
+    // Themes in ~/.config/termedit/themes take priority over the bundled ones
+    pub fn LoadTheme (name: &str) -> Result <ColorBindings <'a>, String> {
+        let path = dirs::home_dir()
+            .map(|home| home.join(".config/termedit/themes").join(format!("{name}.json")))
+            .filter(|path| path.exists());
+        if let Some(path) = path {
+            let json = DataManager::LoadJson(&path.to_string_lossy())
+                .map_err(|error| format!("Failed to load theme '{name}': {error}"))?;
+            return ColorBindings::FromJson(&json);
+        }
+
+        let (_, theme) = BUNDLED_THEMES.iter()
+            .find(|(bundled, _)| *bundled == name)
+            .ok_or(format!("No theme named '{name}' found"))?;
+        let json = serde_json::from_str(theme).expect("A bundled theme isn't valid json");
+        ColorBindings::FromJson(&json)
+    }
+
+    // every theme that can be loaded (the bundled ones and any custom ones), sorted
+    pub fn ThemeNames () -> Vec <String> {
+        let mut names: Vec <String> = BUNDLED_THEMES.iter().map(|(name, _)| name.to_string()).collect();
+        if let Some(home) = dirs::home_dir() {
+            names.extend(DataManager::JsonNames(&home.join(".config/termedit/themes")));
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn FromJson (json: &Value) -> Result <ColorBindings <'a>, String> {
+        let mut bindings = ColorBindings::default();
+        bindings.Overlay(json)?;
+        Ok(bindings)
+    }
+
+    // Replaces any colors which the theme specifies
+    fn Overlay (&mut self, json: &Value) -> Result <(), String> {
+        if let Some(name) = json.get("name").and_then(Value::as_str) {
+            self.name = name.to_string();
+        }
+
+        if let Some(ui) = json.get("ui").and_then(Value::as_object) {
+            for (key, value) in ui {
+                let color = ThemeColor::FromJson(value)?;
+                match key.as_str() {
+                    "error" => self.errorCol = color,
+                    "suggestion" => self.suggestion = color,
+                    "command" => self.command = color,
+                    "default" => self.default = color,
+                    "highlight" => self.highlight = color,
+                    // these are all backgrounds
+                    "tabBar" => self.tabBar = color.ToBackground(),
+                    "tabBarFocused" => self.tabBarFocused = color.ToBackground(),
+                    "selection" => self.selection = color.ToBackground(),
+                    "matchingBracket" => self.matchingBracket = color.ToBackground(),
+                    "gutter" => self.gutter = color,
+                    "gutterCursor" => self.gutterCursor = color,
+                    _ => return Err(format!("Unknown ui color '{key}'")),
+                }
+            }
+        }
+
+        if let Some(tokens) = json.get("tokens").and_then(Value::as_object) {
+            for (key, value) in tokens {
+                let token = TokenType::FromName(key).ok_or(format!("Unknown token type '{key}'"))?;
+                let color = ThemeColor::FromJson(value)?;
+                self.syntaxHighlighting.insert((token, &ColorTypes::True), color.trueColor);
+                self.syntaxHighlighting.insert((token, &ColorTypes::Partial), color.partial);
+                self.syntaxHighlighting.insert((token, &ColorTypes::Basic), color.basic);
+            }
         }
+        Ok(())
     }
 }
 
@@ -117,5 +210,6 @@ impl <'a> Default for ColorBindings <'a> {
 pub struct ColorMode <'a> {
     pub colorType: ColorTypes,
     pub colorBindings: ColorBindings <'a>,
+    pub background: crate::TermRender::ColorMode,  // whether the terminal is light or dark
 }
 
@@ -0,0 +1,899 @@
+// the registry of everything that can be typed into the command line
+// (parsing the arguments, where each command can be used, and the fuzzy search for the palette)
+
+use proc_macros::color;
+
+use crate::App as MainApp;
+use crate::{AppState, MenuState, TabState, FileBrowser, Keymap, Settings, TermRender, RustAnalyzerLsp};
+use crate::Colors::ColorBindings;
+use crate::LineOperations::SortOptions;
+use crate::CodeTabs::CodeTab;
+use crate::TermRender::{Colorize, ColorType, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandContext {
+    Menu,
+    Editor,  // a project is open
+    Code,  // a project is open with a file open in it
+}
+
+impl CommandContext {
+    // anything that works in the editor also works once a file is open
+    fn Allows (&self, current: CommandContext) -> bool {
+        *self == current || (*self == CommandContext::Editor && current == CommandContext::Code)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    Directory,
+    File,  // relative to the current directory
+    Number,
+    Theme,
+    Keymap,
+    Position,  // :col after a line number
+    Substitution,  // /old/new/flags
+    Setting,  // setting=value
+    SortOptions,  // i and/or u
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Argument {
+    pub name: &'static str,
+    pub kind: ArgumentKind,
+    pub optional: bool,
+}
+
+impl Argument {
+    const fn Required (name: &'static str, kind: ArgumentKind) -> Argument {
+        Argument {  name, kind, optional: false  }
+    }
+
+    const fn Optional (name: &'static str, kind: ArgumentKind) -> Argument {
+        Argument {  name, kind, optional: true  }
+    }
+
+    fn Check (&self, value: &str) -> Result <(), String> {
+        match self.kind {
+            ArgumentKind::Number if value.parse::<usize>().is_err() => {
+                Err(format!("<{}> has to be a number, not '{value}'", self.name))
+            },
+            ArgumentKind::Position if value.strip_prefix(':').is_none_or(|column| column.parse::<usize>().is_err()) => {
+                Err(format!("The column has to be a number, not '{}'", value.trim_start_matches(':')))
+            },
+            ArgumentKind::Substitution => ParseSubstitution(value).map(|_| ()),
+            ArgumentKind::Setting => Settings::ParseAssignment(value).map(|_| ()),
+            ArgumentKind::SortOptions => SortOptions::Parse(value).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    // arguments with a fixed shape are shown as that shape instead of a name in brackets
+    fn IsForm (&self) -> bool {
+        matches!(self.kind, ArgumentKind::Position | ArgumentKind::Substitution)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Quit,
+    Settings,
+    Open,
+    Light,
+    Dark,
+    Theme,
+    Keymap,
+    Vim,
+    JumpUp,
+    JumpDown,
+    GoToDefinition,
+    MatchingBracket,
+    Fold,
+    FoldAll,
+    UnfoldAll,
+    Wrap,
+    Comment,
+    Duplicate,
+    Join,
+    Sort,
+    DeleteLines,
+    Write,
+    WriteAll,
+    WriteQuit,
+    Edit,
+    GoToLine,
+    Substitute,
+    Set,
+}
+
+#[derive(Debug)]
+pub struct CommandInfo {
+    pub command: Command,
+    pub name: &'static str,
+    pub arguments: &'static [Argument],
+    pub description: &'static str,
+    pub contexts: &'static [CommandContext],
+}
+
+impl CommandInfo {
+    pub fn IsAvailableIn (&self, context: CommandContext) -> bool {
+        self.contexts.iter().any(|allowed| allowed.Allows(context))
+    }
+
+    // the name with its arguments, like -theme <name>
+    pub fn Usage (&self) -> String {
+        let mut usage = self.name.to_string();
+        for (index, argument) in self.arguments.iter().enumerate() {
+            // the jumps take their number without a space ([5)
+            if index > 0 || !self.IsPrefix() {  usage.push(' ');  }
+            if argument.IsForm() {  usage.push_str(argument.name);  }
+            else if argument.optional {  usage.push_str(&format!("[{}]", argument.name));  }
+            else {  usage.push_str(&format!("<{}>", argument.name));  }
+        }
+        usage
+    }
+
+    pub fn IsPrefix (&self) -> bool {
+        matches!(self.command, Command::JumpUp | Command::JumpDown | Command::GoToLine | Command::Substitute)
+    }
+
+    fn TakesRange (&self) -> bool {
+        matches!(self.command,
+            Command::GoToLine | Command::Substitute | Command::Comment | Command::Join | Command::Sort | Command::DeleteLines
+        )
+    }
+}
+
+const SORT_OPTIONS: &[&str] = &["i", "u", "iu"];
+
+const BOTH: &[CommandContext] = &[CommandContext::Menu, CommandContext::Editor];
+
+pub static COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        command: Command::Quit, name: "q", arguments: &[],
+        description: "Quit the editor", contexts: BOTH,
+    },
+    CommandInfo {
+        command: Command::Settings, name: "settings", arguments: &[],
+        description: "Open the settings", contexts: &[CommandContext::Menu],
+    },
+    CommandInfo {
+        command: Command::Open, name: "open",
+        arguments: &[Argument::Required("path", ArgumentKind::Directory)],
+        description: "Open a project folder", contexts: &[CommandContext::Menu],
+    },
+    CommandInfo {
+        command: Command::Light, name: "-light", arguments: &[],
+        description: "Use the light background", contexts: BOTH,
+    },
+    CommandInfo {
+        command: Command::Dark, name: "-dark", arguments: &[],
+        description: "Use the dark background", contexts: BOTH,
+    },
+    CommandInfo {
+        command: Command::Theme, name: "-theme",
+        arguments: &[Argument::Required("name", ArgumentKind::Theme)],
+        description: "Switch the color theme", contexts: BOTH,
+    },
+    CommandInfo {
+        command: Command::Keymap, name: "-keymap",
+        arguments: &[Argument::Required("name", ArgumentKind::Keymap)],
+        description: "Switch the key bindings", contexts: BOTH,
+    },
+    CommandInfo {
+        command: Command::Vim, name: "-vim", arguments: &[],
+        description: "Turn vim style editing on or off", contexts: BOTH,
+    },
+    CommandInfo {
+        command: Command::JumpUp, name: "[",
+        arguments: &[Argument::Required("lines", ArgumentKind::Number)],
+        description: "Jump up a number of lines", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::JumpDown, name: "]",
+        arguments: &[Argument::Required("lines", ArgumentKind::Number)],
+        description: "Jump down a number of lines", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::GoToDefinition, name: "gd", arguments: &[],
+        description: "Go to the definition of the token on the cursor", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::MatchingBracket, name: "match", arguments: &[],
+        description: "Jump to the bracket paired with the one by the cursor", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Fold, name: "fold", arguments: &[],
+        description: "Fold the block around the cursor (or unfold it)", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::FoldAll, name: "foldall",
+        arguments: &[Argument::Optional("level", ArgumentKind::Number)],
+        description: "Fold every block nested at least this deep (1 folds everything)", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::UnfoldAll, name: "unfoldall", arguments: &[],
+        description: "Unfold every folded block", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Wrap, name: "wrap", arguments: &[],
+        description: "Wrap long lines onto more rows in this tab (or stop wrapping them)", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Comment, name: "comment", arguments: &[],
+        description: "Comment or uncomment the selected lines (or a range of lines)", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Duplicate, name: "duplicate", arguments: &[],
+        description: "Copy the selected lines (or the cursor's line) below themselves", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Join, name: "join", arguments: &[],
+        description: "Join the selected lines (or a range, or the cursor's line and the next) into one", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Sort, name: "sort",
+        arguments: &[Argument::Optional("iu", ArgumentKind::SortOptions)],
+        description: "Sort the selected lines or a range (the whole file without either); i ignores case, u drops repeats",
+        contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::DeleteLines, name: "delete", arguments: &[],
+        description: "Delete the selected lines or a range (without copying them)", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Write, name: "w",
+        arguments: &[Argument::Optional("path", ArgumentKind::File)],
+        description: "Save the file (or save it as a new path)", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::WriteAll, name: "wa", arguments: &[],
+        description: "Save every open file", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::WriteQuit, name: "wq", arguments: &[],
+        description: "Save the file and quit", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Edit, name: "e",
+        arguments: &[Argument::Required("path", ArgumentKind::File)],
+        description: "Open a file (relative to the project)", contexts: &[CommandContext::Editor],
+    },
+    // typed as just the line number (12 or 12:5)
+    CommandInfo {
+        command: Command::GoToLine, name: "",
+        arguments: &[Argument::Optional("<line>[:col]", ArgumentKind::Position)],
+        description: "Jump to a line (and column)", contexts: &[CommandContext::Code],
+    },
+    // the line range goes before it (%s, 3,8s or .,$s); it's only the cursor's line without one
+    CommandInfo {
+        command: Command::Substitute, name: "s",
+        arguments: &[Argument::Required("/<old>/<new>/[g]", ArgumentKind::Substitution)],
+        description: "Replace text on the line or a range (% is every line)", contexts: &[CommandContext::Code],
+    },
+    CommandInfo {
+        command: Command::Set, name: "set",
+        arguments: &[Argument::Required("setting=value", ArgumentKind::Setting)],
+        description: "Change a setting until the editor is closed", contexts: BOTH,
+    },
+];
+
+// a line as it's typed in a range; numbers count from 1 like the line numbers shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    Line(usize),
+    Current,  // .
+    Last,  // $
+}
+
+impl Address {
+    fn Parse (text: &str) -> Result <Address, String> {
+        match text {
+            "." => Ok(Address::Current),
+            "$" => Ok(Address::Last),
+            _ => text.parse::<usize>()
+                .map(Address::Line)
+                .map_err(|_| format!("'{text}' isn't a line")),
+        }
+    }
+
+    // the index of the line it points to (past the end is the last line)
+    fn Resolve (&self, cursorLine: usize, lineCount: usize) -> usize {
+        let line = match self {
+            Address::Line(line) => line.saturating_sub(1),
+            Address::Current => cursorLine,
+            Address::Last => lineCount.saturating_sub(1),
+        };
+        std::cmp::min(line, lineCount.saturating_sub(1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: Address,
+    pub end: Address,
+}
+
+impl LineRange {
+    // 5, .,$ or % (every line); nothing typed is no range
+    fn Parse (text: &str) -> Result <Option <LineRange>, String> {
+        if text.is_empty() {  return Ok(None);  }
+        if text == "%" {
+            return Ok(Some(LineRange {  start: Address::Line(1), end: Address::Last  }));
+        }
+        let (start, end) = text.split_once(',').unwrap_or((text, text));
+        Ok(Some(LineRange {  start: Address::Parse(start)?, end: Address::Parse(end)?  }))
+    }
+
+    pub fn Resolve (&self, cursorLine: usize, lineCount: usize) -> (usize, usize) {
+        let start = self.start.Resolve(cursorLine, lineCount);
+        let end = self.end.Resolve(cursorLine, lineCount);
+        (std::cmp::min(start, end), std::cmp::max(start, end))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    pub pattern: String,
+    pub replacement: String,
+    pub global: bool,  // every match on a line instead of just the first
+}
+
+// /old/new/g; any character can stand in for the slashes, and a backslash escapes it
+pub fn ParseSubstitution (text: &str) -> Result <Substitution, String> {
+    let usage = || String::from("Usage: s/<old>/<new>/[g]");
+    let mut characters = text.chars();
+    let delimiter = characters.next().filter(|chr| !chr.is_alphanumeric() && *chr != '\\').ok_or_else(usage)?;
+
+    let mut parts = vec![String::new()];
+    while let Some(chr) = characters.next() {
+        let escaped = chr == '\\' && characters.clone().next() == Some(delimiter);
+        if escaped {  characters.next();  }
+        if chr == delimiter && parts.len() < 3 {
+            parts.push(String::new());
+        } else if let Some(part) = parts.last_mut() {
+            part.push(if escaped {  delimiter  } else {  chr  });
+        }
+    }
+    if parts.len() < 2 {  return Err(usage());  }
+    if parts[0].is_empty() {  return Err(String::from("There's nothing to search for"));  }
+    let flags = parts.get(2).cloned().unwrap_or_default();
+    if let Some(flag) = flags.chars().find(|flag| *flag != 'g') {
+        return Err(format!("Unknown flag '{flag}' (only g is supported)"));
+    }
+
+    Ok(Substitution {
+        pattern: parts[0].clone(),
+        replacement: parts[1].clone(),
+        global: !flags.is_empty(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub command: Command,
+    pub range: Option <LineRange>,
+    pub arguments: Vec <String>,
+}
+
+// splits off the line range and the command's name (the jumps and substitutions don't need a space
+// before their argument, and a range on its own is a jump to that line)
+pub fn SplitName (text: &str) -> (&str, &str, Option <&str>) {
+    let rangeLength = text
+        .find(|chr: char| !(chr.is_ascii_digit() || matches!(chr, '.' | '$' | '%' | ',')))
+        .unwrap_or(text.len());
+    let (range, text) = text.split_at(rangeLength);
+    if !range.is_empty() && (text.is_empty() || text.starts_with(':')) {
+        return (range, "", Some(text));
+    }
+    if text.starts_with('[') || text.starts_with(']') {
+        return (range, &text[..1], Some(&text[1..]));
+    }
+    if let Some(rest) = text.strip_prefix('s') &&
+        rest.starts_with(|chr: char| !chr.is_alphanumeric() && chr != ' ')
+    {
+        return (range, "s", Some(rest));
+    }
+    match text.split_once(' ') {
+        Some((name, rest)) => (range, name, Some(rest)),
+        None => (range, text, None),
+    }
+}
+
+pub fn FindCommand (name: &str) -> Option <&'static CommandInfo> {
+    COMMANDS.iter().find(|info| info.name == name)
+}
+
+pub fn Parse (text: &str, context: CommandContext) -> Result <Invocation, String> {
+    let (range, name, rest) = SplitName(text.trim_start());
+    let info = FindCommand(name).ok_or(format!("Unknown command '{name}'"))?;
+    if !info.IsAvailableIn(context) {
+        return Err(format!("'{}' can't be used here", if name.is_empty() {  range  } else {  name  }));
+    }
+    let range = LineRange::Parse(range)?;
+    if range.is_some() && !info.TakesRange() {
+        return Err(format!("'{name}' doesn't take a line range"));
+    }
+    // jumping only makes sense to a single line
+    if info.command == Command::GoToLine && range.is_some_and(|range| range.start != range.end) {
+        return Err(format!("Usage: {}", info.Usage()));
+    }
+
+    // the last argument takes the rest of the line (so paths can have spaces)
+    let mut rest = rest.unwrap_or("").trim();
+    let mut arguments = vec![];
+    for (index, argument) in info.arguments.iter().enumerate() {
+        if rest.is_empty() {
+            if argument.optional {  break;  }
+            return Err(format!("Usage: {}", info.Usage()));
+        }
+        let value =
+            if index + 1 == info.arguments.len() {  std::mem::take(&mut rest)  }
+            else {
+                let (value, remaining) = rest.split_once(' ').unwrap_or((rest, ""));
+                rest = remaining.trim_start();
+                value
+            };
+        argument.Check(value)?;
+        arguments.push(value.to_string());
+    }
+    if !rest.is_empty() {
+        return Err(format!("Usage: {}", info.Usage()));
+    }
+
+    Ok(Invocation {  command: info.command, range, arguments  })
+}
+
+// how well the query matches; every character has to show up in order, with
+// runs of characters and matches at the start counting for more
+pub fn FuzzyScore (query: &str, candidate: &str) -> Option <usize> {
+    let candidate = candidate.trim_start_matches('-');
+    let mut score = 0;
+    let mut lastMatch: Option <usize> = None;
+    let mut characters = candidate.char_indices();
+    for chr in query.trim_start_matches('-').chars() {
+        let (index, _) = characters.find(|(_, other)| other.eq_ignore_ascii_case(&chr))?;
+        score += 1;
+        if index == 0 {  score += 4;  }
+        if lastMatch.is_some_and(|last| last + 1 == index) {  score += 3;  }
+        lastMatch = Some(index);
+    }
+    Some(score)
+}
+
+// the commands matching the query, best first
+pub fn Search (query: &str, context: CommandContext) -> Vec <&'static CommandInfo> {
+    let mut matches: Vec <(usize, &CommandInfo)> = COMMANDS.iter()
+        .filter(|info| info.IsAvailableIn(context))
+        .filter_map(|info| FuzzyScore(query, info.name).map(|score| (score, info)))
+        .collect();
+    matches.sort_by(|(score, info), (otherScore, other)| {
+        otherScore.cmp(score).then(info.name.len().cmp(&other.name.len()))
+    });
+    matches.into_iter().map(|(_, info)| info).collect()
+}
+
+// saves the tab, giving back why it couldn't be
+fn WriteTab (tab: &mut CodeTab) -> Result <(), String> {
+    if tab.path.is_empty() {
+        return Err(String::from("There's no path to save to (use w <path>)"));
+    }
+    tab.TrySave().map_err(|error| format!("Couldn't save '{}': {error}", tab.path))
+}
+
+impl <'a> MainApp <'a> {
+    pub(crate) fn CommandContext (&self) -> CommandContext {
+        match self.appState {
+            AppState::Menu => CommandContext::Menu,
+            _ if self.codeTabs.tabs.is_empty() => CommandContext::Editor,
+            _ => CommandContext::Code,
+        }
+    }
+
+    // runs whatever is in the command line (it's cleared either way; failing leaves an error up instead)
+    pub(crate) async fn RunCommand<'b> (&mut self, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let text = std::mem::take(&mut self.currentCommand);
+        self.historyIndex = None;
+        if text.trim().is_empty() {  return;  }
+        if self.commandHistory.last() != Some(&text) {
+            self.commandHistory.push(text.clone());
+        }
+
+        match Parse(&text, self.CommandContext()) {
+            Ok(invocation) => self.ExecuteCommand(invocation, rustAnalyzer).await,
+            Err(error) => self.commandError = Some(error),
+        }
+    }
+
+    async fn ExecuteCommand<'b> (&mut self, invocation: Invocation, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let argument = invocation.arguments.first().cloned().unwrap_or_default();
+        match invocation.command {
+            Command::Quit => self.Exit(),
+            Command::Settings => self.menuState = MenuState::Settings,
+            Command::Open => {
+                let foundFile = self.fileBrowser.LoadFilePath(&argument, &mut self.codeTabs);
+                match foundFile {
+                    Ok(_) => {
+                        self.fileBrowser.fileCursor = 0;
+                        self.codeTabs.currentTab = 0;
+
+                        self.appState = AppState::CommandPrompt;
+                        self.currentDir = FileBrowser::GetPathName(&argument);
+                        let folder = std::path::PathBuf::from(&self.currentDir);
+                        self.LoadProjectSettings(&folder);
+
+                        self.allFiles.clear(); self.RecalcAllFiles();
+                    },
+                    Err(_) => self.commandError = Some(format!("Couldn't open '{argument}'")),
+                }
+            },
+            Command::Light => self.colorMode.background = TermRender::ColorMode::Light,
+            Command::Dark => self.colorMode.background = TermRender::ColorMode::Dark,
+            Command::Theme => self.ChangeTheme(argument.trim()),
+            Command::Keymap => self.ChangeKeymap(argument.trim()),
+            Command::Vim => self.ToggleVim(),
+            Command::JumpUp | Command::JumpDown => {
+                let lines = argument.parse::<usize>().unwrap_or(0);
+                let tab = &mut self.codeTabs.tabs[self.lastTab];
+                let line =
+                    if invocation.command == Command::JumpUp {  tab.cursor.0.saturating_sub(lines)  }
+                    else {  tab.cursor.0.saturating_add(lines)  };
+                tab.JumpCursor(line, 1);
+            },
+            Command::GoToDefinition => self.GoToDefinition(),
+            Command::MatchingBracket => self.JumpToMatchingBracket(),
+            Command::Fold => if !self.ToggleFold() {
+                self.commandError = Some(String::from("There's no block to fold here"));
+            },
+            Command::FoldAll => self.FoldAll(argument.parse::<usize>().unwrap_or(1).max(1)),
+            Command::UnfoldAll => self.UnfoldAll(),
+            Command::Wrap => self.ToggleWrap(),
+            Command::Comment | Command::Join | Command::Sort | Command::DeleteLines => {
+                let tab = &self.codeTabs.tabs[self.lastTab];
+                let lines = invocation.range.map(|range| range.Resolve(tab.cursor.0, tab.lines.len()));
+                match invocation.command {
+                    Command::Comment => if !self.ToggleComment(lines, rustAnalyzer).await {
+                        self.commandError = Some(String::from("This language doesn't have comments"));
+                    },
+                    Command::Join => if !self.JoinLines(lines, rustAnalyzer).await {
+                        self.commandError = Some(String::from("There's no line below to join"));
+                    },
+                    Command::Sort => {
+                        let options = SortOptions::Parse(&argument).unwrap_or_default();
+                        self.SortLines(lines, options, rustAnalyzer).await;
+                    },
+                    _ => self.DeleteLines(lines, rustAnalyzer).await,
+                }
+            },
+            Command::Duplicate => self.DuplicateLines(rustAnalyzer).await,
+            Command::Write => {
+                if !argument.is_empty() {
+                    let path = self.CommandPath(&argument);
+                    let name = std::path::Path::new(&path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or(argument);
+                    let tab = &mut self.codeTabs.tabs[self.lastTab];
+                    (tab.path, tab.name, tab.fileName) = (path, name.clone(), name.clone());
+                    self.codeTabs.tabFileNames[self.lastTab] = name;
+                }
+                self.commandError = WriteTab(&mut self.codeTabs.tabs[self.lastTab]).err();
+            },
+            Command::WriteAll => {
+                self.commandError = self.codeTabs.tabs.iter_mut()
+                    .filter(|tab| !tab.saved)
+                    .filter_map(|tab| WriteTab(tab).err())
+                    .last();
+            },
+            Command::WriteQuit => match WriteTab(&mut self.codeTabs.tabs[self.lastTab]) {
+                Ok(_) => self.Exit(),
+                Err(error) => self.commandError = Some(error),
+            },
+            Command::Edit => self.EditFile(&argument, rustAnalyzer).await,
+            Command::GoToLine => {
+                let tab = &mut self.codeTabs.tabs[self.lastTab];
+                let line = invocation.range
+                    .map(|range| range.start.Resolve(tab.cursor.0, tab.lines.len()))
+                    .unwrap_or(tab.cursor.0);
+                tab.highlighting = false;
+                tab.JumpCursor(line, 0);
+                let column = argument.trim_start_matches(':').parse::<usize>().ok()
+                    .map(|column| column.saturating_sub(1))
+                    .unwrap_or_else(|| tab.lines[line].len() - tab.lines[line].trim_start().len());
+                tab.cursor.1 = std::cmp::min(column, tab.lines[line].len());
+                while !tab.lines[line].is_char_boundary(tab.cursor.1) {  tab.cursor.1 -= 1;  }
+
+                self.appState = AppState::Tabs;
+                self.tabState = TabState::Code;
+            },
+            Command::Substitute => {
+                let Ok(substitution) = ParseSubstitution(&argument) else {  return;  };
+                self.Substitute(invocation.range, substitution, rustAnalyzer).await;
+            },
+            Command::Set => {
+                let applied = Settings::ParseAssignment(&argument)
+                    .and_then(|(info, value)| self.ApplySetting(info.setting, &value).map(|_| info.setting));
+                match applied {
+                    Ok(setting) => {
+                        self.KeepSetting(setting);
+                        self.commandError = None;
+                    },
+                    Err(error) => self.commandError = Some(error),
+                }
+            },
+        }
+    }
+
+    // paths typed into commands are relative to the current directory (~/ is the home directory)
+    fn CommandPath (&self, path: &str) -> String {
+        if let Some(path) = path.strip_prefix("~/") {
+            return FileBrowser::GetPathName(path);
+        }
+        std::path::Path::new(&self.currentDir)
+            .join(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // switches to the file's tab if it's already open; a file that doesn't exist yet starts out empty
+    async fn EditFile<'b> (&mut self, argument: &str, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let path = self.CommandPath(argument);
+        let index = match self.codeTabs.tabs.iter().position(|tab| tab.path == path) {
+            Some(index) => index,
+            None => {
+                let (contents, exists) = match std::fs::read_to_string(&path) {
+                    Ok(contents) => (contents, true),
+                    Err(error) if error.kind() == std::io::ErrorKind::NotFound => (String::new(), false),
+                    Err(error) => {
+                        self.commandError = Some(format!("Couldn't open '{argument}': {error}"));
+                        return;
+                    },
+                };
+                let name = std::path::Path::new(&path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or(argument.to_string());
+                self.OpenCodeTab(name, path, &contents, rustAnalyzer).await;
+                let index = self.codeTabs.tabs.len() - 1;
+                self.codeTabs.tabs[index].saved = exists;
+                index
+            },
+        };
+
+        self.codeTabs.currentTab = index;
+        self.lastTab = index;
+        self.appState = AppState::Tabs;
+        self.tabState = TabState::Code;
+    }
+
+    // replaces the literal text on each line of the range (the cursor's line without one), undone as one group
+    async fn Substitute<'b> (&mut self,
+                             range: Option <LineRange>,
+                             substitution: Substitution,
+                             rustAnalyzer: RustAnalyzerLsp<'b>
+    ) {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let scripts = &self.luaSyntaxHighlightScripts;
+        let (start, end) = range
+            .map(|range| range.Resolve(tab.cursor.0, tab.lines.len()))
+            .unwrap_or((tab.cursor.0, tab.cursor.0));
+        let undoDepth = tab.changeBuffer.len();
+        tab.highlighting = false;
+
+        let mut lastChanged = None;
+        for line in start..=end {
+            let mut matches: Vec <usize> = tab.lines[line]
+                .match_indices(&substitution.pattern)
+                .map(|(index, _)| index)
+                .collect();
+            if !substitution.global {  matches.truncate(1);  }
+
+            // going right to left so the earlier matches stay where they are
+            for &column in matches.iter().rev() {
+                tab.cursorEnd = (line, column);
+                tab.cursor = (line, column + substitution.pattern.len());
+                tab.highlighting = true;
+                if substitution.replacement.is_empty() {
+                    tab.DelChars(0, 0, scripts, rustAnalyzer).await;
+                } else {
+                    tab.InsertChars(substitution.replacement.clone(), scripts, rustAnalyzer).await;
+                }
+                tab.highlighting = false;
+            }
+            if !matches.is_empty() {  lastChanged = Some(line);  }
+        }
+        tab.MergeChanges(undoDepth);
+
+        match lastChanged {
+            Some(line) => {
+                tab.JumpCursor(line, 0);
+                tab.cursor.1 = tab.lines[line].len() - tab.lines[line].trim_start().len();
+            },
+            None => self.commandError = Some(format!("Couldn't find '{}'", substitution.pattern)),
+        }
+    }
+
+    // what the command line would be after pressing tab (none if there's nothing to complete)
+    pub(crate) fn CommandCompletion (&self) -> Option <String> {
+        let text = &self.currentCommand;
+        let (range, name, rest) = SplitName(text);
+        let Some(rest) = rest else {
+            let info = Search(name, self.CommandContext()).into_iter().next()?;
+            let mut completed = format!("{range}{}", info.name);
+            if !info.arguments.is_empty() && !info.IsPrefix() {  completed.push(' ');  }
+            return (completed != *text).then_some(completed);
+        };
+
+        // only the last argument is completed
+        let argument = FindCommand(name)?.arguments.last()?;
+        let typed = rest.trim_start();
+        let candidates = match argument.kind {
+            ArgumentKind::Directory => {
+                let folder = typed.rsplit('/').next().unwrap_or("");
+                let path = self.dirFiles.iter().find(|path| path.starts_with(folder))?;
+                return Some(format!("{text}{}/", &path[folder.len()..]));
+            },
+            ArgumentKind::File => {
+                let partial = typed.rsplit('/').next().unwrap_or("");
+                let entry = self.dirFiles.iter().find(|entry| entry.starts_with(partial) && *entry != partial)?;
+                return Some(format!("{text}{}", &entry[partial.len()..]));
+            },
+            ArgumentKind::Theme => ColorBindings::ThemeNames(),
+            ArgumentKind::Keymap => Keymap::Keymap::KeymapNames(),
+            ArgumentKind::Setting => Settings::SettingCompletions(),
+            ArgumentKind::SortOptions => SORT_OPTIONS.iter().map(|options| options.to_string()).collect(),
+            ArgumentKind::Number | ArgumentKind::Position | ArgumentKind::Substitution => return None,
+        };
+        candidates.into_iter()
+            .find(|candidate| candidate.starts_with(typed) && candidate != typed)
+            .map(|candidate| format!("{text}{}", &candidate[typed.len()..]))
+    }
+
+    pub(crate) fn CompleteCommand (&mut self) {
+        let Some(completed) = self.CommandCompletion() else {  return;  };
+        self.currentCommand = completed;
+        self.UpdateCommandDirectory();
+    }
+
+    // keeps the folders (or files) being suggested in line with the path being typed out
+    pub(crate) fn UpdateCommandDirectory (&mut self) {
+        let (_, name, Some(rest)) = SplitName(&self.currentCommand) else {  return;  };
+        let kind = FindCommand(name)
+            .and_then(|info| info.arguments.last())
+            .map(|argument| argument.kind);
+        if !matches!(kind, Some(ArgumentKind::Directory | ArgumentKind::File)) {  return;  }
+
+        let folder = rest.trim_start().rfind('/')
+            .map(|index| &rest.trim_start()[..=index])
+            .unwrap_or("");
+        self.dirFiles.clear();
+        if kind == Some(ArgumentKind::File) {
+            // files are relative to the project, which stays the current directory
+            let folder = self.CommandPath(folder);
+            FileBrowser::CalculateEntries(&folder, &mut self.dirFiles);
+            return;
+        }
+        self.currentDir = FileBrowser::GetPathName(folder);
+        FileBrowser::CalculateDirectories(&self.currentDir, &mut self.dirFiles);
+    }
+
+    // steps back through the commands that have been run (what was being typed is kept to come back to)
+    pub(crate) fn CommandHistoryUp (&mut self) {
+        if self.commandHistory.is_empty() {  return;  }
+        let index = match self.historyIndex {
+            None => {
+                self.historyDraft = self.currentCommand.clone();
+                self.commandHistory.len() - 1
+            },
+            Some(index) => index.saturating_sub(1),
+        };
+        self.historyIndex = Some(index);
+        self.currentCommand = self.commandHistory[index].clone();
+        self.commandError = None;
+    }
+
+    pub(crate) fn CommandHistoryDown (&mut self) {
+        let Some(index) = self.historyIndex else {  return;  };
+        if index + 1 < self.commandHistory.len() {
+            self.historyIndex = Some(index + 1);
+            self.currentCommand = self.commandHistory[index + 1].clone();
+        } else {
+            self.historyIndex = None;
+            self.currentCommand = std::mem::take(&mut self.historyDraft);
+        }
+    }
+
+    // the matching commands while the name is being typed, then the usage and any suggestions for the argument
+    pub(crate) fn PaletteLines (&self, rows: usize, width: usize) -> Vec <Span> {
+        let (_, name, rest) = SplitName(&self.currentCommand);
+        let entry = |info: &CommandInfo, first: bool| {
+            let usage = format!(" {:<18}", info.Usage());
+            Span::FromTokens(vec![
+                if first {  color![usage, Yellow, Bold, OnBrightBlack]  }
+                else {  color![usage, BrightWhite, Bold, OnBrightBlack]  },
+                color![format!("{:<width$}", info.description, width = width.saturating_sub(19)), BrightWhite, OnBrightBlack],
+            ])
+        };
+
+        let Some(rest) = rest else {
+            return Search(name, self.CommandContext()).into_iter()
+                .take(rows)
+                .enumerate()
+                .map(|(index, info)| entry(info, index == 0))
+                .collect();
+        };
+
+        let Some(info) = FindCommand(name) else {  return vec![];  };
+        let typed = rest.trim_start();
+        let (typed, suggestions) = match info.arguments.last().map(|argument| argument.kind) {
+            Some(ArgumentKind::Directory | ArgumentKind::File) => (typed.rsplit('/').next().unwrap_or(""), self.dirFiles.clone()),
+            Some(ArgumentKind::Theme) => (typed, ColorBindings::ThemeNames()),
+            Some(ArgumentKind::Keymap) => (typed, Keymap::Keymap::KeymapNames()),
+            Some(ArgumentKind::Setting) => (typed, Settings::SettingCompletions()),
+            Some(ArgumentKind::SortOptions) => (typed, SORT_OPTIONS.iter().map(|options| options.to_string()).collect()),
+            _ => (typed, vec![]),
+        };
+        std::iter::once(entry(info, false))
+            .chain(suggestions.into_iter()
+                .filter(|suggestion| suggestion.starts_with(typed))
+                .take(rows.saturating_sub(1))
+                .map(|suggestion| Span::FromTokens(vec![
+                    color![format!("   {:<width$}", suggestion, width = width.saturating_sub(3)), BrightBlack, OnBrightBlack],
+                ])))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn SplitsRangesAndNames () {
+        assert_eq!(SplitName("w file.rs"), ("", "w", Some("file.rs")));
+        assert_eq!(SplitName("q"), ("", "q", None));
+        assert_eq!(SplitName("42"), ("42", "", Some("")));
+        assert_eq!(SplitName("%s/a/b/g"), ("%", "s", Some("/a/b/g")));
+        assert_eq!(SplitName("1,$s#a#b#"), ("1,$", "s", Some("#a#b#")));
+        assert_eq!(SplitName("set tabwidth=2"), ("", "set", Some("tabwidth=2")));
+    }
+
+    #[test]
+    fn ParsesAndResolvesLineRanges () {
+        assert_eq!(LineRange::Parse(""), Ok(None));
+        let every = LineRange::Parse("%").unwrap().unwrap();
+        assert_eq!(every.Resolve(3, 10), (0, 9));
+        let single = LineRange::Parse("5").unwrap().unwrap();
+        assert_eq!(single.Resolve(0, 10), (4, 4));
+        // backwards ranges get flipped, and addresses past the end stop at the last line
+        let range = LineRange::Parse("$,.").unwrap().unwrap();
+        assert_eq!(range.Resolve(2, 10), (2, 9));
+        assert_eq!(LineRange::Parse("3,40").unwrap().unwrap().Resolve(0, 10), (2, 9));
+        assert!(LineRange::Parse("a,3").is_err());
+    }
+
+    fn Substitute (pattern: &str, replacement: &str, global: bool) -> Substitution {
+        Substitution {  pattern: pattern.to_string(), replacement: replacement.to_string(), global  }
+    }
+
+    #[test]
+    fn ParsesSubstitutions () {
+        assert_eq!(ParseSubstitution("/old/new/"), Ok(Substitute("old", "new", false)));
+        assert_eq!(ParseSubstitution("/old/new/g"), Ok(Substitute("old", "new", true)));
+        // the trailing delimiter can be left off, and the replacement can be empty
+        assert_eq!(ParseSubstitution("/old/new"), Ok(Substitute("old", "new", false)));
+        assert_eq!(ParseSubstitution("/old//g"), Ok(Substitute("old", "", true)));
+        // any other delimiter works, and a backslash escapes it (other backslashes are kept)
+        assert_eq!(ParseSubstitution("#a/b#c/d#"), Ok(Substitute("a/b", "c/d", false)));
+        assert_eq!(ParseSubstitution(r"/a\/b/c\n/"), Ok(Substitute("a/b", r"c\n", false)));
+    }
+
+    #[test]
+    fn RejectsBadSubstitutions () {
+        assert!(ParseSubstitution("").is_err());
+        assert!(ParseSubstitution("/old").is_err());
+        assert!(ParseSubstitution("//new/").is_err());
+        assert!(ParseSubstitution("/old/new/x").is_err());
+        assert!(ParseSubstitution("aoldanewa").is_err());
+    }
+}
@@ -11,6 +11,16 @@ pub fn LoadJson (fileName: &str) -> Result <Value, std::io::Error> {
     Ok(json)
 }
 
+// the names of the json files in a directory (without the .json); a missing directory just has none
+pub fn JsonNames (directory: &std::path::Path) -> Vec <String> {
+    let Ok(entries) = std::fs::read_dir(directory) else {  return vec![];  };
+    entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect()
+}
+
 
 /*
 // loads all tasks
@@ -494,12 +494,22 @@ impl <'a> MainApp <'a> {
 
         //let name = &self.fileBrowser.files[height];
 
-        let mut lines: Vec <String> = vec!();
-
         //let fullPath = &self.fileBrowser.filePaths[height];
 
         let msg = fileInfo.path.as_str().trim();  // temporary for debugging (ya sure.... very temporary--5/30/25)
         let contents = std::fs::read_to_string(&fileInfo.path).expect(msg);
+        let (name, path) = (fileInfo.name.clone(), fileInfo.path.clone());
+        self.OpenCodeTab(name, path, &contents, rustAnalyzer).await;
+    }
+
+    // adds a tab for the file's contents (the caller picks which tab is shown)
+    pub(crate) async fn OpenCodeTab (&mut self,
+                                     name: String,
+                                     path: String,
+                                     contents: &str,
+                                     rustAnalyzer: &Option <std::sync::Arc <parking_lot::RwLock <RustAnalyzer>>>,
+    ) {
+        let mut lines: Vec <String> = vec!();
         let mut current = String::new();
         for chr in contents.chars() {
             if chr == '\n' {
@@ -511,14 +521,17 @@ impl <'a> MainApp <'a> {
         }
         lines.push(current);
 
+        let (tabWidth, useTabs) = self.IndentFor(&name, &lines);
         let mut tab = CodeTab {
             lines,
+            tabWidth,
+            useTabs,
             ..Default::default()
         };// tab.ClearRenderCache();
-        tab.name = fileInfo.name.clone();
+        tab.name = name.clone();
 
-        tab.fileName = fileInfo.name.clone();
-        tab.path = fileInfo.path.clone();
+        tab.fileName = name.clone();
+        tab.path = path;
 
         tab.lineTokens.write().clear();
         let ending = tab.fileName.split('.').next_back().unwrap_or("");
@@ -538,7 +551,7 @@ impl <'a> MainApp <'a> {
         //(tab.scopes, tab.scopeJumps, tab.linearScopes) = GenerateScopes(&tab.lineTokens, &tab.lineTokenFlags, &mut tab.outlineKeywords);
 
         self.codeTabs.tabs.push(tab);
-        self.codeTabs.tabFileNames.push(fileInfo.name.clone());
+        self.codeTabs.tabFileNames.push(name);
     }
 
     /// Recalculates the cumulative file structure; if a directory is collapsed or expanded, this has to be recalculated.
@@ -732,6 +745,20 @@ impl FileBrowser {
         }
     }
 
+    // finds the files and directories in a given directory (directories end with a /)
+    pub fn CalculateEntries (directory: &str, entries: &mut Vec <String>) {
+        let Ok(paths) = std::fs::read_dir(directory) else {  return;  };
+        for path in paths.flatten() {
+            let name = path.file_name().to_string_lossy().into_owned();
+            if path.file_type().is_ok_and(|fileType| fileType.is_dir()) {
+                entries.push(format!("{name}/"));
+            } else {
+                entries.push(name);
+            }
+        }
+        entries.sort();
+    }
+
     // loads a project into memory
     pub fn LoadFilePath (
         &mut self,
@@ -0,0 +1,238 @@
+// code folding; the foldable ranges come from the scope tree (or the indentation for languages without a linter)
+// and a folded range is drawn as its first line with the rest hidden under it
+
+use crate::App as MainApp;
+use crate::CodeTab;
+use crate::TokenInfo::ScopeNode;
+
+// (first line, last line, depth); folding hides the lines after the first up to and including the last
+pub type FoldRange = (usize, usize, usize);
+
+fn ScopeRanges (node: &ScopeNode, depth: usize, ranges: &mut Vec <FoldRange>) {
+    for child in &node.children {
+        if child.end > child.start {
+            ranges.push((child.start, child.end, depth));
+        }
+        ScopeRanges(child, depth + 1, ranges);
+    }
+}
+
+fn IndentWidth (line: &str, tabWidth: usize) -> usize {
+    line.chars()
+        .take_while(|chr| chr.is_whitespace())
+        .map(|chr| if chr == '\t' {  tabWidth  } else {  1  })
+        .sum()
+}
+
+// a line followed by more indented ones folds them (blank lines in between go with it, but not trailing ones)
+fn IndentRanges (lines: &[String], tabWidth: usize) -> Vec <FoldRange> {
+    let mut ranges = vec![];
+    let mut open: Vec <(usize, usize)> = vec![];  // (line, indent) of the lines still taking in the ones below
+    let mut lastText = 0;
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {  continue;  }
+        let indent = IndentWidth(line, tabWidth);
+        while let Some(&(start, startIndent)) = open.last() && startIndent >= indent {
+            open.pop();
+            if lastText > start {  ranges.push((start, lastText, open.len() + 1));  }
+        }
+        open.push((index, indent));
+        lastText = index;
+    }
+    while let Some((start, _)) = open.pop() {
+        if lastText > start {  ranges.push((start, lastText, open.len() + 1));  }
+    }
+    ranges
+}
+
+impl CodeTab {
+    // every range that can be folded (sorted by the first line, outermost first)
+    pub fn FoldRanges (&self) -> Vec <FoldRange> {
+        let scopes = self.scopes.read();
+        let mut ranges = vec![];
+        if scopes.children.is_empty() {
+            ranges = IndentRanges(&self.lines, self.tabWidth);
+        } else {
+            ScopeRanges(&scopes, 1, &mut ranges);
+            // the tree can lag behind the edits for a moment
+            ranges.retain(|(_, end, _)| *end < self.lines.len());
+        }
+        ranges.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+        ranges
+    }
+
+    pub fn IsHidden (&self, line: usize) -> bool {
+        self.folds.iter().any(|(start, end)| line > *start && line <= *end)
+    }
+
+    // the number of lines hidden under a line, if it starts a fold
+    pub fn FoldedLength (&self, line: usize) -> Option <usize> {
+        self.folds.iter()
+            .filter(|(start, _)| *start == line)
+            .map(|(start, end)| end - start)
+            .max()
+    }
+
+    // the line a hidden line is shown under (or the line itself when it isn't hidden)
+    pub fn VisibleLine (&self, mut line: usize) -> usize {
+        while let Some(start) = self.folds.iter()
+            .filter(|(start, end)| line > *start && line <= *end)
+            .map(|(start, _)| *start)
+            .min()
+        {  line = start;  }
+        line
+    }
+
+    // the next line that isn't hidden (can be past the end of the file)
+    pub fn NextVisibleLine (&self, line: usize) -> usize {
+        let mut next = line + 1;
+        while let Some(end) = self.folds.iter()
+            .filter(|(start, end)| next > *start && next <= *end)
+            .map(|(_, end)| *end)
+            .max()
+        {  next = end + 1;  }
+        next
+    }
+
+    pub fn PreviousVisibleLine (&self, line: usize) -> usize {
+        self.VisibleLine(line.saturating_sub(1))
+    }
+
+    // moves a number of shown lines up or down, stopping at the ends of the file
+    pub fn StepVisibleLines (&self, line: usize, count: usize, up: bool) -> usize {
+        let lastLine = self.lines.len().saturating_sub(1);
+        if self.folds.is_empty() {
+            return if up {  line.saturating_sub(count)  }
+                   else {  std::cmp::min(line + count, lastLine)  };
+        }
+        (0..count).fold(line, |line, _| {
+            if up {  self.PreviousVisibleLine(line)  }
+            else {
+                let next = self.NextVisibleLine(line);
+                if next > lastLine {  line  } else {  next  }
+            }
+        })
+    }
+
+    // the number of rows drawn from one line down to another
+    pub fn VisibleRowsBetween (&self, from: usize, to: usize) -> usize {
+        if self.folds.is_empty() {  return to.saturating_sub(from);  }
+        let (mut line, to) = (self.VisibleLine(from), self.VisibleLine(to));
+        let mut rows = 0;
+        while line < to {
+            line = self.NextVisibleLine(line);
+            rows += 1;
+        } rows
+    }
+
+    // the line drawn on a row, counting from the top of the view
+    pub fn LineAtRow (&self, top: usize, row: usize) -> usize {
+        if self.folds.is_empty() {  return top + row;  }
+        self.StepVisibleLines(self.VisibleLine(top), row, false)
+    }
+
+    // unfolds the fold starting on the line, or folds the innermost range around it
+    pub fn ToggleFold (&mut self, line: usize) -> bool {
+        if self.FoldedLength(line).is_some() {
+            self.Unfold(line);
+            return true;
+        } else {
+            let Some((start, end, _)) = self.FoldRanges()
+                .into_iter()
+                .filter(|(start, end, _)| *start <= line && line <= *end)
+                .min_by_key(|(start, end, _)| end - start)
+            else {  return false;  };
+            self.folds.push((start, end));
+            self.HideCursor();
+        }
+        self.ClearRenderCache();
+        true
+    }
+
+    pub fn Unfold (&mut self, line: usize) {
+        if self.FoldedLength(line).is_none() {  return;  }
+        self.folds.retain(|(start, _)| *start != line);
+        self.ClearRenderCache();
+    }
+
+    // folds every range at or below a depth (1 being the outermost ranges)
+    pub fn FoldAll (&mut self, level: usize) {
+        self.folds = self.FoldRanges()
+            .into_iter()
+            .filter(|(_, _, depth)| *depth >= level)
+            .map(|(start, end, _)| (start, end))
+            .collect();
+        self.HideCursor();
+        self.ClearRenderCache();
+    }
+
+    pub fn UnfoldAll (&mut self) {
+        self.folds.clear();
+        self.ClearRenderCache();
+    }
+
+    // moves the cursor out of a range that was just folded
+    fn HideCursor (&mut self) {
+        let line = self.VisibleLine(self.cursor.0);
+        if line == self.cursor.0 {  return;  }
+        self.cursor = (line, std::cmp::min(self.cursor.1, self.lines[line].len()));
+        self.highlighting = false;
+    }
+
+    // opens any fold the cursor ended up inside of (after a jump, search, or edit)
+    pub fn RevealCursor (&mut self) {
+        let line = self.cursor.0;
+        if !self.IsHidden(line) {  return;  }
+        self.folds.retain(|(start, end)| line <= *start || line > *end);
+        self.ClearRenderCache();
+    }
+
+    // keeps the folds on the same lines as lines are added above or inside of them (the bracket and wrap caches are redone too)
+    pub fn LinesInserted (&mut self, at: usize, count: usize) {
+        self.unmatchedBrackets = None;
+        self.wrapCache.get_mut().clear();
+        for (start, end) in self.folds.iter_mut() {
+            if *start >= at {
+                *start += count;
+                *end += count;
+            } else if *end >= at {
+                *end += count;
+            }
+        }
+    }
+
+    // same as above but for lines being taken out (a fold that loses its first line is dropped)
+    pub fn LinesRemoved (&mut self, at: usize, count: usize) {
+        self.unmatchedBrackets = None;
+        self.wrapCache.get_mut().clear();
+        let past = at + count;
+        self.folds.retain_mut(|(start, end)| {
+            if *start >= past {
+                *start -= count;
+                *end -= count;
+                true
+            } else if *end < at {  true  }
+            else if *start >= at {  false  }
+            else {
+                *end -= std::cmp::min(*end + 1, past) - at;
+                *end > *start
+            }
+        });
+    }
+}
+
+impl <'a> MainApp <'a> {
+    pub(crate) fn ToggleFold (&mut self) -> bool {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let line = tab.cursor.0;
+        tab.ToggleFold(line)
+    }
+
+    pub(crate) fn FoldAll (&mut self, level: usize) {
+        self.codeTabs.tabs[self.lastTab].FoldAll(level);
+    }
+
+    pub(crate) fn UnfoldAll (&mut self) {
+        self.codeTabs.tabs[self.lastTab].UnfoldAll();
+    }
+}
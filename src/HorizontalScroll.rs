@@ -0,0 +1,87 @@
+// horizontal scrolling for when lines aren't wrapped. The view follows the cursor sideways (keeping a margin
+// around it) and shift+scrolling moves it by hand. Each line is drawn up to the right edge of the view and then
+// cut down to the columns in view, so the selection and bracket colors stay lined up with the text, and an arrow
+// at either edge shows the line carries on past it
+
+use crate::CodeTab;
+use crate::TermRender::{Colored, Colorize, ColorType};
+use crate::WordWrap::SplitRows;
+use crate::color;
+
+// columns kept between the cursor and either edge while it's being followed
+const SCROLL_MARGIN: usize = 8;
+// how many columns the wheel moves for each row it'd move down
+const SIDEWAYS_SPEED: f64 = 2.0;
+
+impl CodeTab {
+    // the column the cursor is drawn at (one past the end of the line is drawn at the end)
+    fn CursorColumn (&self) -> usize {
+        self.DisplayColumn(self.cursor.0, std::cmp::min(self.cursor.1, self.lines[self.cursor.0].len()))
+    }
+
+    // scrolls sideways just far enough to keep the cursor (and the margin around it) in view
+    pub fn FollowCursorSideways (&mut self) {
+        if self.softWrap || self.wrapWidth == 0 {
+            (self.horizontalScroll, self.horizontalScrollFlt) = (0, 0.0);
+            return;
+        }
+        let column = self.CursorColumn();
+        let margin = std::cmp::min(SCROLL_MARGIN, self.wrapWidth / 4);
+        if column < self.horizontalScroll + margin {
+            self.horizontalScroll = column.saturating_sub(margin);
+        } else if column + margin >= self.horizontalScroll + self.wrapWidth {
+            self.horizontalScroll = column + margin + 1 - self.wrapWidth;
+        }
+        self.horizontalScrollFlt = self.horizontalScroll as f64;
+    }
+
+    // moves the view sideways by hand (up to about the end of the longest line)
+    pub fn ScrollSideways (&mut self, acceleration: f64) {
+        if self.softWrap {  return;  }
+        // in drawn columns, so tabs count for their full width
+        let longest = (0..self.lines.len()).map(|line| self.DisplayColumn(line, self.lines[line].len())).max().unwrap_or(0);
+        self.horizontalScrollFlt = f64::min(f64::max(
+            self.horizontalScrollFlt + acceleration * SIDEWAYS_SPEED, 0.0
+        ), longest.saturating_sub(self.wrapWidth / 2) as f64);
+
+        let scroll = self.horizontalScrollFlt as usize;
+        if scroll != self.horizontalScroll {
+            self.horizontalScroll = scroll;
+            self.ClearRenderCache();
+        }
+    }
+
+    // cuts a drawn line down to the columns in view, handing back its text and width
+    pub fn ScrolledRow (&self, rendered: Vec <Colored>) -> (Vec <Colored>, usize) {
+        let total: usize = rendered.iter().map(|token| token.GetSize()).sum();
+        let start = self.horizontalScroll;
+        let (left, right) = (start > 0 && total > 0, total > start + self.wrapWidth);
+        let from = start + left as usize;
+        let to = std::cmp::max(start + self.wrapWidth - right as usize, from);
+        let mut text = SplitRows(rendered, &[from, to]).swap_remove(1);
+        if left {  text.insert(0, color!["‹", BrightBlack]);  }
+        if right {  text.push(color!["›", BrightBlack]);  }
+        let width = text.iter().map(|token| token.GetSize()).sum();
+        (text, width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ScrollsToTheWidestLine () {
+        let mut tab = CodeTab {
+            lines: vec![String::from("short"), String::from("\t\t\tx")],
+            tabWidth: 4,
+            wrapWidth: 4,
+            ..Default::default()
+        };
+        // the tabs are drawn 12 columns wide, far more than the 4 bytes they take up
+        tab.ScrollSideways(100.0);
+        assert_eq!(tab.horizontalScroll, 11);
+        tab.ScrollSideways(-100.0);
+        assert_eq!(tab.horizontalScroll, 0);
+    }
+}
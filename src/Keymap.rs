@@ -0,0 +1,471 @@
+use crate::eventHandler::{KeyCode, KeyModifiers, KeyParser};
+use crate::DataManager;
+use serde_json::Value;
+use std::collections::HashMap;
+
+// the built in keymaps; (name, json)
+static PRESETS: [(&str, &str); 2] = [
+    ("mac-iterm", include_str!("../assets/keymaps/mac-iterm.json")),
+    ("standard", include_str!("../assets/keymaps/standard.json")),
+];
+
+// the keymap used when nothing else is picked (matches the iTerm2 escape codes in the readme)
+pub static DEFAULT_KEYMAP: &str = "mac-iterm";
+
+// Where the keys are going; each context has its own set of bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    Code,
+    Files,
+    Tabs,
+    Menu,
+    Prompt,
+}
+
+impl KeyContext {
+    pub fn FromName (name: &str) -> Option <KeyContext> {
+        match name {
+            "code" => Some(KeyContext::Code),
+            "files" => Some(KeyContext::Files),
+            "tabs" => Some(KeyContext::Tabs),
+            "menu" => Some(KeyContext::Menu),
+            "prompt" => Some(KeyContext::Prompt),
+            _ => None,
+        }
+    }
+
+    pub fn Name (&self) -> &'static str {
+        match self {
+            KeyContext::Code => "code",
+            KeyContext::Files => "files",
+            KeyContext::Tabs => "tabs",
+            KeyContext::Menu => "menu",
+            KeyContext::Prompt => "prompt",
+        }
+    }
+}
+
+// Everything a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    // code
+    Save,
+    Find,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    SelectAll,
+    ClosePane,
+    CursorLeft,
+    CursorRight,
+    CursorUp,
+    CursorDown,
+    WordLeft,
+    WordRight,
+    ScopeStart,
+    ScopeEnd,
+    LineStart,
+    LineEnd,
+    FileStart,
+    FileEnd,
+    PageUp,
+    PageDown,
+    DeleteLeft,
+    DeleteRight,
+    DeleteWordLeft,
+    DeleteWordRight,
+    DeleteLineLeft,
+    DeleteLineRight,
+    Indent,
+    Unindent,
+    AcceptSuggestion,
+    NewLine,
+    GoToDefinition,
+    FindReferences,
+    MatchingBracket,
+    ToggleFold,
+    FoldAll,
+    UnfoldAll,
+    ToggleComment,
+    ToggleWrap,
+    MoveLinesUp,
+    MoveLinesDown,
+    DuplicateLines,
+    JoinLines,
+    DeleteLines,
+    RecordMacro,
+    PlayMacro,
+
+    // files
+    OutlineUp,
+    OutlineDown,
+    OutlineJump,
+
+    // tabs
+    TabLeft,
+    TabRight,
+    MoveTabLeft,
+    MoveTabRight,
+    OpenTab,
+    CloseTab,
+
+    // prompt
+    NextPanel,
+    SwitchFileView,
+    HistoryUp,
+    HistoryDown,
+
+    // menu
+    CompletePath,
+    SettingUp,
+    SettingDown,
+    SettingLeft,
+    SettingRight,
+
+    // prompt and menu
+    Confirm,
+    DeleteChar,
+}
+
+// (action, name, the contexts it can be used in)
+static ACTIONS: &[(Action, &str, &[KeyContext])] = &[
+    (Action::Save, "save", &[KeyContext::Code]),
+    (Action::Find, "find", &[KeyContext::Code]),
+    (Action::Undo, "undo", &[KeyContext::Code]),
+    (Action::Redo, "redo", &[KeyContext::Code]),
+    (Action::Copy, "copy", &[KeyContext::Code]),
+    (Action::Cut, "cut", &[KeyContext::Code]),
+    (Action::Paste, "paste", &[KeyContext::Code]),
+    (Action::SelectAll, "select-all", &[KeyContext::Code]),
+    (Action::ClosePane, "close-pane", &[KeyContext::Code]),
+    (Action::CursorLeft, "cursor-left", &[KeyContext::Code]),
+    (Action::CursorRight, "cursor-right", &[KeyContext::Code]),
+    (Action::CursorUp, "cursor-up", &[KeyContext::Code]),
+    (Action::CursorDown, "cursor-down", &[KeyContext::Code]),
+    (Action::WordLeft, "word-left", &[KeyContext::Code]),
+    (Action::WordRight, "word-right", &[KeyContext::Code]),
+    (Action::ScopeStart, "scope-start", &[KeyContext::Code]),
+    (Action::ScopeEnd, "scope-end", &[KeyContext::Code]),
+    (Action::LineStart, "line-start", &[KeyContext::Code]),
+    (Action::LineEnd, "line-end", &[KeyContext::Code]),
+    (Action::FileStart, "file-start", &[KeyContext::Code]),
+    (Action::FileEnd, "file-end", &[KeyContext::Code]),
+    (Action::PageUp, "page-up", &[KeyContext::Code]),
+    (Action::PageDown, "page-down", &[KeyContext::Code]),
+    (Action::DeleteLeft, "delete-left", &[KeyContext::Code]),
+    (Action::DeleteRight, "delete-right", &[KeyContext::Code]),
+    (Action::DeleteWordLeft, "delete-word-left", &[KeyContext::Code]),
+    (Action::DeleteWordRight, "delete-word-right", &[KeyContext::Code]),
+    (Action::DeleteLineLeft, "delete-line-left", &[KeyContext::Code]),
+    (Action::DeleteLineRight, "delete-line-right", &[KeyContext::Code]),
+    (Action::Indent, "indent", &[KeyContext::Code]),
+    (Action::Unindent, "unindent", &[KeyContext::Code]),
+    (Action::AcceptSuggestion, "accept-suggestion", &[KeyContext::Code]),
+    (Action::NewLine, "new-line", &[KeyContext::Code]),
+    (Action::GoToDefinition, "go-to-definition", &[KeyContext::Code]),
+    (Action::FindReferences, "find-references", &[KeyContext::Code]),
+    (Action::MatchingBracket, "matching-bracket", &[KeyContext::Code]),
+    (Action::ToggleFold, "toggle-fold", &[KeyContext::Code]),
+    (Action::FoldAll, "fold-all", &[KeyContext::Code]),
+    (Action::UnfoldAll, "unfold-all", &[KeyContext::Code]),
+    (Action::ToggleComment, "toggle-comment", &[KeyContext::Code]),
+    (Action::ToggleWrap, "toggle-wrap", &[KeyContext::Code]),
+    (Action::MoveLinesUp, "move-lines-up", &[KeyContext::Code]),
+    (Action::MoveLinesDown, "move-lines-down", &[KeyContext::Code]),
+    (Action::DuplicateLines, "duplicate-lines", &[KeyContext::Code]),
+    (Action::JoinLines, "join-lines", &[KeyContext::Code]),
+    (Action::DeleteLines, "delete-lines", &[KeyContext::Code]),
+    (Action::RecordMacro, "record-macro", &[KeyContext::Code]),
+    (Action::PlayMacro, "play-macro", &[KeyContext::Code]),
+
+    (Action::OutlineUp, "outline-up", &[KeyContext::Files]),
+    (Action::OutlineDown, "outline-down", &[KeyContext::Files]),
+    (Action::OutlineJump, "outline-jump", &[KeyContext::Files]),
+
+    (Action::TabLeft, "tab-left", &[KeyContext::Tabs]),
+    (Action::TabRight, "tab-right", &[KeyContext::Tabs]),
+    (Action::MoveTabLeft, "move-tab-left", &[KeyContext::Tabs]),
+    (Action::MoveTabRight, "move-tab-right", &[KeyContext::Tabs]),
+    (Action::OpenTab, "open-tab", &[KeyContext::Tabs]),
+    (Action::CloseTab, "close-tab", &[KeyContext::Tabs]),
+
+    (Action::NextPanel, "next-panel", &[KeyContext::Prompt]),
+    (Action::SwitchFileView, "switch-file-view", &[KeyContext::Prompt]),
+    (Action::HistoryUp, "history-up", &[KeyContext::Prompt]),
+    (Action::HistoryDown, "history-down", &[KeyContext::Prompt]),
+
+    (Action::CompletePath, "complete-path", &[KeyContext::Menu]),
+    (Action::SettingUp, "setting-up", &[KeyContext::Menu]),
+    (Action::SettingDown, "setting-down", &[KeyContext::Menu]),
+    (Action::SettingLeft, "setting-left", &[KeyContext::Menu]),
+    (Action::SettingRight, "setting-right", &[KeyContext::Menu]),
+
+    (Action::Confirm, "confirm", &[KeyContext::Prompt, KeyContext::Menu]),
+    (Action::DeleteChar, "delete-char", &[KeyContext::Prompt, KeyContext::Menu]),
+];
+
+impl Action {
+    pub fn FromName (name: &str) -> Option <Action> {
+        ACTIONS.iter().find(|(_, actionName, _)| *actionName == name).map(|(action, _, _)| *action)
+    }
+
+    pub fn Name (&self) -> &'static str {
+        ACTIONS.iter().find(|(action, _, _)| action == self).map(|(_, name, _)| *name).unwrap_or_default()
+    }
+
+    pub fn IsAvailableIn (&self, context: KeyContext) -> bool {
+        ACTIONS.iter().any(|(action, _, contexts)| action == self && contexts.contains(&context))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChordKey {
+    Code (KeyCode),
+    Char (char),
+}
+
+// A key along with the modifiers held with it (the modifiers are stored as bit flags so the order doesn't matter)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub modifiers: u8,
+    pub key: ChordKey,
+}
+
+impl KeyChord {
+    fn ModifierBit (modifier: &KeyModifiers) -> u8 {
+        match modifier {
+            KeyModifiers::Shift => 1,
+            KeyModifiers::Command => 2,
+            KeyModifiers::Option => 4,
+            KeyModifiers::Control => 8,
+        }
+    }
+
+    pub fn Contains (&self, modifier: &KeyModifiers) -> bool {
+        self.modifiers & KeyChord::ModifierBit(modifier) != 0
+    }
+
+    // Parses chords like "cmd+shift+z", "alt+left", or "tab"
+    pub fn Parse (text: &str) -> Result <KeyChord, String> {
+        let parts: Vec <&str> = text.split('+').map(str::trim).collect();
+        let Some((key, modifiers)) = parts.split_last() else {
+            return Err(format!("Empty key chord '{text}'"));
+        };
+
+        let mut chord = KeyChord {
+            modifiers: 0,
+            key: match KeyCode::FromName(&key.to_lowercase()) {
+                Some(KeyCode::BackTab) => ChordKey::Code(KeyCode::Tab),
+                Some(code) => ChordKey::Code(code),
+                None if key.chars().count() == 1 => ChordKey::Char(key.to_lowercase().chars().next().unwrap_or_default()),
+                None => return Err(format!("Unknown key '{key}' in '{text}'")),
+            },
+        };
+        for modifier in modifiers {
+            let Some(modifier) = KeyModifiers::FromName(&modifier.to_lowercase()) else {
+                return Err(format!("Unknown modifier '{modifier}' in '{text}'"));
+            };
+            chord.modifiers |= KeyChord::ModifierBit(&modifier);
+        }
+        // back tab is just shift + tab as far as bindings go
+        if key.eq_ignore_ascii_case("backtab") {
+            chord.modifiers |= KeyChord::ModifierBit(&KeyModifiers::Shift);
+        }
+        Ok(chord)
+    }
+
+    // All the chords pressed this frame (usually only one)
+    fn FromEvents (keyEvents: &KeyParser) -> Vec <KeyChord> {
+        let modifiers = keyEvents.keyModifiers.iter().fold(0, |bits, modifier| bits | KeyChord::ModifierBit(modifier));
+        let codes = keyEvents.keyEvents.iter()
+            .filter(|(_, pressed)| **pressed)
+            .map(|(code, _)| match code {
+                KeyCode::BackTab => ChordKey::Code(KeyCode::Tab),  // shift is already in the modifiers
+                _ => ChordKey::Code(*code),
+            });
+        let chars = keyEvents.charEvents.iter().map(|chr| ChordKey::Char(chr.to_ascii_lowercase()));
+        codes.chain(chars).map(|key| KeyChord { modifiers, key }).collect()
+    }
+
+    fn WithoutShift (&self) -> KeyChord {
+        KeyChord {
+            modifiers: self.modifiers & !KeyChord::ModifierBit(&KeyModifiers::Shift),
+            key: self.key,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    pub name: String,
+    bindings: HashMap <KeyContext, HashMap <KeyChord, Action>>,
+}
+
+impl Default for Keymap {
+    fn default () -> Keymap {
+        Keymap::Preset(DEFAULT_KEYMAP).expect("The default keymap is missing")
+    }
+}
+
+impl Keymap {
+    pub fn IsPreset (name: &str) -> bool {
+        PRESETS.iter().any(|(preset, _)| *preset == name)
+    }
+
+    pub fn Preset (name: &str) -> Option <Keymap> {
+        let (_, json) = PRESETS.iter().find(|(preset, _)| *preset == name)?;
+        let json = serde_json::from_str(json).expect("A bundled keymap isn't valid json");
+        let mut keymap = Keymap {
+            name: name.to_string(),
+            bindings: HashMap::new(),
+        };
+        keymap.Overlay(&json).expect("A bundled keymap is invalid");
+        Some(keymap)
+    }
+
+    // every keymap that can be loaded (the presets and any custom ones), sorted
+    pub fn KeymapNames () -> Vec <String> {
+        let mut names: Vec <String> = PRESETS.iter().map(|(preset, _)| preset.to_string()).collect();
+        if let Some(home) = dirs::home_dir() {
+            names.extend(DataManager::JsonNames(&home.join(".config/termedit/keymaps")));
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // Loads a keymap by name
+    // Keymaps in ~/.config/termedit/keymaps are layered on top of a preset; either the preset
+    // with the same name, or the one named by "base" (standard if left out)
+    pub fn LoadKeymap (name: &str) -> Result <Keymap, String> {
+        let path = dirs::home_dir()
+            .map(|home| home.join(".config/termedit/keymaps").join(format!("{name}.json")))
+            .filter(|path| path.exists());
+        let Some(path) = path else {
+            return Keymap::Preset(name).ok_or(format!("No keymap named '{name}' found"));
+        };
+
+        let json = DataManager::LoadJson(&path.to_string_lossy())
+            .map_err(|error| format!("Failed to load keymap '{name}': {error}"))?;
+        let base = match Keymap::IsPreset(name) {
+            true => name,
+            false => json.get("base").and_then(Value::as_str).unwrap_or("standard"),
+        };
+        let mut keymap = Keymap::Preset(base).ok_or(format!("Unknown base keymap '{base}'"))?;
+        keymap.name = name.to_string();
+        keymap.Overlay(&json).map_err(|error| format!("Keymap '{name}': {error}"))?;
+        Ok(keymap)
+    }
+
+    // Adds (or replaces) the bindings in the json; binding a chord to null removes it
+    // Every problem gets collected so they can all be reported at once
+    fn Overlay (&mut self, json: &Value) -> Result <(), String> {
+        let Some(sections) = json.as_object() else {
+            return Err(String::from("A keymap has to be a json object"));
+        };
+
+        let mut problems = vec![];
+        for (section, table) in sections {
+            if matches!(section.as_str(), "name" | "base") {  continue;  }
+            let Some(context) = KeyContext::FromName(section) else {
+                problems.push(format!("Unknown context '{section}'"));
+                continue;
+            };
+            let Some(table) = table.as_object() else {
+                problems.push(format!("The '{section}' bindings have to be a json object"));
+                continue;
+            };
+
+            // different spellings (cmd+s and command+s) end up as the same chord
+            let mut bound: HashMap <KeyChord, (&String, Option <Action>)> = HashMap::new();
+            for (chordName, actionName) in table {
+                let chord = match KeyChord::Parse(chordName) {
+                    Ok(chord) => chord,
+                    Err(error) => {
+                        problems.push(format!("{section}: {error}"));
+                        continue;
+                    },
+                };
+                let action = match actionName {
+                    Value::Null => None,
+                    Value::String(actionName) => match Action::FromName(actionName) {
+                        Some(action) if action.IsAvailableIn(context) => Some(action),
+                        Some(_) => {
+                            problems.push(format!("{section}: '{actionName}' can't be used in {section}"));
+                            continue;
+                        },
+                        None => {
+                            problems.push(format!("{section}: Unknown action '{actionName}'"));
+                            continue;
+                        },
+                    },
+                    _ => {
+                        problems.push(format!("{section}: '{chordName}' has to be bound to an action name or null"));
+                        continue;
+                    },
+                };
+
+                if let Some((otherName, otherAction)) = bound.get(&chord) && *otherAction != action {
+                    problems.push(format!(
+                        "{section}: '{chordName}' and '{otherName}' are the same keys but are bound to different actions"
+                    ));
+                    continue;
+                }
+                bound.insert(chord, (chordName, action));
+            }
+
+            let bindings = self.bindings.entry(context).or_default();
+            for (chord, (_, action)) in bound {
+                match action {
+                    Some(action) => {  bindings.insert(chord, action);  },
+                    None => {  bindings.remove(&chord);  },
+                }
+            }
+        }
+
+        problems.extend(self.Validate());
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    // Catches bindings which can't work once everything is layered together
+    fn Validate (&self) -> Vec <String> {
+        let mut problems = vec![];
+        for (context, bindings) in &self.bindings {
+            for (chord, action) in bindings {
+                let ChordKey::Char(chr) = chord.key else {  continue;  };
+                let typed = !(chord.Contains(&KeyModifiers::Command) ||
+                    chord.Contains(&KeyModifiers::Control) ||
+                    chord.Contains(&KeyModifiers::Option));
+                if typed {
+                    // every context has something being typed into
+                    problems.push(format!(
+                        "{}: '{}' is bound to '{chr}', which would block typing it", context.Name(), action.Name()
+                    ));
+                } else if chord.Contains(&KeyModifiers::Control) && chord.Contains(&KeyModifiers::Shift) {
+                    // control + shift + key sends the same byte as control + key
+                    problems.push(format!(
+                        "{}: '{}' uses ctrl+shift+{chr}, which terminals send as ctrl+{chr}", context.Name(), action.Name()
+                    ));
+                }
+            }
+        }
+        problems.sort();
+        problems
+    }
+
+    // Finds the action for the keys pressed this frame
+    // An exact match wins; otherwise shift is ignored, since it only extends the selection (shift+left is still left)
+    pub fn GetAction (&self, context: KeyContext, keyEvents: &KeyParser) -> Option <Action> {
+        let bindings = self.bindings.get(&context)?;
+        let pressed = KeyChord::FromEvents(keyEvents);
+        pressed.iter()
+            .find_map(|chord| bindings.get(chord).copied())
+            .or_else(|| pressed.iter().find_map(|chord| bindings.get(&chord.WithoutShift()).copied()))
+    }
+}
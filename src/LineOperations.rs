@@ -0,0 +1,260 @@
+// operations on whole lines (moving, duplicating, joining, sorting, and deleting them). They're all made out of
+// the same single line edits typing makes, so undo/redo, the tokens, and the scopes are handled the usual way,
+// and each one is merged into a single undo group
+
+use crate::App as MainApp;
+use crate::{CodeTab, LuaScripts, RustAnalyzerLsp};
+use crate::CodeTabs::Edits;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortOptions {
+    pub caseInsensitive: bool,
+    pub unique: bool,  // drops repeated lines (compared the same way as they're sorted)
+}
+
+impl SortOptions {
+    // any of i (case-insensitive) and u (unique), like "iu"
+    pub fn Parse (text: &str) -> Result <SortOptions, String> {
+        let mut options = SortOptions::default();
+        for flag in text.chars() {
+            match flag {
+                'i' => options.caseInsensitive = true,
+                'u' => options.unique = true,
+                _ => return Err(format!("Unknown sort option '{flag}' (only i and u are supported)")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// joins two lines with a single space, leaving it out next to brackets and punctuation
+fn JoinText (left: &str, right: &str) -> String {
+    let (left, right) = (left.trim_end(), right.trim_start());
+    if right.is_empty() {  return left.to_string();  }
+    let spaced = !left.is_empty() &&
+        !left.ends_with(['(', '[', '{']) &&
+        !right.starts_with([')', ']', '}', ',', ';', '.']);
+    format!("{left}{}{right}", if spaced {  " "  } else {  ""  })
+}
+
+impl CodeTab {
+    // the selected lines, or just the cursor's line
+    pub fn LinesUnderCursor (&self) -> (usize, usize) {
+        if self.highlighting {  self.SelectedLines()  }
+        else {  (self.cursor.0, self.cursor.0)  }
+    }
+
+    async fn SetLine <'a> (&mut self,
+                           line: usize,
+                           text: &str,
+                           luaSyntaxHighlightScripts: &LuaScripts,
+                           rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        if self.lines[line] == text {  return;  }
+        let length = self.lines[line].len();
+        if length > 0 {
+            self.cursor = (line, length);
+            self.DelChars(length, 0, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        }
+        if !text.is_empty() {
+            self.cursor = (line, 0);
+            self.InsertChars(text.to_string(), luaSyntaxHighlightScripts, rustAnalyzer).await;
+        }
+    }
+
+    // adds a line at an index (past the first line) by breaking the end of the line above it
+    async fn InsertLine <'a> (&mut self,
+                              line: usize,
+                              text: &str,
+                              luaSyntaxHighlightScripts: &LuaScripts,
+                              rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        self.cursor = (line - 1, self.lines[line - 1].len());
+        self.LineBreakIn(false, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        self.SetLine(line, text, luaSyntaxHighlightScripts, rustAnalyzer).await;
+    }
+
+    // empties the line and then joins it with a neighbour (the only line is just emptied)
+    async fn RemoveLine <'a> (&mut self,
+                              line: usize,
+                              luaSyntaxHighlightScripts: &LuaScripts,
+                              rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        self.SetLine(line, "", luaSyntaxHighlightScripts, rustAnalyzer).await;
+        if self.lines.len() == 1 {  return;  }
+        // the last line goes onto the end of the one above it, anything else takes in the line below
+        let position =
+            if line + 1 == self.lines.len() {  (line - 1, self.lines[line - 1].len())  }
+            else {  (line, 0)  };
+        self.redoneBuffer.clear();
+        let edit = Edits::RemoveLine { position };
+        edit.Redo(self, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        self.changeBuffer.push(vec![Edits::Edit::RemoveLine(edit)]);
+    }
+
+    // swaps the lines from start to end for new ones as one undo group (the cursor is left for the caller to place)
+    pub async fn ReplaceLines <'a> (&mut self,
+                                    start: usize,
+                                    end: usize,
+                                    newLines: &[String],
+                                    luaSyntaxHighlightScripts: &LuaScripts,
+                                    rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let undoDepth = self.changeBuffer.len();
+        self.highlighting = false;
+        // the folded lines might not stay together
+        self.folds.retain(|(foldStart, foldEnd)| *foldEnd < start || *foldStart > end);
+        self.ClearRenderCache();
+
+        let oldLength = end - start + 1;
+        for (index, text) in newLines.iter().enumerate() {
+            if index < oldLength {
+                self.SetLine(start + index, text, luaSyntaxHighlightScripts, rustAnalyzer).await;
+            } else {
+                self.InsertLine(start + index, text, luaSyntaxHighlightScripts, rustAnalyzer).await;
+            }
+        }
+        for _ in newLines.len()..oldLength {
+            self.RemoveLine(start + newLines.len(), luaSyntaxHighlightScripts, rustAnalyzer).await;
+        }
+        self.MergeChanges(undoDepth);
+    }
+
+    // moves the lines under the cursor past the line above or below them (the selection goes with them)
+    pub async fn MoveLines <'a> (&mut self, up: bool, luaSyntaxHighlightScripts: &LuaScripts, rustAnalyzer: RustAnalyzerLsp<'a>) -> bool {
+        let (start, end) = self.LinesUnderCursor();
+        if up && start == 0 || !up && end + 1 >= self.lines.len() {  return false;  }
+        let (from, to) = if up {  (start - 1, end)  } else {  (start, end + 1)  };
+        let mut newLines = self.lines[from..=to].to_vec();
+        if up {  newLines.rotate_left(1);  } else {  newLines.rotate_right(1);  }
+
+        let (cursor, cursorEnd, highlighting) = (self.cursor, self.cursorEnd, self.highlighting);
+        self.ReplaceLines(from, to, &newLines, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        let Shift = |position: (usize, usize)| {
+            if up {  (position.0 - 1, position.1)  } else {  (position.0 + 1, position.1)  }
+        };
+        (self.cursor, self.highlighting) = (Shift(cursor), highlighting);
+        if highlighting {  self.cursorEnd = Shift(cursorEnd);  }
+        true
+    }
+
+    // copies the lines under the cursor to just below them, with the cursor (and selection) moving onto the copy
+    pub async fn DuplicateLines <'a> (&mut self, luaSyntaxHighlightScripts: &LuaScripts, rustAnalyzer: RustAnalyzerLsp<'a>) {
+        let (start, end) = self.LinesUnderCursor();
+        let mut newLines = self.lines[start..=end].to_vec();
+        newLines.extend_from_within(..);
+
+        let (cursor, cursorEnd, highlighting) = (self.cursor, self.cursorEnd, self.highlighting);
+        self.ReplaceLines(start, end, &newLines, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        let length = end - start + 1;
+        (self.cursor, self.highlighting) = ((cursor.0 + length, cursor.1), highlighting);
+        if highlighting {  self.cursorEnd = (cursorEnd.0 + length, cursorEnd.1);  }
+    }
+
+    // joins the selected lines (or the cursor's line and the one below it) into one, leaving the cursor where the last two met
+    pub async fn JoinLines <'a> (&mut self,
+                                 lines: Option <(usize, usize)>,
+                                 luaSyntaxHighlightScripts: &LuaScripts,
+                                 rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) -> bool {
+        let (start, mut end) = lines.unwrap_or(self.LinesUnderCursor());
+        if start == end {  end += 1;  }
+        if end >= self.lines.len() {  return false;  }
+
+        let mut joined = self.lines[start].clone();
+        let mut joinPoint = joined.len();
+        for line in &self.lines[start + 1..=end] {
+            joinPoint = joined.trim_end().len();
+            joined = JoinText(&joined, line);
+        }
+        self.ReplaceLines(start, end, &[joined], luaSyntaxHighlightScripts, rustAnalyzer).await;
+        self.cursor = (start, joinPoint);
+        true
+    }
+
+    // sorts the lines by their text
+    pub async fn SortLines <'a> (&mut self,
+                                 start: usize,
+                                 end: usize,
+                                 options: SortOptions,
+                                 luaSyntaxHighlightScripts: &LuaScripts,
+                                 rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let Key = |line: &String| if options.caseInsensitive {  line.to_lowercase()  } else {  line.clone()  };
+        let mut newLines = self.lines[start..=end].to_vec();
+        newLines.sort_by_key(Key);
+        if options.unique {  newLines.dedup_by_key(|line| Key(line));  }
+
+        self.ReplaceLines(start, end, &newLines, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        self.cursor = (start, 0);
+    }
+
+    // deletes the lines (without touching the clipboard)
+    pub async fn DeleteLines <'a> (&mut self,
+                                   start: usize,
+                                   end: usize,
+                                   luaSyntaxHighlightScripts: &LuaScripts,
+                                   rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let column = self.cursor.1;
+        self.ReplaceLines(start, end, &[], luaSyntaxHighlightScripts, rustAnalyzer).await;
+        let line = std::cmp::min(start, self.lines.len() - 1);
+        self.cursor = (line, std::cmp::min(column, self.lines[line].len()));
+        while !self.lines[line].is_char_boundary(self.cursor.1) {  self.cursor.1 -= 1;  }
+    }
+}
+
+impl <'a> MainApp <'a> {
+    pub(crate) async fn MoveLines<'b> (&mut self, up: bool, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        self.codeTabs.tabs[self.lastTab].MoveLines(up, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+    }
+
+    pub(crate) async fn DuplicateLines<'b> (&mut self, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        self.codeTabs.tabs[self.lastTab].DuplicateLines(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+    }
+
+    pub(crate) async fn JoinLines<'b> (&mut self, lines: Option <(usize, usize)>, rustAnalyzer: RustAnalyzerLsp<'b>) -> bool {
+        self.codeTabs.tabs[self.lastTab].JoinLines(lines, &self.luaSyntaxHighlightScripts, rustAnalyzer).await
+    }
+
+    // without a range or selection it's the whole file
+    pub(crate) async fn SortLines<'b> (&mut self, lines: Option <(usize, usize)>, options: SortOptions, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let (start, end) = lines.unwrap_or(
+            if tab.highlighting {  tab.SelectedLines()  }
+            else {  (0, tab.lines.len() - 1)  }
+        );
+        tab.SortLines(start, end, options, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+    }
+
+    pub(crate) async fn DeleteLines<'b> (&mut self, lines: Option <(usize, usize)>, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let (start, end) = lines.unwrap_or(tab.LinesUnderCursor());
+        tab.DeleteLines(start, end, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn JoinsWithOneSpace () {
+        assert_eq!(JoinText("let x =   ", "   5;"), "let x = 5;");
+        assert_eq!(JoinText("", "  text"), "text");
+        assert_eq!(JoinText("text", "   "), "text");
+        // no space just inside brackets or before punctuation
+        assert_eq!(JoinText("call(", "  a"), "call(a");
+        assert_eq!(JoinText("vec![a", "]"), "vec![a]");
+        assert_eq!(JoinText("a", ", b"), "a, b");
+        assert_eq!(JoinText("x", ".len()"), "x.len()");
+    }
+
+    #[test]
+    fn ParsesSortOptions () {
+        assert_eq!(SortOptions::Parse(""), Ok(SortOptions::default()));
+        assert_eq!(SortOptions::Parse("iu"), Ok(SortOptions {  caseInsensitive: true, unique: true  }));
+        assert_eq!(SortOptions::Parse("u"), Ok(SortOptions {  caseInsensitive: false, unique: true  }));
+        assert!(SortOptions::Parse("ix").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+// keyboard macros; the keys pressed each frame get recorded into a register and are played back
+// through the usual key handling (the registers are saved to ~/.config/termedit/macros.json)
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde_json::{json, Map, Value};
+use arboard::Clipboard;
+
+use crate::App as MainApp;
+use crate::RustAnalyzerLsp;
+use crate::eventHandler::{KeyCode, KeyFrame, KeyModifiers, KeyParser};
+
+// (app state, tab, the tab's (cursor, undo depth, line count))
+type ReplayState = (crate::AppState, usize, Option <((usize, usize), usize, usize)>);
+
+// the register the keymap's record and play actions use (vim mode names its own)
+pub const DEFAULT_MACRO_REGISTER: char = 'q';
+
+#[derive(Debug, Default)]
+pub struct Macros {
+    registers: HashMap <char, Vec <KeyFrame>>,
+    recording: Option <(char, Vec <KeyFrame>)>,
+    startedThisFrame: bool,  // the keys that started the recording aren't part of it
+    queued: Option <(char, usize)>,  // (register, times to play it)
+    lastPlayed: Option <char>,
+    playing: bool,
+    pub failed: bool,  // set when a key couldn't do anything while playing (the rest of the replays are skipped)
+}
+
+fn MacroPath () -> Option <PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config/termedit/macros.json"))
+}
+
+fn FrameToJson (frame: &KeyFrame) -> Value {
+    json!({
+        "keys": frame.keys.iter().map(KeyCode::Name).collect::<Vec <String>>(),
+        "chars": frame.chars.iter().collect::<String>(),
+        "modifiers": frame.modifiers.iter().map(KeyModifiers::Name).collect::<Vec <&str>>(),
+        "pastes": frame.pastes,
+    })
+}
+
+fn FrameFromJson (json: &Value) -> Option <KeyFrame> {
+    let names = |field: &str| -> Vec <&str> {
+        json.get(field)
+            .and_then(Value::as_array)
+            .map(|items| items.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default()
+    };
+    Some(KeyFrame {
+        keys: names("keys").into_iter().map(KeyCode::FromName).collect::<Option <Vec <KeyCode>>>()?,
+        chars: json.get("chars").and_then(Value::as_str).unwrap_or("").chars().collect(),
+        modifiers: names("modifiers").into_iter().map(KeyModifiers::FromName).collect::<Option <Vec <KeyModifiers>>>()?,
+        pastes: names("pastes").into_iter().map(String::from).collect(),
+    })
+}
+
+impl Macros {
+    // a missing or broken file just means there aren't any saved macros
+    pub fn Load () -> Macros {
+        let mut macros = Macros::default();
+        let Some(text) = MacroPath().and_then(|path| std::fs::read_to_string(path).ok()) else {  return macros;  };
+        let Ok(Value::Object(registers)) = serde_json::from_str::<Value>(&text) else {  return macros;  };
+        for (name, frames) in &registers {
+            let (Some(register), Some(frames)) = (name.chars().next(), frames.as_array()) else {  continue;  };
+            if let Some(frames) = frames.iter().map(FrameFromJson).collect::<Option <Vec <KeyFrame>>>() {
+                macros.registers.insert(register, frames);
+            }
+        }
+        macros
+    }
+
+    fn Save (&self) -> Result <(), String> {
+        let path = MacroPath().ok_or("There's no home directory to save the macros in")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|error| format!("Couldn't save the macros: {error}"))?;
+        }
+        let registers: Map <String, Value> = self.registers.iter()
+            .map(|(register, frames)| (register.to_string(), Value::Array(frames.iter().map(FrameToJson).collect())))
+            .collect();
+        let text = serde_json::to_string_pretty(&Value::Object(registers)).unwrap_or_default();
+        std::fs::write(path, text).map_err(|error| format!("Couldn't save the macros: {error}"))
+    }
+
+    pub fn IsRecording (&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn StartRecording (&mut self, register: char) {
+        self.recording = Some((register, vec![]));
+        self.startedThisFrame = true;
+    }
+
+    pub fn StopRecording (&mut self) -> Result <(), String> {
+        let Some((register, frames)) = self.recording.take() else {  return Ok(());  };
+        self.registers.insert(register, frames);
+        self.Save()
+    }
+
+    // called once a frame, after the keys have been handled
+    pub fn Record (&mut self, frame: KeyFrame) {
+        if std::mem::take(&mut self.startedThisFrame) || self.playing || frame.IsEmpty() {  return;  }
+        if let Some((_, frames)) = &mut self.recording {
+            frames.push(frame);
+        }
+    }
+
+    // plays the macro at the end of the frame (no register is the last one played); macros can't start other macros
+    pub fn Queue (&mut self, register: Option <char>, count: usize) {
+        if self.playing {  return;  }
+        let Some(register) = register.or(self.lastPlayed) else {  return;  };
+        self.queued = Some((register, count.max(1)));
+    }
+
+    pub fn StatusText (&self) -> Option <String> {
+        self.recording.as_ref().map(|(register, _)| format!("recording @{register}"))
+    }
+}
+
+impl <'a> MainApp <'a> {
+    pub(crate) fn ToggleMacroRecording (&mut self, register: char) {
+        if self.macros.IsRecording() {
+            if let Err(error) = self.macros.StopRecording() {
+                self.debugInfo = error;
+            }
+        } else {
+            self.macros.StartRecording(register);
+        }
+    }
+
+    // anything a replay could have changed; a replay that changes none of it is treated as failing
+    fn ReplayState (&self) -> ReplayState {
+        let tab = self.codeTabs.tabs.get(self.lastTab)
+            .map(|tab| (tab.cursor, tab.changeBuffer.len(), tab.lines.len()));
+        (self.appState.clone(), self.lastTab, tab)
+    }
+
+    // each replay goes through the key handling as if it was typed, and is undone as one group
+    pub(crate) async fn PlayQueuedMacro<'b> (&mut self, clipBoard: &mut Clipboard, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let Some((register, count)) = self.macros.queued.take() else {  return;  };
+        let Some(frames) = self.macros.registers.get(&register).cloned() else {
+            self.debugInfo = format!("There's no macro in register {register}");
+            return;
+        };
+        self.macros.lastPlayed = Some(register);
+        self.macros.playing = true;
+        self.macros.failed = false;
+
+        for _ in 0..count {
+            let progress = self.ReplayState();
+            let tabIndex = self.lastTab;
+            let undoDepth = self.codeTabs.tabs.get(tabIndex).map(|tab| tab.changeBuffer.len());
+            for frame in &frames {
+                self.HandleKeyEvents(&KeyParser::FromFrame(frame), clipBoard, rustAnalyzer).await;
+                if self.macros.failed {  break;  }
+            }
+            if let (Some(undoDepth), Some(tab)) = (undoDepth, self.codeTabs.tabs.get_mut(tabIndex)) {
+                tab.MergeChanges(undoDepth);
+            }
+            if self.macros.failed || self.ReplayState() == progress {  break;  }
+        }
+        self.macros.playing = false;
+    }
+}
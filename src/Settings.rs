@@ -0,0 +1,606 @@
+// the settings that get saved to ~/.config/termedit/config.json (a project's .termedit.json can override them)
+// the settings menu and the set command are both built off of this list, so new settings show up in both
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use serde_json::{Map, Value};
+
+use crate::App as MainApp;
+use crate::{Keymap, Vim};
+use crate::Colors::ColorBindings;
+use crate::TermRender::ColorTypes;
+
+// the file in a project's folder that overrides the settings while it's open
+pub const PROJECT_CONFIG: &str = ".termedit.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    ColorType,
+    Keymap,
+    Theme,
+    TabWidth,
+    Indent,
+    DetectIndent,
+    Vim,
+    ModifyOtherKeys,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    Choice(&'static [(&'static str, &'static str)]),  // (value, what the menu shows)
+    Keymap,
+    Theme,
+    Number(usize, usize),  // the smallest and largest it can be
+    Toggle,
+}
+
+#[derive(Debug)]
+pub struct SettingInfo {
+    pub setting: Setting,
+    pub key: &'static str,  // the name in the config files (and for set)
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: SettingKind,
+}
+
+pub static SETTINGS: &[SettingInfo] = &[
+    SettingInfo {
+        setting: Setting::ColorType, key: "colortype", name: "Color Mode",
+        description: "Not all terminals accept all color modes. If the colors are messed up, try lowering this",
+        kind: SettingKind::Choice(&[("basic", "Basic"), ("partial", "8-bit"), ("true", "24-bit")]),
+    },
+    SettingInfo {
+        setting: Setting::Keymap, key: "keymap", name: "Keymap",
+        description: "The key bindings (mac-iterm uses the command key, standard uses control); custom keymaps go in ~/.config/termedit/keymaps",
+        kind: SettingKind::Keymap,
+    },
+    SettingInfo {
+        setting: Setting::Theme, key: "theme", name: "Theme",
+        description: "The colors for the code and editor; custom themes go in ~/.config/termedit/themes",
+        kind: SettingKind::Theme,
+    },
+    SettingInfo {
+        setting: Setting::TabWidth, key: "tabwidth", name: "Tab Width",
+        description: "The number of spaces tab indents by, and how wide a tab character is drawn",
+        kind: SettingKind::Number(1, 16),
+    },
+    SettingInfo {
+        setting: Setting::Indent, key: "indent", name: "Indent With",
+        description: "Whether tab indents with spaces or tab characters (languages can have their own in the config file)",
+        kind: SettingKind::Choice(&[("spaces", "Spaces"), ("tabs", "Tabs")]),
+    },
+    SettingInfo {
+        setting: Setting::DetectIndent, key: "detectindent", name: "Detect Indent",
+        description: "Indent files the way they're already indented (tabs or the number of spaces), over the settings",
+        kind: SettingKind::Toggle,
+    },
+    SettingInfo {
+        setting: Setting::Vim, key: "vim", name: "Vim Mode",
+        description: "Vim style modal editing (-vim also turns it on or off while editing)",
+        kind: SettingKind::Toggle,
+    },
+    SettingInfo {
+        setting: Setting::ModifyOtherKeys, key: "modifyotherkeys", name: "modifyOtherKeys",
+        description: "Has terminals without the kitty keyboard protocol send xterm's codes for modifier combinations (turn off if a terminal misreads the request)",
+        kind: SettingKind::Toggle,
+    },
+];
+
+impl SettingInfo {
+    // every value it can take, in the order the menu steps through them (none for numbers)
+    pub fn Choices (&self) -> Vec <String> {
+        match self.kind {
+            SettingKind::Choice(choices) => choices.iter().map(|(value, _)| value.to_string()).collect(),
+            SettingKind::Keymap => Keymap::Keymap::KeymapNames(),
+            SettingKind::Theme => ColorBindings::ThemeNames(),
+            SettingKind::Number(..) => vec![],
+            SettingKind::Toggle => vec![String::from("off"), String::from("on")],
+        }
+    }
+
+    pub fn Label (&self, value: &str) -> String {
+        let SettingKind::Choice(choices) = self.kind else {  return value.to_string();  };
+        choices.iter()
+            .find(|(choice, _)| *choice == value)
+            .map(|(_, label)| label.to_string())
+            .unwrap_or(value.to_string())
+    }
+
+    // the value the way the rest of the settings code expects it (on/off for toggles), or why it isn't valid
+    pub fn Check (&self, value: &str) -> Result <String, String> {
+        let value = value.trim();
+        match self.kind {
+            SettingKind::Number(min, max) => match value.parse::<usize>() {
+                Ok(number) if (min..=max).contains(&number) => Ok(number.to_string()),
+                _ => Err(format!("{} has to be from {min} to {max}, not '{value}'", self.key)),
+            },
+            SettingKind::Toggle => match value {
+                "on" | "true" => Ok(String::from("on")),
+                "off" | "false" => Ok(String::from("off")),
+                _ => Err(format!("{} has to be on or off, not '{value}'", self.key)),
+            },
+            _ if self.Choices().iter().any(|choice| choice == value) => Ok(value.to_string()),
+            _ => Err(format!("{} has to be one of {}, not '{value}'", self.key, self.Choices().join(", "))),
+        }
+    }
+
+    fn ToJson (&self, value: &str) -> Value {
+        match self.kind {
+            SettingKind::Number(..) => value.parse::<usize>().map(Value::from).unwrap_or(Value::Null),
+            SettingKind::Toggle => Value::Bool(value == "on"),
+            _ => Value::String(value.to_string()),
+        }
+    }
+}
+
+// a language's own indent (by file extension, or the whole name for files like Makefile); anything left out uses the settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndentOverride {
+    pub tabWidth: Option <usize>,
+    pub useTabs: Option <bool>,
+}
+
+// what the open project's config replaced, so it can be put back (and left out of the saved settings)
+#[derive(Debug, Default)]
+pub struct ProjectOverrides {
+    pub settings: Vec <(Setting, String)>,  // the values from before the project's config
+    pub configured: Vec <Setting>,  // the settings only the project's config picked
+    pub languageIndents: Option <HashMap <String, IndentOverride>>,  // from before, if the project had its own
+}
+
+// everything read out of a config file
+#[derive(Debug, Default)]
+pub struct Config {
+    pub settings: Vec <(Setting, String)>,
+    pub languages: Vec <(String, IndentOverride)>,
+    pub warnings: Vec <String>,
+}
+
+pub fn FindSetting (key: &str) -> Option <&'static SettingInfo> {
+    SETTINGS.iter().find(|info| info.key == key)
+}
+
+// everything set can be completed to (numbers only get as far as the =)
+pub fn SettingCompletions () -> Vec <String> {
+    SETTINGS.iter()
+        .flat_map(|info| match info.kind {
+            SettingKind::Number(..) => vec![format!("{}=", info.key)],
+            _ => info.Choices().into_iter().map(|choice| format!("{}={choice}", info.key)).collect(),
+        })
+        .collect()
+}
+
+// key=value for the set command
+pub fn ParseAssignment (text: &str) -> Result <(&'static SettingInfo, String), String> {
+    let (key, value) = text.split_once('=').ok_or(String::from("Usage: set <setting>=<value>"))?;
+    let info = FindSetting(key.trim()).ok_or(format!("Unknown setting '{}'", key.trim()))?;
+    Ok((info, info.Check(value)?))
+}
+
+pub fn ConfigPath () -> Option <PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config/termedit/config.json"))
+}
+
+// the text a json value would be typed as for set
+fn ValueText (value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Bool(on) => String::from(if *on {  "on"  } else {  "off"  }),
+        other => other.to_string(),
+    }
+}
+
+// the "languages" section, e.g. {"go": {"indent": "tabs"}, "js": {"tabwidth": 2}}
+fn ReadLanguages (fileName: &str, languages: &Value, config: &mut Config) {
+    let Value::Object(languages) = languages else {
+        config.warnings.push(format!("{fileName}: languages has to be a json object"));
+        return;
+    };
+    for (language, values) in languages {
+        let Value::Object(values) = values else {
+            config.warnings.push(format!("{fileName}: languages.{language} has to be a json object"));
+            continue;
+        };
+        let mut indent = IndentOverride::default();
+        for (key, value) in values {
+            let checked = match FindSetting(key) {
+                Some(info) if matches!(info.setting, Setting::TabWidth | Setting::Indent) => info.Check(&ValueText(value)),
+                _ => Err(format!("only tabwidth and indent can be set per language, not '{key}'")),
+            };
+            match checked {
+                Ok(value) if key == "tabwidth" => indent.tabWidth = value.parse::<usize>().ok(),
+                Ok(value) => indent.useTabs = Some(value == "tabs"),
+                Err(error) => config.warnings.push(format!("{fileName}: languages.{language}: {error}")),
+            }
+        }
+        config.languages.push((language.clone(), indent));
+    }
+}
+
+// the settings in a config file, along with anything wrong with it (a missing file just has nothing in it)
+pub fn ReadConfig (path: &Path) -> Config {
+    let fileName = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut config = Config::default();
+    let Ok(text) = std::fs::read_to_string(path) else {  return config;  };
+    let values = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(values)) => values,
+        Ok(_) => {
+            config.warnings.push(format!("{fileName} has to be a json object"));
+            return config;
+        },
+        Err(error) => {
+            config.warnings.push(format!("{fileName} isn't valid json: {error}"));
+            return config;
+        },
+    };
+
+    for (key, value) in &values {
+        if key == "languages" {
+            ReadLanguages(&fileName, value, &mut config);
+            continue;
+        }
+        let Some(info) = FindSetting(key) else {
+            config.warnings.push(format!("{fileName}: unknown setting '{key}'"));
+            continue;
+        };
+        match info.Check(&ValueText(value)) {
+            Ok(value) => config.settings.push((info.setting, value)),
+            Err(error) => config.warnings.push(format!("{fileName}: {error}")),
+        }
+    }
+    config
+}
+
+// how the file is already indented, if it's clear enough to tell (the width is left out for tabs)
+pub fn DetectIndent (lines: &[String]) -> Option <IndentOverride> {
+    let mut tabLines = 0;
+    let mut spaceLines = 0;
+    let mut widths: HashMap <usize, usize> = HashMap::new();
+    let mut lastIndent = 0;
+    for line in lines {
+        let trimmed = line.trim_start();
+        // blank lines and the * lines of block comments don't say anything about the indent
+        if trimmed.is_empty() || trimmed.starts_with('*') {  continue;  }
+        if line.starts_with('\t') {
+            tabLines += 1;
+            continue;
+        }
+
+        let indent = line.len() - trimmed.len();
+        if indent > 0 {  spaceLines += 1;  }
+        // steps of one space are almost always lining things up rather than indenting
+        if indent > lastIndent && (2..=8).contains(&(indent - lastIndent)) {
+            *widths.entry(indent - lastIndent).or_default() += 1;
+        }
+        lastIndent = indent;
+    }
+
+    if tabLines > spaceLines {
+        return Some(IndentOverride {  tabWidth: None, useTabs: Some(true)  });
+    }
+    widths.into_iter()
+        .max_by_key(|(width, count)| (*count, std::cmp::Reverse(*width)))
+        .map(|(width, _)| IndentOverride {  tabWidth: Some(width), useTabs: Some(false)  })
+}
+
+impl <'a> MainApp <'a> {
+    pub(crate) fn SettingValue (&self, setting: Setting) -> String {
+        match setting {
+            Setting::ColorType => String::from(match self.colorMode.colorType {
+                ColorTypes::Basic => "basic",
+                ColorTypes::Partial => "partial",
+                ColorTypes::True => "true",
+            }),
+            Setting::Keymap => self.keymap.name.clone(),
+            Setting::Theme => self.colorMode.colorBindings.name.clone(),
+            Setting::TabWidth => self.tabWidth.to_string(),
+            Setting::Indent => String::from(if self.useTabs {  "tabs"  } else {  "spaces"  }),
+            Setting::DetectIndent => String::from(if self.detectIndent {  "on"  } else {  "off"  }),
+            Setting::Vim => String::from(if self.vim.is_some() {  "on"  } else {  "off"  }),
+            Setting::ModifyOtherKeys => String::from(if self.modifyOtherKeys {  "on"  } else {  "off"  }),
+        }
+    }
+
+    // the value has to have gone through SettingInfo::Check first
+    pub(crate) fn ApplySetting (&mut self, setting: Setting, value: &str) -> Result <(), String> {
+        match setting {
+            Setting::ColorType => {
+                self.colorMode.colorType = match value {
+                    "basic" => ColorTypes::Basic,
+                    "partial" => ColorTypes::Partial,
+                    _ => ColorTypes::True,
+                };
+            },
+            Setting::Keymap => self.keymap = Keymap::Keymap::LoadKeymap(value)?,
+            Setting::Theme => {
+                self.colorMode.colorBindings = ColorBindings::LoadTheme(value)?;
+                // saved by the file's name, which a custom theme's own name might not match
+                self.colorMode.colorBindings.name = value.to_string();
+                // the cached lines still have the old colors
+                for tab in self.codeTabs.tabs.iter_mut() {
+                    tab.ClearRenderCache();
+                }
+            },
+            Setting::TabWidth => {
+                self.tabWidth = value.parse::<usize>().map_err(|error| error.to_string())?;
+                self.UpdateTabIndents();
+            },
+            Setting::Indent => {
+                self.useTabs = value == "tabs";
+                self.UpdateTabIndents();
+            },
+            Setting::DetectIndent => {
+                self.detectIndent = value == "on";
+                self.UpdateTabIndents();
+            },
+            Setting::Vim => {
+                if (value == "on") != self.vim.is_some() {
+                    self.vim = (value == "on").then(Vim::VimState::default);
+                }
+            },
+            // switched in the terminal by the main loop, since that's what can talk to it
+            Setting::ModifyOtherKeys => self.modifyOtherKeys = value == "on",
+        }
+        Ok(())
+    }
+
+    // the tab width and whether to use tabs for a file: the settings, then its language's, then how the file's already indented
+    pub(crate) fn IndentFor (&self, fileName: &str, lines: &[String]) -> (usize, bool) {
+        let mut indent = (self.tabWidth, self.useTabs);
+        let extension = fileName.split('.').next_back().unwrap_or("");
+        let mut overrides = vec![self.languageIndents.get(extension).copied()];
+        if self.detectIndent {
+            overrides.push(DetectIndent(lines));
+        }
+        for indentOverride in overrides.into_iter().flatten() {
+            indent.0 = indentOverride.tabWidth.unwrap_or(indent.0);
+            indent.1 = indentOverride.useTabs.unwrap_or(indent.1);
+        }
+        indent
+    }
+
+    pub(crate) fn UpdateTabIndents (&mut self) {
+        let indents: Vec <(usize, bool)> = self.codeTabs.tabs.iter()
+            .map(|tab| self.IndentFor(&tab.fileName, &tab.lines))
+            .collect();
+        for (tab, (tabWidth, useTabs)) in self.codeTabs.tabs.iter_mut().zip(indents) {
+            if tab.tabWidth != tabWidth {
+                tab.ClearRenderCache();
+            }
+            (tab.tabWidth, tab.useTabs) = (tabWidth, useTabs);
+        }
+    }
+
+    // applies a config file over the current settings, handing back what it replaced; any problems are left
+    // in the command line
+    pub(crate) fn LoadSettings (&mut self, path: &Path) -> ProjectOverrides {
+        let config = ReadConfig(path);
+        let mut replaced = ProjectOverrides::default();
+        let mut warnings = config.warnings;
+        for (setting, value) in config.settings {
+            let previous = self.SettingValue(setting);
+            match self.ApplySetting(setting, &value) {
+                Ok(_) => {
+                    replaced.settings.push((setting, previous));
+                    if !self.configuredSettings.contains(&setting) {
+                        self.configuredSettings.push(setting);
+                        replaced.configured.push(setting);
+                    }
+                },
+                Err(error) => warnings.push(error),
+            }
+        }
+        if !config.languages.is_empty() {
+            replaced.languageIndents = Some(self.languageIndents.clone());
+            self.languageIndents.extend(config.languages);
+            self.UpdateTabIndents();
+        }
+        if !warnings.is_empty() {
+            self.commandError = Some(warnings.join("; "));
+        }
+        replaced
+    }
+
+    // swaps the last project's overrides for the ones in the folder being opened
+    pub(crate) fn LoadProjectSettings (&mut self, folder: &Path) {
+        let previous = std::mem::take(&mut self.projectOverrides);
+        // backwards, so a setting the file had twice ends up with its first value
+        for (setting, value) in previous.settings.into_iter().rev() {
+            let _ = self.ApplySetting(setting, &value);
+        }
+        self.configuredSettings.retain(|setting| !previous.configured.contains(setting));
+        if let Some(languageIndents) = previous.languageIndents {
+            self.languageIndents = languageIndents;
+            self.UpdateTabIndents();
+        }
+        self.projectOverrides = self.LoadSettings(&folder.join(PROJECT_CONFIG));
+    }
+
+    // a setting changed by hand is the user's own from then on, rather than the project's
+    pub(crate) fn KeepSetting (&mut self, setting: Setting) {
+        self.projectOverrides.settings.retain(|(overridden, _)| *overridden != setting);
+        self.projectOverrides.configured.retain(|configured| *configured != setting);
+    }
+
+    // the value a setting has outside of the open project
+    pub(crate) fn GlobalValue (&self, setting: Setting) -> String {
+        self.projectOverrides.settings.iter()
+            .find(|(overridden, _)| *overridden == setting)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| self.SettingValue(setting))
+    }
+
+    // the settings as they are outside of the project (anything in the file that isn't a setting is left as it was)
+    pub(crate) fn SaveSettings (&mut self) {
+        let Some(path) = ConfigPath() else {  return;  };
+        let mut values = std::fs::read_to_string(&path).ok()
+            .and_then(|text| serde_json::from_str::<Map <String, Value>>(&text).ok())
+            .unwrap_or_default();
+        for info in SETTINGS {
+            values.insert(info.key.to_string(), info.ToJson(&self.GlobalValue(info.setting)));
+        }
+
+        let text = serde_json::to_string_pretty(&Value::Object(values)).unwrap_or_default();
+        let saved = path.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, text));
+        if let Err(error) = saved {
+            self.commandError = Some(format!("Couldn't save the settings: {error}"));
+        }
+    }
+
+    // steps the selected setting in the menu left or right (without wrapping around) and saves it
+    pub(crate) fn StepSetting (&mut self, forward: bool) {
+        let Some(info) = SETTINGS.get(self.currentMenuSettingBox) else {  return;  };
+        let current = self.SettingValue(info.setting);
+        let value = match info.kind {
+            SettingKind::Number(min, max) => {
+                let number = current.parse::<usize>().unwrap_or(min);
+                let number = if forward {  number.saturating_add(1)  } else {  number.saturating_sub(1)  };
+                number.clamp(min, max).to_string()
+            },
+            _ => {
+                let choices = info.Choices();
+                let index = choices.iter().position(|choice| *choice == current);
+                let index = match index {
+                    Some(index) if forward => std::cmp::min(index + 1, choices.len().saturating_sub(1)),
+                    Some(index) => index.saturating_sub(1),
+                    None => 0,
+                };
+                let Some(choice) = choices.get(index) else {  return;  };
+                choice.clone()
+            },
+        };
+        if value == current {  return;  }
+
+        match self.ApplySetting(info.setting, &value) {
+            Ok(_) => {
+                self.KeepSetting(info.setting);
+                self.SaveSettings();
+            },
+            Err(error) => self.commandError = Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn Lines (text: &str) -> Vec <String> {
+        text.split('\n').map(String::from).collect()
+    }
+
+    #[test]
+    fn DetectsSpaceWidths () {
+        let twoSpaces = Lines("fn main () {\n  if x {\n    y();\n  }\n}");
+        assert_eq!(DetectIndent(&twoSpaces), Some(IndentOverride {  tabWidth: Some(2), useTabs: Some(false)  }));
+        // block comment stars and single spaces used to line things up don't count
+        let fourSpaces = Lines("/*\n * note\n */\nfn a () {\n    call(one,\n         two);\n    b();\n}");
+        assert_eq!(DetectIndent(&fourSpaces), Some(IndentOverride {  tabWidth: Some(4), useTabs: Some(false)  }));
+    }
+
+    #[test]
+    fn DetectsTabs () {
+        let tabs = Lines("fn main () {\n\tif x {\n\t\ty();\n\t}\n}");
+        assert_eq!(DetectIndent(&tabs), Some(IndentOverride {  tabWidth: None, useTabs: Some(true)  }));
+    }
+
+    #[test]
+    fn LeavesUnindentedFilesAlone () {
+        assert_eq!(DetectIndent(&Lines("a\nb\n\nc")), None);
+        assert_eq!(DetectIndent(&[]), None);
+    }
+
+    // writes the json out to its own file (named after the test) and reads it back as a config
+    fn Read (name: &str, json: &str) -> Config {
+        let path = std::env::temp_dir().join(format!("termedit-{name}.json"));
+        std::fs::write(&path, json).expect("Couldn't write the test config");
+        let config = ReadConfig(&path);
+        let _ = std::fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn ReadsSettingsAndLanguages () {
+        let config = Read("settings", r#"{
+            "tabwidth": 2, "indent": "tabs", "vim": true, "detectindent": "off",
+            "languages": {"go": {"indent": "tabs"}, "js": {"tabwidth": 8}}
+        }"#);
+        assert!(config.warnings.is_empty(), "{:?}", config.warnings);
+        for setting in [(Setting::TabWidth, "2"), (Setting::Indent, "tabs"), (Setting::Vim, "on"), (Setting::DetectIndent, "off")] {
+            assert!(config.settings.contains(&(setting.0, setting.1.to_string())), "{setting:?} in {:?}", config.settings);
+        }
+        assert!(config.languages.contains(&(String::from("go"), IndentOverride {  tabWidth: None, useTabs: Some(true)  })));
+        assert!(config.languages.contains(&(String::from("js"), IndentOverride {  tabWidth: Some(8), useTabs: None  })));
+    }
+
+    #[test]
+    fn WarnsAboutBadConfigs () {
+        // the good values still go through alongside the bad ones
+        let config = Read("warnings", r#"{
+            "tabwidth": 40, "indent": "both", "fontsize": 12, "vim": "on",
+            "languages": {"go": {"vim": "on", "tabwidth": 0}, "py": 4}
+        }"#);
+        assert_eq!(config.settings, [(Setting::Vim, String::from("on"))]);
+        assert_eq!(config.languages, [(String::from("go"), IndentOverride::default())]);
+        let warnings = config.warnings.join("\n");
+        for expected in [
+            "termedit-warnings.json: tabwidth has to be from 1 to 16, not '40'",
+            "termedit-warnings.json: indent has to be one of spaces, tabs, not 'both'",
+            "termedit-warnings.json: unknown setting 'fontsize'",
+            "termedit-warnings.json: languages.go: only tabwidth and indent can be set per language, not 'vim'",
+            "termedit-warnings.json: languages.go: tabwidth has to be from 1 to 16, not '0'",
+            "termedit-warnings.json: languages.py has to be a json object",
+        ] {
+            assert!(warnings.contains(expected), "missing '{expected}' in:\n{warnings}");
+        }
+        assert_eq!(config.warnings.len(), 6);
+
+        assert_eq!(Read("not-json", "{tabwidth: 2").warnings.len(), 1);
+        assert_eq!(Read("not-object", "[1, 2]").warnings, ["termedit-not-object.json has to be a json object"]);
+        // a missing file is just empty
+        let missing = ReadConfig(&std::env::temp_dir().join("termedit-missing.json"));
+        assert!(missing.settings.is_empty() && missing.warnings.is_empty());
+    }
+
+    #[test]
+    fn SwapsProjectOverrides () {
+        let (first, second) = (std::env::temp_dir().join("termedit-project-a"), std::env::temp_dir().join("termedit-project-b"));
+        for folder in [&first, &second] {  std::fs::create_dir_all(folder).expect("Couldn't make the test project");  }
+        std::fs::write(first.join(PROJECT_CONFIG), r#"{"tabwidth": 2, "indent": "tabs", "languages": {"go": {"tabwidth": 8}}}"#)
+            .expect("Couldn't write the test config");
+        let _ = std::fs::remove_file(second.join(PROJECT_CONFIG));
+
+        let mut app = MainApp {  tabWidth: 4, ..Default::default()  };
+        app.LoadProjectSettings(&first);
+        assert_eq!((app.tabWidth, app.useTabs), (2, true));
+        assert!(app.languageIndents.contains_key("go"));
+        // what gets saved is what the settings were before the project
+        assert_eq!(app.GlobalValue(Setting::TabWidth), "4");
+        assert_eq!(app.GlobalValue(Setting::Indent), "spaces");
+
+        // changing one by hand makes it the user's own
+        app.ApplySetting(Setting::Indent, "tabs").unwrap();
+        app.KeepSetting(Setting::Indent);
+        assert_eq!(app.GlobalValue(Setting::Indent), "tabs");
+
+        // and the next project starts from the settings without the last one's
+        app.LoadProjectSettings(&second);
+        assert_eq!((app.tabWidth, app.useTabs), (4, true));
+        assert!(app.languageIndents.is_empty());
+        assert_eq!(app.configuredSettings, [Setting::Indent]);
+        let _ = std::fs::remove_file(first.join(PROJECT_CONFIG));
+    }
+
+    #[test]
+    fn ParsesAssignments () {
+        let (info, value) = ParseAssignment(" tabwidth = 3 ").unwrap();
+        assert_eq!((info.setting, value.as_str()), (Setting::TabWidth, "3"));
+        let (info, value) = ParseAssignment("detectindent=true").unwrap();
+        assert_eq!((info.setting, value.as_str()), (Setting::DetectIndent, "on"));
+        assert_eq!(ParseAssignment("tabwidth").unwrap_err(), "Usage: set <setting>=<value>");
+        assert_eq!(ParseAssignment("size=3").unwrap_err(), "Unknown setting 'size'");
+        assert_eq!(ParseAssignment("vim=maybe").unwrap_err(), "vim has to be on or off, not 'maybe'");
+    }
+}
@@ -0,0 +1,574 @@
+// snippets; a prefix typed before the completion key is swapped for a body written in the LSP's snippet syntax
+// ($1, ${2:default}, ${3|one,two|}, $0, and $NAME variables). The same number more than once mirrors it. After
+// expanding, tab walks through the stops (with $0, or the end, last) and whatever's typed into a stop is
+// copied into its mirrors. Snippets come from a language's Lua script and json files, and the lsp's completions
+// are expanded the same way
+
+use std::collections::{BTreeMap, HashMap};
+use serde_json::Value;
+use crate::App as MainApp;
+use crate::{CodeTab, LuaScripts, RustAnalyzerLsp};
+use crate::CodeTabs::LeadingWhitespace;
+use crate::TokenInfo::Languages;
+use crate::DataManager;
+use crate::eventHandler::{KeyCode, KeyParser};
+
+// the snippet files built into the binary (so they're there no matter where the editor is run from)
+static BUNDLED_SNIPPETS: [(&str, &str); 1] = [
+    ("cpp", include_str!("../assets/snippets/cpp.json")),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub prefix: String,
+    pub body: String,
+    pub description: String,
+}
+
+impl Snippet {
+    // a json file in the same layout as vscode's: {"name": {"prefix": "fn", "body": ["line", ...], "description": ""}}
+    // where the prefix can also be a list of them, and the body a single string
+    pub fn FromJson (json: &Value) -> Vec <Snippet> {
+        let Some(entries) = json.as_object() else {  return vec![];  };
+        let mut snippets = vec![];
+        for (name, entry) in entries {
+            let Some(body) = JoinedText(entry.get("body")) else {  continue;  };
+            let description = entry.get("description").and_then(Value::as_str).unwrap_or(name).to_string();
+            let prefixes = match entry.get("prefix") {
+                Some(Value::Array(prefixes)) => prefixes.iter().filter_map(Value::as_str).map(String::from).collect(),
+                Some(Value::String(prefix)) => vec![prefix.clone()],
+                _ => vec![name.clone()],
+            };
+            for prefix in prefixes {
+                snippets.push(Snippet {  prefix, body: body.clone(), description: description.clone()  });
+            }
+        }
+        snippets
+    }
+
+    // the snippets table of a language's script; each is either just the body or {body = "", description = ""}
+    pub fn FromLua (table: mlua::Table) -> Vec <Snippet> {
+        let mut snippets = vec![];
+        for (prefix, entry) in table.pairs::<String, mlua::Value>().flatten() {
+            let (body, description) = match entry {
+                mlua::Value::String(body) => (body.to_string_lossy(), String::new()),
+                mlua::Value::Table(entry) => {
+                    let body = match entry.get::<mlua::Value>("body") {
+                        Ok(mlua::Value::String(body)) => body.to_string_lossy(),
+                        Ok(mlua::Value::Table(lines)) => lines.sequence_values::<String>().flatten().collect::<Vec <_>>().join("\n"),
+                        _ => continue,
+                    };
+                    (body, entry.get::<Option <String>>("description").ok().flatten().unwrap_or_default())
+                },
+                _ => continue,
+            };
+            snippets.push(Snippet {  prefix, body, description  });
+        }
+        snippets
+    }
+
+    // an lsp completion item; only insertTextFormat 2 is a snippet, so anything else has its $'s escaped
+    pub fn FromCompletionItem (item: &Value) -> Option <Snippet> {
+        let label = item.get("label")?.as_str()?;
+        let text = item.get("textEdit").and_then(|edit| edit.get("newText"))
+            .or(item.get("insertText"))
+            .and_then(Value::as_str)
+            .unwrap_or(label);
+        let body =
+            if item.get("insertTextFormat").and_then(Value::as_u64) == Some(2) {  text.to_string()  }
+            else {  text.replace('\\', "\\\\").replace('$', "\\$").replace('}', "\\}")  };
+        Some(Snippet {
+            prefix: item.get("filterText").and_then(Value::as_str).unwrap_or(label).to_string(),
+            body,
+            description: item.get("detail").and_then(Value::as_str).unwrap_or_default().to_string(),
+        })
+    }
+}
+
+// a string, or a list of strings as lines
+fn JoinedText (value: Option <&Value>) -> Option <String> {
+    match value? {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(lines) => Some(lines.iter().filter_map(Value::as_str).collect::<Vec <_>>().join("\n")),
+        _ => None,
+    }
+}
+
+// every snippet for a language (the script's, then the bundled json, then the user's json in
+// ~/.config/termedit/snippets); the user's file is read each time so edits to it show up right away
+pub fn LanguageSnippets (language: Languages, luaSyntaxHighlightScripts: &LuaScripts) -> Vec <Snippet> {
+    let mut snippets = luaSyntaxHighlightScripts.lock()
+        .get(&language)
+        .map(|script| script.snippets.clone())
+        .unwrap_or_default();
+    let name = format!("{language:?}").to_lowercase();
+    if let Some((_, bundled)) = BUNDLED_SNIPPETS.iter().find(|(bundledName, _)| *bundledName == name) {
+        let json = serde_json::from_str(bundled).expect("The bundled snippets aren't valid json");
+        snippets.extend(Snippet::FromJson(&json));
+    }
+    if let Some(home) = dirs::home_dir() &&
+        let Ok(json) = DataManager::LoadJson(&home.join(".config/termedit/snippets").join(format!("{name}.json")).to_string_lossy())
+    {
+        snippets.extend(Snippet::FromJson(&json));
+    }
+    snippets
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text (String),
+    Stop (usize, Vec <Node>),  // a tab stop or placeholder (a bare $1 has no children)
+    Choice (usize, Vec <String>),
+    Variable (String, Vec <Node>),  // the children are its default
+}
+
+// reads nodes until the end, or the } closing a placeholder
+fn ParseNodes (chars: &[char], index: &mut usize, inPlaceholder: bool) -> Vec <Node> {
+    let mut nodes = vec![];
+    let mut text = String::new();
+    while *index < chars.len() {
+        let chr = chars[*index];
+        *index += 1;
+        match chr {
+            '\\' if matches!(chars.get(*index), Some('$' | '}' | '\\')) => {
+                text.push(chars[*index]);
+                *index += 1;
+            },
+            '}' if inPlaceholder => break,
+            '$' => {
+                let start = *index;
+                let Some(node) = ParseDollar(chars, index) else {
+                    *index = start;
+                    text.push('$');
+                    continue;
+                };
+                if !text.is_empty() {  nodes.push(Node::Text(std::mem::take(&mut text)));  }
+                nodes.push(node);
+            },
+            _ => text.push(chr),
+        }
+    }
+    if !text.is_empty() {  nodes.push(Node::Text(text));  }
+    nodes
+}
+
+fn ParseNumber (chars: &[char], index: &mut usize) -> Option <usize> {
+    let start = *index;
+    while chars.get(*index).is_some_and(char::is_ascii_digit) {  *index += 1;  }
+    chars[start..*index].iter().collect::<String>().parse().ok()
+}
+
+fn ParseName (chars: &[char], index: &mut usize) -> Option <String> {
+    if !chars.get(*index).is_some_and(|chr| chr.is_ascii_alphabetic() || *chr == '_') {  return None;  }
+    let start = *index;
+    while chars.get(*index).is_some_and(|chr| chr.is_ascii_alphanumeric() || *chr == '_') {  *index += 1;  }
+    Some(chars[start..*index].iter().collect())
+}
+
+// what follows a $ (None if it's just a $)
+fn ParseDollar (chars: &[char], index: &mut usize) -> Option <Node> {
+    if let Some(number) = ParseNumber(chars, index) {  return Some(Node::Stop(number, vec![]));  }
+    if let Some(name) = ParseName(chars, index) {  return Some(Node::Variable(name, vec![]));  }
+    if chars.get(*index) != Some(&'{') {  return None;  }
+    *index += 1;
+    if let Some(number) = ParseNumber(chars, index) {
+        *index += 1;
+        return match chars.get(*index - 1)? {
+            '}' => Some(Node::Stop(number, vec![])),
+            ':' => Some(Node::Stop(number, ParseNodes(chars, index, true))),
+            '|' => {
+                let (mut choices, mut choice) = (vec![], String::new());
+                while *index < chars.len() {
+                    let chr = chars[*index];
+                    *index += 1;
+                    match chr {
+                        '\\' if *index < chars.len() => {
+                            choice.push(chars[*index]);
+                            *index += 1;
+                        },
+                        ',' => choices.push(std::mem::take(&mut choice)),
+                        '|' if chars.get(*index) == Some(&'}') => {
+                            *index += 1;
+                            choices.push(choice);
+                            return Some(Node::Choice(number, choices));
+                        },
+                        _ => choice.push(chr),
+                    }
+                }
+                None
+            },
+            _ => None,
+        };
+    }
+    let name = ParseName(chars, index)?;
+    *index += 1;
+    match chars.get(*index - 1)? {
+        '}' => Some(Node::Variable(name, vec![])),
+        ':' => Some(Node::Variable(name, ParseNodes(chars, index, true))),
+        // transforms aren't supported, so the variable is used as is
+        _ => {
+            let mut depth = 1;
+            while *index < chars.len() && depth > 0 {
+                match chars[*index] {
+                    '\\' => *index += 1,
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {},
+                }
+                *index += 1;
+            }
+            Some(Node::Variable(name, vec![]))
+        },
+    }
+}
+
+// a parsed body; the text to insert, and the byte ranges in it of each tab stop in the order they're visited.
+// The first range of a stop is the one the cursor goes to; the rest mirror it
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Expansion {
+    pub text: String,
+    pub stops: Vec <Vec <(usize, usize)>>,
+}
+
+struct Layout <'a> {
+    indent: &'a str,  // what every line after the first starts with
+    unit: &'a str,  // what a tab in the body becomes (a level of indentation)
+    variables: &'a HashMap <&'static str, String>,
+}
+
+impl Layout <'_> {
+    fn Push (&self, text: &mut String, piece: &str) {
+        for chr in piece.chars() {
+            match chr {
+                '\t' => text.push_str(self.unit),
+                '\n' => {
+                    text.push('\n');
+                    text.push_str(self.indent);
+                },
+                _ => text.push(chr),
+            }
+        }
+    }
+
+    // the text of each stop that's given one (the first placeholder with any, which its mirrors copy)
+    fn Defaults (&self, nodes: &[Node], defaults: &mut HashMap <usize, String>) {
+        for node in nodes {
+            match node {
+                Node::Stop(number, children) if !children.is_empty() => {
+                    if !defaults.contains_key(number) {
+                        let mut text = String::new();
+                        self.Render(children, defaults, &mut text, &mut BTreeMap::new());
+                        defaults.insert(*number, text);
+                    }
+                    self.Defaults(children, defaults);
+                },
+                Node::Choice(number, choices) => {
+                    defaults.entry(*number).or_insert(choices.first().cloned().unwrap_or_default());
+                },
+                Node::Variable(_, children) => self.Defaults(children, defaults),
+                _ => {},
+            }
+        }
+    }
+
+    fn Render (&self,
+               nodes: &[Node],
+               defaults: &HashMap <usize, String>,
+               text: &mut String,
+               stops: &mut BTreeMap <usize, Vec <(usize, usize)>>
+    ) {
+        for node in nodes {
+            let start = text.len();
+            match node {
+                Node::Text(piece) => self.Push(text, piece),
+                Node::Stop(number, children) => {
+                    if children.is_empty() {
+                        self.Push(text, defaults.get(number).map_or("", String::as_str));
+                    } else {
+                        self.Render(children, defaults, text, stops);
+                    }
+                    stops.entry(*number).or_default().push((start, text.len()));
+                },
+                Node::Choice(number, _) => {
+                    self.Push(text, defaults.get(number).map_or("", String::as_str));
+                    stops.entry(*number).or_default().push((start, text.len()));
+                },
+                Node::Variable(name, children) => match self.variables.get(name.as_str()) {
+                    Some(value) => self.Push(text, value),
+                    None => self.Render(children, defaults, text, stops),
+                },
+            }
+        }
+    }
+}
+
+impl Expansion {
+    // expands a body to go in at a line with the given indentation
+    pub fn Parse (body: &str, indent: &str, unit: &str, variables: &HashMap <&'static str, String>) -> Expansion {
+        let chars: Vec <char> = body.chars().collect();
+        let nodes = ParseNodes(&chars, &mut 0, false);
+        let layout = Layout {  indent, unit, variables  };
+        let mut defaults = HashMap::new();
+        layout.Defaults(&nodes, &mut defaults);
+        let (mut text, mut stops) = (String::new(), BTreeMap::new());
+        layout.Render(&nodes, &defaults, &mut text, &mut stops);
+
+        // $0 is the last stop, and it's the end of the snippet when it isn't given
+        let last = stops.remove(&0).unwrap_or(vec![(text.len(), text.len())]);
+        let mut stops: Vec <Vec <(usize, usize)>> = stops.into_values().collect();
+        stops.push(last);
+        Expansion {  text, stops  }
+    }
+}
+
+// the tab stops of an expanded snippet as (line, start, end) in the file
+#[derive(Debug, Clone)]
+pub struct ActiveSnippet {
+    stops: Vec <Vec <(usize, usize, usize)>>,
+    current: usize,
+    // the length of the current stop's line, and the line count, as of the last check (to tell what was typed)
+    lineLength: usize,
+    lineCount: usize,
+}
+
+impl ActiveSnippet {
+    // moves everything on a line at or past a column over (and stretches what spans the column), except one range
+    fn Shift (&mut self, line: usize, column: usize, change: isize, except: (usize, usize)) {
+        let Move = |index: usize| (index as isize + change).max(0) as usize;
+        for (stop, ranges) in self.stops.iter_mut().enumerate() {
+            for (index, range) in ranges.iter_mut().enumerate() {
+                if (stop, index) == except || range.0 != line {  continue;  }
+                if range.1 >= column {  (range.1, range.2) = (Move(range.1), Move(range.2));  }
+                else if range.2 >= column {  range.2 = Move(range.2);  }
+            }
+        }
+    }
+}
+
+impl CodeTab {
+    // the values the $NAME variables take
+    fn SnippetVariables (&self) -> HashMap <&'static str, String> {
+        let baseName = self.fileName.rsplit_once('.').map_or(self.fileName.as_str(), |(name, _)| name);
+        HashMap::from([
+            ("TM_FILENAME", self.fileName.clone()),
+            ("TM_FILENAME_BASE", baseName.to_string()),
+            ("TM_FILEPATH", self.path.clone()),
+            ("TM_LINE_INDEX", self.cursor.0.to_string()),
+            ("TM_LINE_NUMBER", (self.cursor.0 + 1).to_string()),
+            ("TM_CURRENT_LINE", self.lines[self.cursor.0].clone()),
+        ])
+    }
+
+    // the snippet whose prefix the cursor is just after (the longest when there's more than one)
+    // a prefix has to start a word
+    pub fn SnippetAtCursor (&self, luaSyntaxHighlightScripts: &LuaScripts) -> Option <Snippet> {
+        let ending = self.fileName.split('.').next_back().unwrap_or("");
+        let before = &self.lines[self.cursor.0][..std::cmp::min(self.cursor.1, self.lines[self.cursor.0].len())];
+        let IsWord = |chr: char| chr.is_alphanumeric() || chr == '_';
+        LanguageSnippets(Languages::FromExtension(ending), luaSyntaxHighlightScripts).into_iter()
+            .filter(|snippet| !snippet.prefix.is_empty() && before.ends_with(&snippet.prefix))
+            .filter(|snippet| {
+                let previous = before[..before.len() - snippet.prefix.len()].chars().next_back();
+                !snippet.prefix.starts_with(IsWord) || !previous.is_some_and(IsWord)
+            })
+            .fold(None, |best: Option <Snippet>, snippet| match best {
+                Some(best) if best.prefix.len() > snippet.prefix.len() => Some(best),
+                _ => Some(snippet),
+            })
+    }
+
+    // the lsp completion that the word before the cursor is the start of (the first in the server's order),
+    // along with how many bytes of it were typed
+    pub fn CompletionAtCursor (&self) -> Option <(&Snippet, usize)> {
+        let before = &self.lines[self.cursor.0][..std::cmp::min(self.cursor.1, self.lines[self.cursor.0].len())];
+        let word = &before[before.trim_end_matches(|chr: char| chr.is_alphanumeric() || chr == '_').len()..];
+        if word.is_empty() {  return None;  }
+        self.completions.iter()
+            .find(|item| item.prefix.starts_with(word))
+            .map(|item| (item, word.len()))
+    }
+
+    // swaps the prefix before the cursor for its snippet, or else the word for the lsp's completion of it;
+    // false if there's neither
+    pub async fn ExpandSnippet <'a> (&mut self,
+                                     luaSyntaxHighlightScripts: &LuaScripts,
+                                     rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) -> bool {
+        if self.highlighting {  return false;  }
+        let (body, replacing) =
+            if let Some(snippet) = self.SnippetAtCursor(luaSyntaxHighlightScripts) {  (snippet.body, snippet.prefix.len())  }
+            else if let Some((item, typed)) = self.CompletionAtCursor() {  (item.body.clone(), typed)  }
+            else {  return false;  };
+        self.InsertSnippet(&body, replacing, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        true
+    }
+
+    // puts a snippet's body in at the cursor (in place of the bytes just before it) as one undo group, with
+    // its lines indented to match the cursor's line, and then selects its first stop
+    pub async fn InsertSnippet <'a> (&mut self,
+                                     body: &str,
+                                     replacing: usize,
+                                     luaSyntaxHighlightScripts: &LuaScripts,
+                                     rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let undoDepth = self.changeBuffer.len();
+        self.highlighting = false;
+        self.snippet = None;
+        let replacing = std::cmp::min(replacing, self.cursor.1);
+        if replacing > 0 {
+            self.DelChars(replacing, 0, luaSyntaxHighlightScripts, rustAnalyzer).await;
+        }
+
+        let indent = LeadingWhitespace(&self.lines[self.cursor.0]).to_string();
+        let expansion = Expansion::Parse(body, &indent, &self.Indent(), &self.SnippetVariables());
+        let start = self.cursor;
+        let pieces: Vec <&str> = expansion.text.split('\n').collect();
+        for (index, piece) in pieces.iter().enumerate() {
+            if !piece.is_empty() {
+                self.InsertChars(piece.to_string(), luaSyntaxHighlightScripts, rustAnalyzer).await;
+            }
+            if index + 1 < pieces.len() {
+                let line = self.cursor.0;
+                self.LineBreakIn(false, luaSyntaxHighlightScripts, rustAnalyzer).await;
+                self.cursor = (line + 1, 0);
+            }
+        }
+        self.MergeChanges(undoDepth);
+
+        // the byte ranges into (line, start, end), with one that runs over a line break cut off at it
+        let Position = |offset: usize| {
+            let before = &expansion.text[..offset];
+            match before.rfind('\n') {
+                Some(lineStart) => (start.0 + before.matches('\n').count(), offset - lineStart - 1),
+                None => (start.0, start.1 + offset),
+            }
+        };
+        let stops = expansion.stops.iter().map(|ranges| ranges.iter().map(|(from, to)| {
+            let ((line, from), (toLine, to)) = (Position(*from), Position(*to));
+            (line, from, if toLine == line {  to  } else {  self.lines[line].len()  })
+        }).collect()).collect();
+        self.snippet = Some(ActiveSnippet {  stops, current: 0, lineLength: 0, lineCount: 0  });
+        self.SelectSnippetStop(0);
+    }
+
+    // selects a stop's text (so typing replaces it); reaching the last stop finishes the snippet
+    fn SelectSnippetStop (&mut self, stop: usize) {
+        let Some(snippet) = self.snippet.as_mut() else {  return;  };
+        let (line, start, end) = snippet.stops[stop][0];
+        snippet.current = stop;
+        snippet.lineLength = self.lines[line].len();
+        snippet.lineCount = self.lines.len();
+        self.cursorEnd = (line, start);
+        self.cursor = (line, end);
+        self.highlighting = start != end;
+        if stop + 1 == snippet.stops.len() {  self.snippet = None;  }
+    }
+
+    // goes to the next (or previous) stop of the snippet being filled in; false if there isn't one
+    pub fn NextSnippetStop (&mut self, forward: bool) -> bool {
+        let Some(snippet) = &self.snippet else {  return false;  };
+        let stop =
+            if forward {  snippet.current + 1  }
+            else {  snippet.current.saturating_sub(1)  };
+        self.SelectSnippetStop(stop);
+        true
+    }
+
+    // keeps the snippet in step after a key press; the current stop grows or shrinks with what was typed
+    // into it, and that's copied into its mirrors (in the same undo group). Leaving the stop finishes it
+    pub async fn FollowSnippet <'a> (&mut self,
+                                     luaSyntaxHighlightScripts: &LuaScripts,
+                                     rustAnalyzer: RustAnalyzerLsp<'a>,
+    ) {
+        let Some(mut snippet) = self.snippet.take() else {  return;  };
+        let current = snippet.current;
+        let (line, start, end) = snippet.stops[current][0];
+        if self.lines.len() != snippet.lineCount || line >= self.lines.len() {  return;  }
+        let change = self.lines[line].len() as isize - snippet.lineLength as isize;
+        let newEnd = end as isize + change;
+        if newEnd < start as isize || self.cursor.0 != line ||
+            self.cursor.1 < start || self.cursor.1 as isize > newEnd
+        {  return;  }
+        snippet.Shift(line, end, change, (current, 0));
+        snippet.stops[current][0].2 = newEnd as usize;
+
+        let text = self.lines[line][start..newEnd as usize].to_string();
+        let undoDepth = self.changeBuffer.len();
+        let (cursor, cursorEnd, highlighting) = (self.cursor, self.cursorEnd, self.highlighting);
+        self.highlighting = false;
+        for mirror in 1..snippet.stops[current].len() {
+            let (mirrorLine, from, to) = snippet.stops[current][mirror];
+            if self.lines.get(mirrorLine).and_then(|line| line.get(from..to)) == Some(text.as_str()) {  continue;  }
+            if to > from {
+                self.cursor = (mirrorLine, to);
+                self.DelChars(to - from, 0, luaSyntaxHighlightScripts, rustAnalyzer).await;
+            }
+            if !text.is_empty() {
+                self.cursor = (mirrorLine, from);
+                self.InsertChars(text.clone(), luaSyntaxHighlightScripts, rustAnalyzer).await;
+            }
+            snippet.Shift(mirrorLine, to, text.len() as isize - (to - from) as isize, (current, mirror));
+            snippet.stops[current][mirror].2 = from + text.len();
+        }
+        // a mirror earlier on the same line pushes the stop (and the cursor in it) over
+        let offset = snippet.stops[current][0].1 as isize - start as isize;
+        let Moved = |position: (usize, usize)| {
+            if position.0 != line {  return position;  }
+            (line, (position.1 as isize + offset) as usize)
+        };
+        (self.cursor, self.cursorEnd, self.highlighting) = (Moved(cursor), Moved(cursorEnd), highlighting);
+        if self.changeBuffer.len() > undoDepth {  self.MergeChanges(undoDepth.saturating_sub(1));  }
+
+        snippet.lineLength = self.lines[line].len();
+        snippet.lineCount = self.lines.len();
+        self.snippet = Some(snippet);
+    }
+}
+
+impl <'a> MainApp <'a> {
+    // after a key press in the code (escape gives up on the snippet)
+    pub(crate) async fn FollowSnippet <'b> (&mut self, keyEvents: &KeyParser, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let Some(tab) = self.codeTabs.tabs.get_mut(self.lastTab) else {  return;  };
+        if keyEvents.ContainsKeyCode(KeyCode::Escape) {  tab.snippet = None;  }
+        tab.FollowSnippet(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn Expand (body: &str) -> Expansion {
+        let variables = HashMap::from([("TM_FILENAME", String::from("a.rs"))]);
+        Expansion::Parse(body, "  ", "    ", &variables)
+    }
+
+    #[test]
+    fn ExpandsStopsAndIndents () {
+        // the tab becomes the indent unit, and every line after the first gets the line's own indent
+        let expansion = Expand("fn ${1:name} (${2}) {\n\t$0\n}");
+        assert_eq!(expansion.text, "fn name () {\n      \n  }");
+        assert_eq!(expansion.stops, [vec![(3, 7)], vec![(9, 9)], vec![(19, 19)]]);
+    }
+
+    #[test]
+    fn MirrorsRepeatedStops () {
+        let expansion = Expand("for (${1:int} ${2:i} = 0; $2 < ${3:n}; $2++) {}");
+        assert_eq!(expansion.text, "for (int i = 0; i < n; i++) {}");
+        assert_eq!(expansion.stops[1], [(9, 10), (16, 17), (23, 24)]);
+    }
+
+    #[test]
+    fn HandlesNestingChoicesEscapesAndVariables () {
+        let expansion = Expand("a \\$1 $$ ${1|x,y|} $TM_FILENAME ${FOO:def} ${2:outer ${3:inner}} $1");
+        assert_eq!(expansion.text, "a $1 $$ x a.rs def outer inner x");
+        assert_eq!(expansion.stops, [vec![(8, 9), (31, 32)], vec![(19, 30)], vec![(25, 30)], vec![(32, 32)]]);
+    }
+
+    #[test]
+    fn PutsTheFinalStopLast () {
+        // without a $0 the snippet ends at its end, and with one it's walked to after the numbered stops
+        assert_eq!(Expand("a ${1:b}").stops, [vec![(2, 3)], vec![(3, 3)]]);
+        assert_eq!(Expand("$0x$2y$1").stops, [vec![(2, 2)], vec![(1, 1)], vec![(0, 0)]]);
+    }
+}
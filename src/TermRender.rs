@@ -16,6 +16,10 @@ pub static CLEAR: &str = "\x1b[0m";
 pub static SHOW_CURSOR: &str = "\x1b[?25h";
 pub static HIDE_CURSOR: &str = "\x1b[?25l";
 
+// DEC mode 2026; the terminal holds off on drawing until the whole frame has been written
+pub static BEGIN_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026h";
+pub static END_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026l";
+
 // * color, modifiers, is_background
 pub static EMPTY_MODIFIER_REFERENCE: &[&str] = &[];  // making a default static type is annoying
 
@@ -68,8 +72,9 @@ pub static REVERSE:   (Option <&str>, &[&str], bool) = (None    , &["7"], false)
 pub static HIDE:      (Option <&str>, &[&str], bool) = (None    , &["8"], false);
 
 
-// manages the global state for light/dark modes (handles basic colors switching around)
+// light/dark modes (handles basic colors switching around)
 // no support for RGB/custom color codes, only the default variants
+// -- (basic but limited automatic support; not everything will look perfect by default)
 #[derive(Clone, PartialEq, Eq, Debug, Default, Hash, Copy)]
 pub enum ColorMode {
     #[default] Dark,
@@ -77,18 +82,43 @@ pub enum ColorMode {
 }
 
 impl ColorMode {
-    pub fn ToLight () {
-        unsafe {COLOR_MODE = ColorMode::Light};
+    // Picks the mode based on the terminal's background color (the reply to the OSC 11 query)
+    pub fn FromBackground ((r, g, b): (u8, u8, u8)) -> Self {
+        // relative luminance (close enough without the gamma correction)
+        let luminance = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+        if luminance > 127.5 {  ColorMode::Light  }
+        else {  ColorMode::Dark  }
     }
+}
 
-    pub fn ToDark () {
-        unsafe {COLOR_MODE = ColorMode::Dark};
+// the types of color modes (some aren't supported by certain terminals...)
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ColorTypes {
+    #[default] True,
+    Partial,
+    Basic
+}
+
+impl ColorTypes {
+    // picks the best color mode the terminal claims to support
+    pub fn FromCapabilities (capabilities: &TerminalCapabilities) -> Self {
+        if capabilities.trueColor {
+            ColorTypes::True
+        } else if capabilities.colors256 {
+            ColorTypes::Partial
+        } else {
+            ColorTypes::Basic
+        }
     }
 }
 
-// hopefully this will let full usage of colors while not worrying too much about light/dark mode
-// -- (basic but limited automatic support; not everything will look perfect by default)
-static mut COLOR_MODE: ColorMode = ColorMode::Dark;
+// The state the windows need when being turned into text
+// Each App carries its own copy, which gets passed down to the render closures
+#[derive(Clone, PartialEq, Eq, Debug, Default, Hash, Copy)]
+pub struct RenderContext {
+    pub colorMode: ColorMode,
+    pub colorType: ColorTypes,
+}
 
 
 #[derive(Clone, Debug, Eq, PartialEq, Default, Hash, Copy)]
@@ -184,16 +214,125 @@ impl UniqueColor {
     }
 }
 
+// The xterm palette, for quantizing rgb colors on terminals without 24-bit color
+// (the 16 basic colors vary between terminals; these are xterm's defaults)
+pub struct Palette;
+
+static BASIC_COLORS: [(ColorType, (u8, u8, u8)); 16] = [
+    (ColorType::Black, (0, 0, 0)),
+    (ColorType::Red, (205, 0, 0)),
+    (ColorType::Green, (0, 205, 0)),
+    (ColorType::Yellow, (205, 205, 0)),
+    (ColorType::Blue, (0, 0, 238)),
+    (ColorType::Magenta, (205, 0, 205)),
+    (ColorType::Cyan, (0, 205, 205)),
+    (ColorType::White, (229, 229, 229)),
+    (ColorType::BrightBlack, (127, 127, 127)),
+    (ColorType::BrightRed, (255, 0, 0)),
+    (ColorType::BrightGreen, (0, 255, 0)),
+    (ColorType::BrightYellow, (255, 255, 0)),
+    (ColorType::BrightBlue, (92, 92, 255)),
+    (ColorType::BrightMagenta, (255, 0, 255)),
+    (ColorType::BrightCyan, (0, 255, 255)),
+    (ColorType::BrightWhite, (255, 255, 255)),
+];
+
+// the channel values used by the 6x6x6 color cube (indices 16 to 231)
+static CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Palette {
+    // The "redmean" weighted distance; much closer to how eyes see color than a plain
+    // euclidean distance, without needing to convert into a proper color space
+    pub fn Distance (a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+        let redMean = (a.0 as i32 + b.0 as i32) / 2;
+        let (dr, dg, db) = (a.0 as i32 - b.0 as i32, a.1 as i32 - b.1 as i32, a.2 as i32 - b.2 as i32);
+        (((512 + redMean) * dr * dr) >> 8) as u32 + (4 * dg * dg) as u32 + (((767 - redMean) * db * db) >> 8) as u32
+    }
+
+    pub fn IndexToRgb (index: u8) -> (u8, u8, u8) {
+        match index {
+            0..=15 => BASIC_COLORS[index as usize].1,
+            16..=231 => {
+                let index = index - 16;
+                (CUBE_LEVELS[(index / 36) as usize], CUBE_LEVELS[(index / 6 % 6) as usize], CUBE_LEVELS[(index % 6) as usize])
+            },
+            _ => {
+                let level = 8 + (index - 232) * 10;
+                (level, level, level)
+            },
+        }
+    }
+
+    // Finds the closest color in the cube and the grayscale ramp (the basic 16 are
+    // skipped, since terminals tend to remap those)
+    pub fn NearestIndex (color: (u8, u8, u8)) -> u8 {
+        let NearestLevel = |channel: u8| {
+            (0..6).min_by_key(|level| (CUBE_LEVELS[*level] as i32 - channel as i32).abs()).unwrap_or(0) as u8
+        };
+        let cube = 16 + 36 * NearestLevel(color.0) + 6 * NearestLevel(color.1) + NearestLevel(color.2);
+
+        let average = (color.0 as u32 + color.1 as u32 + color.2 as u32) / 3;
+        let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+        if Palette::Distance(color, Palette::IndexToRgb(gray)) < Palette::Distance(color, Palette::IndexToRgb(cube)) {
+            gray
+        } else {
+            cube
+        }
+    }
+
+    pub fn NearestBasic (color: (u8, u8, u8)) -> ColorType {
+        BASIC_COLORS.iter()
+            .min_by_key(|(_, rgb)| Palette::Distance(color, *rgb))
+            .map(|(colorType, _)| *colorType)
+            .unwrap_or_default()
+    }
+}
+
 impl ColorType {
     // Converts the color type into a unique color (static or dynamic)
-    pub fn GetColor (&self) -> UniqueColor {
-        if unsafe { COLOR_MODE } == ColorMode::Dark {
-            self.GetDarkColor()
+    // Rgb and 256 colors get quantized down if the terminal can't show them
+    pub fn GetColor (&self, context: &RenderContext) -> UniqueColor {
+        let color = self.Quantize(context.colorType);
+        if context.colorMode == ColorMode::Dark {
+            color.GetDarkColor()
         } else {
-            self.GetLightColor()
+            color.GetLightColor()
+        }
+    }
+
+    // Maps the color onto the closest one available in the given color mode
+    pub fn Quantize (&self, colorType: ColorTypes) -> ColorType {
+        match (colorType, *self) {
+            (ColorTypes::Partial, ColorType::Rgb(r, g, b)) => ColorType::Ansi(Palette::NearestIndex((r, g, b))),
+            (ColorTypes::Partial, ColorType::OnRGB(r, g, b)) => ColorType::OnANSI(Palette::NearestIndex((r, g, b))),
+            (ColorTypes::Basic, ColorType::Rgb(r, g, b)) => Palette::NearestBasic((r, g, b)),
+            (ColorTypes::Basic, ColorType::OnRGB(r, g, b)) => Palette::NearestBasic((r, g, b)).ToBackground(),
+            (ColorTypes::Basic, ColorType::Ansi(index)) => Palette::NearestBasic(Palette::IndexToRgb(index)),
+            (ColorTypes::Basic, ColorType::OnANSI(index)) => Palette::NearestBasic(Palette::IndexToRgb(index)).ToBackground(),
+            (_, color) => color,
         }
     }
 
+    pub fn IsModifier (&self) -> bool {
+        matches!(self,
+            ColorType::Bold | ColorType::Dim | ColorType::Italic | ColorType::Underline |
+            ColorType::Blink | ColorType::Reverse | ColorType::Hide
+        )
+    }
+
+    pub fn IsBackground (&self) -> bool {
+        matches!(self,
+            ColorType::OnBlack | ColorType::OnRed | ColorType::OnGreen | ColorType::OnYellow |
+            ColorType::OnBlue | ColorType::OnMagenta | ColorType::OnCyan | ColorType::OnWhite |
+            ColorType::OnDefault |
+            ColorType::OnBrightBlack | ColorType::OnBrightRed | ColorType::OnBrightGreen |
+            ColorType::OnBrightYellow | ColorType::OnBrightBlue | ColorType::OnBrightMagenta |
+            ColorType::OnBrightCyan | ColorType::OnBrightWhite | ColorType::OnBrightDefault |
+            ColorType::OnRGB (..) | ColorType::OnANSI (_)
+        )
+    }
+
     fn GetLightColor (&self) -> UniqueColor {
         match self {
             ColorType::Black =>   { UniqueColor::Static(BRIGHT_WHITE) },
@@ -335,6 +474,90 @@ impl ColorType {
             ColorType::Hide => { UniqueColor::Static(HIDE) },
         }
     }
+
+    // Gets a color from its name (the names match the variants)
+    // Hex codes (#rrggbb) are read as 24-bit colors
+    pub fn FromName (name: &str) -> Option <ColorType> {
+        if let Some(hex) = name.strip_prefix('#') {
+            if hex.len() != 6 {  return None;  }
+            let channel = |range: std::ops::Range <usize>| u8::from_str_radix(hex.get(range)?, 16).ok();
+            return Some(ColorType::Rgb(channel(0..2)?, channel(2..4)?, channel(4..6)?));
+        }
+        Some(match name {
+            "Black" => ColorType::Black,
+            "Red" => ColorType::Red,
+            "Green" => ColorType::Green,
+            "Yellow" => ColorType::Yellow,
+            "Blue" => ColorType::Blue,
+            "Magenta" => ColorType::Magenta,
+            "Cyan" => ColorType::Cyan,
+            "White" => ColorType::White,
+            "Default" => ColorType::Default,
+            "BrightBlack" => ColorType::BrightBlack,
+            "BrightRed" => ColorType::BrightRed,
+            "BrightGreen" => ColorType::BrightGreen,
+            "BrightYellow" => ColorType::BrightYellow,
+            "BrightBlue" => ColorType::BrightBlue,
+            "BrightMagenta" => ColorType::BrightMagenta,
+            "BrightCyan" => ColorType::BrightCyan,
+            "BrightWhite" => ColorType::BrightWhite,
+            "BrightDefault" => ColorType::BrightDefault,
+            "OnBlack" => ColorType::OnBlack,
+            "OnRed" => ColorType::OnRed,
+            "OnGreen" => ColorType::OnGreen,
+            "OnYellow" => ColorType::OnYellow,
+            "OnBlue" => ColorType::OnBlue,
+            "OnMagenta" => ColorType::OnMagenta,
+            "OnCyan" => ColorType::OnCyan,
+            "OnWhite" => ColorType::OnWhite,
+            "OnDefault" => ColorType::OnDefault,
+            "OnBrightBlack" => ColorType::OnBrightBlack,
+            "OnBrightRed" => ColorType::OnBrightRed,
+            "OnBrightGreen" => ColorType::OnBrightGreen,
+            "OnBrightYellow" => ColorType::OnBrightYellow,
+            "OnBrightBlue" => ColorType::OnBrightBlue,
+            "OnBrightMagenta" => ColorType::OnBrightMagenta,
+            "OnBrightCyan" => ColorType::OnBrightCyan,
+            "OnBrightWhite" => ColorType::OnBrightWhite,
+            "OnBrightDefault" => ColorType::OnBrightDefault,
+            "Bold" => ColorType::Bold,
+            "Dim" => ColorType::Dim,
+            "Italic" => ColorType::Italic,
+            "Underline" => ColorType::Underline,
+            "Blink" => ColorType::Blink,
+            "Reverse" => ColorType::Reverse,
+            "Hide" => ColorType::Hide,
+            _ => return None,
+        })
+    }
+
+    // Converts a foreground color into its background counterpart
+    // Anything that's already a background (or a modifier) is left as is
+    pub fn ToBackground (&self) -> ColorType {
+        match *self {
+            ColorType::Black => ColorType::OnBlack,
+            ColorType::Red => ColorType::OnRed,
+            ColorType::Green => ColorType::OnGreen,
+            ColorType::Yellow => ColorType::OnYellow,
+            ColorType::Blue => ColorType::OnBlue,
+            ColorType::Magenta => ColorType::OnMagenta,
+            ColorType::Cyan => ColorType::OnCyan,
+            ColorType::White => ColorType::OnWhite,
+            ColorType::Default => ColorType::OnDefault,
+            ColorType::BrightBlack => ColorType::OnBrightBlack,
+            ColorType::BrightRed => ColorType::OnBrightRed,
+            ColorType::BrightGreen => ColorType::OnBrightGreen,
+            ColorType::BrightYellow => ColorType::OnBrightYellow,
+            ColorType::BrightBlue => ColorType::OnBrightBlue,
+            ColorType::BrightMagenta => ColorType::OnBrightMagenta,
+            ColorType::BrightCyan => ColorType::OnBrightCyan,
+            ColorType::BrightWhite => ColorType::OnBrightWhite,
+            ColorType::BrightDefault => ColorType::OnBrightDefault,
+            ColorType::Rgb(r, g, b) => ColorType::OnRGB(r, g, b),
+            ColorType::Ansi(index) => ColorType::OnANSI(index),
+            color => color,
+        }
+    }
 }
 
 // Color setters for standard primitives
@@ -372,13 +595,14 @@ impl Colorize for String {
 
 // A colored string
 // It stores all of its modifiers like colors/underlying/other
+// The colors are only converted into escape codes when rendering (that depends on the light/dark mode)
 //#[derive(Clone)]
 #[derive(Clone, Debug, Eq, PartialEq, Default, Hash)]
 pub struct Colored {
     text: String,
-    mods: Vec <String>,
-    color: Option <String>,
-    bgColor: Option <String>,
+    mods: Vec <ColorType>,
+    color: Option <ColorType>,
+    bgColor: Option <ColorType>,
 }
 
 impl Colorize for Colored {
@@ -411,33 +635,32 @@ impl Colored {
             Colored {
                 text: self.text[..midPoint].to_string(),
                 mods: self.mods.clone(),
-                color: self.color.clone(),
-                bgColor: self.bgColor.clone(),
+                color: self.color,
+                bgColor: self.bgColor,
             },
             Colored {
                 text: self.text[midPoint..].to_string(),
                 mods: self.mods.clone(),
-                color: self.color.clone(),
-                bgColor: self.bgColor.clone(),
+                color: self.color,
+                bgColor: self.bgColor,
             }
         )
     }
 
+    /// the same as Split, but the midpoint is counted in characters rather than bytes
+    pub fn SplitChars (&self, midPoint: usize) -> (Colored, Colored) {
+        let index = self.text.char_indices().nth(midPoint).map_or(self.text.len(), |(index, _)| index);
+        self.Split(index)
+    }
+
     pub fn IsUncolored (&self) -> bool {
         self.mods.is_empty() && self.color.is_none() && self.bgColor.is_none()
     }
 
     pub fn Contains (&self, color: &ColorType) -> bool {
-        let col = color.GetColor().UnwrapIntoTuple();
-        if col.0 == self.bgColor && col.2 {  return true;  }
-        if let Some(selfColor) = &self.color {
-            if let Some(otherCol) = col.0 {
-                if selfColor.contains(&otherCol) {  return true;  }
-            }
-        }
-        for modifier in col.1 {
-            if self.mods.contains(&modifier) {  return true;  }
-        } false
+        self.color.as_ref() == Some(color) ||
+            self.bgColor.as_ref() == Some(color) ||
+            self.mods.contains(color)
     }
 
     pub fn ChangeText (&mut self, text: String) {
@@ -446,20 +669,9 @@ impl Colored {
 
     // Adds a color type
     pub fn AddColor (&mut self, color: ColorType) {
-        self.AddUnique(color.GetColor());
-    }
-
-    // Adds a unique color
-    pub fn AddUnique (&mut self, uniqueColor: UniqueColor) {
-        let (color, mods, background) = uniqueColor.UnwrapIntoTuple();
-        if background {  self.bgColor = color;  }
-        else if let Some(col) = color {
-            // making sure to not overwrite the existing color if this is None
-            self.color = Some(col);
-        }
-        for modifier in mods {
-            self.mods.push(modifier);
-        }
+        if color.IsModifier() {  self.mods.push(color);  }
+        else if color.IsBackground() {  self.bgColor = Some(color);  }
+        else {  self.color = Some(color);  }
     }
 
     // Takes a set of color types and returns a filled out Colored instance
@@ -467,8 +679,8 @@ impl Colored {
         let mut colored = Colored {
             text: colored.text.clone(),
             mods: colored.mods.clone(),
-            color: colored.color.clone(),
-            bgColor: colored.bgColor.clone(),
+            color: colored.color,
+            bgColor: colored.bgColor,
         };
         for color in colors {
             colored.AddColor(color);
@@ -483,35 +695,23 @@ impl Colored {
         } colored
     }
 
-    // Takes a set of unique colors and generates a filled out instance
-    pub fn GetFromUniqueColors (text: String, uniqueColors: Vec <UniqueColor>) -> Colored {
-        let mut colored = Colored::new(text);
-        for color in uniqueColors {
-            colored.AddUnique(color);
-        } colored
-    }
-
-    pub fn GetText (&self, lastColor: &mut String) -> (String, usize) {
+    pub fn GetText (&self, lastColor: &mut String, context: &RenderContext) -> (String, usize) {
         let mut text = String::new();
 
-        let col = match &self.color {
-            Some(colr) => colr,
-            _ => &String::new()
-        };
-
-        let bgCol = match &self.bgColor {
-            Some(colr) => colr,
-            _ => &String::new()
-        };
+        let Resolve = |color: &ColorType| color.GetColor(context).UnwrapIntoTuple();
+        let col = self.color.as_ref().and_then(|color| Resolve(color).0);
+        let bgCol = self.bgColor.as_ref().and_then(|color| Resolve(color).0);
+        let mods: Vec <String> = self.mods.iter().flat_map(|modifier| Resolve(modifier).1).collect();
+        let (col, bgCol) = (col.unwrap_or_default(), bgCol.unwrap_or_default());
 
         let color = match
-            (self.bgColor.is_some(), self.color.is_some(), !self.mods.is_empty())
+            (!bgCol.is_empty(), !col.is_empty(), !mods.is_empty())
         {
-            (true, true, true) => format!("\x1b[0;{};{};{}m", col, bgCol, self.mods.join(";")),
+            (true, true, true) => format!("\x1b[0;{};{};{}m", col, bgCol, mods.join(";")),
             (true, true, false) => format!("\x1b[0;{};{}m", col, bgCol),
-            (false, true, true) => format!("\x1b[0;{};{}m", col, self.mods.join(";")),
+            (false, true, true) => format!("\x1b[0;{};{}m", col, mods.join(";")),
             (false, true, false) => format!("\x1b[0;{}m", col),
-            (true, false, true) => format!("\x1b[0;{};{}m", bgCol, self.mods.join(";")),
+            (true, false, true) => format!("\x1b[0;{};{}m", bgCol, mods.join(";")),
             (true, false, false) => format!("\x1b[0;{}m", bgCol),
             (false, false, _) => String::from("\x1b[0m"),
         };
@@ -552,13 +752,13 @@ impl Span {
         size
     }
 
-    pub fn Join (&self) -> (String, usize) {
+    pub fn Join (&self, context: &RenderContext) -> (String, usize) {
         //let mut lastColored = vec![];
         let mut lastColored = String::new();
         let mut total = String::new();
         let mut totalSize = 0;
         for colored in &self.line {
-            let (text, size) = colored.GetText(&mut lastColored);
+            let (text, size) = colored.GetText(&mut lastColored, context);
             total.push_str(&text);
             totalSize += size;
         }
@@ -764,7 +964,7 @@ impl Window {
         renderClosures
     }
 
-    pub fn GetRenderClosure (&mut self) -> RenderClosure {
+    pub fn GetRenderClosure (&mut self, context: &RenderContext) -> RenderClosure {
         if self.wasUpdated {  return vec![];  }  // no re-rendering is needed
 
         let mut renderClosures: RenderClosure = vec![];
@@ -773,7 +973,7 @@ impl Window {
         }
 
         // these will need to be sorted by row, and the cursor movement is handled externally (the u16 pair)
-        let borderColor = self.color.GetText(&mut String::new());
+        let borderColor = self.color.GetText(&mut String::new(), context);
         self.wasUpdated = true;
 
         // make sure to not call UpdateRender when using closures
@@ -781,7 +981,9 @@ impl Window {
             if self.bordered {  1  }
             else {  0  }
         };
-        let mut updated = false;
+        // the border rows can be invalidated on their own (title changes or being overdrawn)
+        let mut updated = self.bordered && !self.updated.is_empty() &&
+            (!self.updated[0] || !self.updated[self.updated.len() - 1]);
         for index in borderedSize..self.size.1 as usize - borderedSize {
             if self.updated[index] {  continue;  }
             self.updated[index] = true;
@@ -789,7 +991,7 @@ impl Window {
 
             let (text, size);
             if index - borderedSize < self.lines.len() {
-                (text, size) = self.lines[index - borderedSize].0.Join();
+                (text, size) = self.lines[index - borderedSize].0.Join(context);
                 self.lines[index - borderedSize].1 = text.clone();
                 self.lines[index - borderedSize].2 = size;
             } else {
@@ -829,6 +1031,7 @@ impl Window {
             let color = borderColor;  // consuming border color here
             let windowSize = self.size.0;  // idk a better way to do this other than cloning
             let title = self.title.clone();
+            let context = *context;
             let closure = move || {
                 let mut text = String::new();
                 text.push_str(&color.0);
@@ -836,7 +1039,7 @@ impl Window {
                 let half = windowSize / 2 - title.1 as u16 / 2 - 1;
                 text.push_str(&"─".repeat(half as usize));
                 text.push_str(CLEAR);
-                text.push_str(&title.0.Join().0);
+                text.push_str(&title.0.Join(&context).0);
                 text.push_str(&color.0);
                 text.push_str(&"─".repeat(windowSize as usize - 2 - half as usize - title.1));
                 text.push('┐');
@@ -851,9 +1054,9 @@ impl Window {
 
     // Gets the rendered text for the individual window
     // This shouldn't crash when rendering out of bounds unlike certain other libraries...
-    pub fn GetRender (&self) -> Vec <String> {
+    pub fn GetRender (&self, context: &RenderContext) -> Vec <String> {
         let mut text = vec![String::new()];
-        let color = self.color.GetText(&mut String::new());
+        let color = self.color.GetText(&mut String::new(), context);
 
         // handling the top border
         let borderSize =
@@ -865,7 +1068,7 @@ impl Window {
                 lineSize += splitSize;
                 text[0].push_str(&"─".repeat(splitSize as usize));
                 lineSize += self.title.1 as u16;
-                text[0].push_str(&self.title.0.Join().0);
+                text[0].push_str(&self.title.0.Join(context).0);
                 //let lineSize = text[0].len();
                 text[0].push_str(&"─".repeat(
                     (self.size.0 as usize).saturating_sub(1 + lineSize as usize)
@@ -1002,9 +1205,249 @@ impl Window {
         }
         self.wasUpdated = true;
     }
+
+    // Flags a single row (relative to the window, including the border) for re-rendering
+    // This is used when something else painted over part of the window
+    pub fn InvalidateRow (&mut self, row: usize) {
+        if row >= self.updated.len() {  return;  }
+        self.updated[row] = false;
+        self.wasUpdated = false;
+    }
 }
 
 
+// A single character on the terminal along with the SGR escape needed to draw it
+// An empty style is the default/cleared color
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Cell {
+    pub chr: char,
+    pub style: String,
+}
+
+impl Default for Cell {
+    fn default () -> Self {
+        Cell {
+            chr: ' ',
+            style: String::new(),
+        }
+    }
+}
+
+// A double buffered grid of cells covering the whole terminal
+// Windows paint into the back buffer, and only the cells that differ from
+// the front buffer (what's currently on screen) get written out
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct CellGrid {
+    width: u16,
+    height: u16,
+    front: Vec <Cell>,
+    back: Vec <Cell>,
+    dirtyRows: Vec <bool>,
+}
+
+impl CellGrid {
+    pub fn new (size: (u16, u16)) -> Self {
+        let mut grid = CellGrid::default();
+        grid.Resize(size);
+        grid
+    }
+
+    // Resets both buffers to blank cells (the terminal is expected to be cleared alongside this)
+    pub fn Resize (&mut self, size: (u16, u16)) {
+        let numCells = size.0 as usize * size.1 as usize;
+        self.width = size.0;
+        self.height = size.1;
+        self.front = vec![Cell::default(); numCells];
+        self.back = vec![Cell::default(); numCells];
+        self.dirtyRows = vec![false; size.1 as usize];
+    }
+
+    // Blanks the back buffer while keeping the front buffer as is
+    // Any cells that don't get repainted will be cleared on the next flush
+    pub fn ClearBack (&mut self) {
+        for cell in self.back.iter_mut() {
+            *cell = Cell::default();
+        }
+        for row in self.dirtyRows.iter_mut() {
+            *row = true;
+        }
+    }
+
+    // Applies an SGR sequence onto the current style
+    // The colors from Colored::GetText always start with a reset, so those simply replace the style
+    fn ApplySgr (style: &mut String, sequence: &str) {
+        let params = &sequence[2..sequence.len() - 1];
+        if params.is_empty() || params == "0" {
+            style.clear();
+        } else if params.starts_with("0;") {
+            style.clear();
+            style.push_str(sequence);
+        } else {
+            style.push_str(sequence);
+        }
+    }
+
+    // Paints a rendered line (text with escape codes) into the back buffer
+    // The position uses the same 1-based coordinates as the cursor escape codes (0 is treated as 1)
+    pub fn Paint (&mut self, x: u16, y: u16, text: &str) {
+        let row = y.max(1) as usize - 1;
+        if row >= self.height as usize {  return;  }
+        let rowStart = row * self.width as usize;
+
+        let mut column = x.max(1) as usize - 1;
+        let mut style = String::new();
+        let mut chars = text.chars();
+        while let Some(chr) = chars.next() {
+            if chr == '\x1b' {
+                // only CSI sequences are expected; anything else is skipped
+                if chars.next() != Some('[') {  continue;  }
+                let mut sequence = String::from("\x1b[");
+                for next in chars.by_ref() {
+                    sequence.push(next);
+                    if ('\x40'..='\x7e').contains(&next) {  break;  }
+                }
+                if sequence.ends_with('m') {
+                    CellGrid::ApplySgr(&mut style, &sequence);
+                }
+                continue;
+            }
+            if chr.is_control() {  continue;  }
+
+            if column < self.width as usize {
+                let cell = &mut self.back[rowStart + column];
+                if cell.chr != chr || cell.style != style {
+                    cell.chr = chr;
+                    cell.style.clone_from(&style);
+                    self.dirtyRows[row] = true;
+                }
+            }
+            column += 1;
+        }
+    }
+
+    // Writes the escape codes for every changed cell into the buffer and syncs the front buffer
+    // Consecutive cells skip the cursor movement, and repeated styles are only emitted once
+    pub fn Flush (&mut self, buffer: &mut String) {
+        let width = self.width as usize;
+        let mut lastStyle: Option <&str> = None;
+        let mut cursor = None;
+        for row in 0..self.height as usize {
+            if !self.dirtyRows[row] {  continue;  }
+            self.dirtyRows[row] = false;
+
+            for column in 0..width {
+                let index = row * width + column;
+                let cell = &self.back[index];
+                if self.front[index] == *cell {  continue;  }
+
+                // moving the cursor into position
+                // ESC[{line};{column}H
+                if cursor != Some((row, column)) {
+                    buffer.push_str("\x1b[");
+                    App::PushU16(buffer, row as u16 + 1);
+                    buffer.push(';');
+                    App::PushU16(buffer, column as u16 + 1);
+                    buffer.push('H');
+                }
+
+                if lastStyle != Some(cell.style.as_str()) {
+                    if !cell.style.starts_with("\x1b[0;") {
+                        buffer.push_str(CLEAR);
+                    }
+                    buffer.push_str(&cell.style);
+                    lastStyle = Some(cell.style.as_str());
+                }
+
+                buffer.push(cell.chr);
+                cursor = Some((row, column + 1));
+                self.front[index].clone_from(cell);
+            }
+        }
+
+        if lastStyle.is_some() {
+            buffer.push_str(CLEAR);
+        }
+    }
+
+    // For dumb terminals, which can't move the cursor or handle the SGR codes
+    // If anything changed, the whole screen is written out as plain lines from the top down
+    pub fn FlushLines (&mut self, buffer: &mut String) {
+        let changed = self.dirtyRows.iter().any(|dirty| *dirty) && self.front != self.back;
+        self.dirtyRows.fill(false);
+        if !changed {  return;  }
+
+        let width = self.width as usize;
+        for cells in self.back.chunks(width.max(1)) {
+            // each row starts on a new line, so one frame never runs on from the last
+            buffer.push_str("\r\n");
+            let line: String = cells.iter().map(|cell| cell.chr).collect();
+            buffer.push_str(line.trim_end());
+        }
+        self.front.clone_from(&self.back);
+    }
+}
+
+// How key presses with modifiers get sent
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum KeyboardProtocol {
+    #[default] Legacy,  // whatever the terminal sends by default (plus any custom codes)
+    ModifyOtherKeys,  // xterm's CSI 27 ; mods ; key ~
+    Kitty,  // CSI key ; mods u, along with release events
+}
+
+// What the terminal is able to handle
+// This starts off from the environment variables and gets refined once the
+// terminal replies to the queries (the replies come back through stdin)
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct TerminalCapabilities {
+    pub term: String,
+    pub colorTerm: String,
+    pub dumb: bool,
+    pub trueColor: bool,
+    pub colors256: bool,
+    pub synchronizedOutput: bool,
+    pub background: Option <(u8, u8, u8)>,
+    pub kittyKeyboard: bool,
+    pub keyboardProtocol: KeyboardProtocol,  // the one picked once the queries are answered
+    pub answered: bool,  // set once the terminal replies to the device attributes query
+}
+
+impl TerminalCapabilities {
+    pub fn FromEnvironment () -> Self {
+        let term = std::env::var("TERM").unwrap_or_default();
+        let colorTerm = std::env::var("COLORTERM").unwrap_or_default();
+        let dumb = term.is_empty() || term == "dumb";
+        let trueColor = !dumb && (
+            matches!(colorTerm.as_str(), "truecolor" | "24bit") || term.contains("direct")
+        );
+        TerminalCapabilities {
+            colors256: trueColor || term.contains("256color"),
+            term,
+            colorTerm,
+            dumb,
+            trueColor,
+            synchronizedOutput: false,
+            background: None,
+            kittyKeyboard: false,
+            keyboardProtocol: KeyboardProtocol::Legacy,
+            answered: false,
+        }
+    }
+
+    // Asks the terminal about what the environment can't tell
+    // Raw mode needs to be enabled first, otherwise the replies get echoed
+    pub fn Query (&self) {
+        if self.dumb {  return;  }
+        print!("\x1b[?2026$p");  // DECRQM for synchronized output
+        print!("\x1bP+q524742\x1b\\");  // XTGETTCAP for RGB (the names are hex encoded)
+        print!("\x1bP+q5463\x1b\\");  // XTGETTCAP for Tc
+        print!("\x1b]11;?\x1b\\");  // OSC 11 for the background color (light/dark detection)
+        print!("\x1b[?u");  // the kitty keyboard protocol's flags (only answered if it's supported)
+        print!("\x1b[c");  // primary device attributes; every terminal answers this one, so it marks the end
+        let _ = std::io::stdout().flush();
+    }
+}
+
 // the main window/application that handles all the windows
 #[derive(Clone, Debug, Eq, PartialEq, Default, Hash)]
 pub struct Rect {
@@ -1023,11 +1466,15 @@ pub struct App {
     updated: bool,
     renderHandle: Option <std::thread::JoinHandle <()>>,
     buffer: std::sync::Arc <parking_lot::RwLock <String>>,
+    grid: std::sync::Arc <parking_lot::RwLock <CellGrid>>,
     resetWindows: bool,
+    capabilities: TerminalCapabilities,
+    renderContext: RenderContext,
 }
 
 impl Drop for App {
     fn drop (&mut self) {
+        if self.capabilities.dumb {  return;  }  // nothing was changed to begin with
         print!("{SHOW_CURSOR}");  // showing the cursor
 
         // clearing the screen
@@ -1044,9 +1491,13 @@ impl Drop for App {
 
 impl App {
     pub fn new () -> Self {  // 1049h
-        print!("\x1B7");
-        print!("\x1B[?1049h");
-        print!("\x1B[?25l");
+        let capabilities = TerminalCapabilities::FromEnvironment();
+        // dumb terminals don't have an alternate screen (or a cursor to hide)
+        if !capabilities.dumb {
+            print!("\x1B7");
+            print!("\x1B[?1049h");
+            print!("\x1B[?25l");
+        }
         App {
             area: Rect::default(),
             activeWindows: vec![],
@@ -1055,10 +1506,39 @@ impl App {
             updated: true,
             renderHandle: None,
             buffer: std::sync::Arc::new(parking_lot::RwLock::new(String::new())),
+            grid: std::sync::Arc::new(parking_lot::RwLock::new(CellGrid::default())),
             resetWindows: false,
+            capabilities,
+            renderContext: RenderContext::default(),
         }
     }
 
+    pub fn GetColorMode (&self) -> ColorMode {
+        self.renderContext.colorMode
+    }
+
+    // Same as the light/dark mode; every rgb color might quantize differently
+    pub fn SetColorType (&mut self, colorType: ColorTypes) {
+        if self.renderContext.colorType == colorType {  return;  }
+        self.renderContext.colorType = colorType;
+        self.resetWindows = true;
+    }
+
+    // Switching modes changes every color, so all the windows get re-rendered
+    pub fn SetColorMode (&mut self, colorMode: ColorMode) {
+        if self.renderContext.colorMode == colorMode {  return;  }
+        self.renderContext.colorMode = colorMode;
+        self.resetWindows = true;
+    }
+
+    pub fn GetCapabilities (&self) -> &TerminalCapabilities {
+        &self.capabilities
+    }
+
+    pub fn GetCapabilitiesMut (&mut self) -> &mut TerminalCapabilities {
+        &mut self.capabilities
+    }
+
     pub fn ContainsWindow (&self, name: String) -> bool {
         self.windowReferences.contains_key(&name)
     }
@@ -1173,7 +1653,8 @@ impl App {
         }
 
         self.buffer.write().clear();
-        if size.0 != self.area.width || size.1 != self.area.height || self.resetWindows {
+        let resized = size.0 != self.area.width || size.1 != self.area.height;
+        if resized || self.resetWindows {
             self.resetWindows = false;
             *self.buffer.write() = String::with_capacity((size.0 * size.1 * 3) as usize);
 
@@ -1184,11 +1665,54 @@ impl App {
                 window.0.UpdateAll();
             }
 
-            // replace with an actual clear..... this doesn't work (it just shifts the screen--or does it???)
-            print!("\x1b[2J\x1b[H");  // re-clearing the screen (everything will need to update)
+            if resized {
+                // replace with an actual clear..... this doesn't work (it just shifts the screen--or does it???)
+                // re-clearing the screen (everything will need to update); a dumb terminal just gets the new frame below
+                if !self.capabilities.dumb {  print!("\x1b[2J\x1b[H");  }
+                self.grid.write().Resize(*size);
+            } else {
+                // the screen is still intact, so only the cells that actually changed get redrawn
+                self.grid.write().ClearBack();
+            }
         }
     }
 
+    // Gathers the draw calls from every window (lowest depth first)
+    // Anything painted underneath a window (or cleared by a hidden one) forces the
+    // overlapped rows of that window to be re-rendered so it stays on top
+    fn GatherDrawCalls (&mut self) -> RenderClosure {
+        let context = self.renderContext;
+        let mut order: Vec <usize> = (0..self.activeWindows.len()).collect();
+        order.sort_by_key(|index| {
+            let window = &self.activeWindows[*index].0;
+            (!window.hidden, window.depth)
+        });
+
+        let mut drawCalls = vec![];
+        // (x, y, width, depth) using the 1-based positions
+        let mut paintedSpans: Vec <(u16, u16, u16, u16)> = vec![];
+        for index in order {
+            let window = &mut self.activeWindows[index].0;
+            let windowX = window.position.0.max(1);
+            let windowY = window.position.1.max(1);
+            if !window.hidden {
+                for span in &paintedSpans {
+                    if span.3 > window.depth ||
+                        span.1 < windowY || span.1 >= windowY + window.size.1 ||
+                        span.0 + span.2 <= windowX || span.0 >= windowX + window.size.0
+                    {  continue;  }
+                    window.InvalidateRow((span.1 - windowY) as usize);
+                }
+            }
+
+            let mut closures = window.GetRenderClosure(&context);
+            for call in &closures {
+                paintedSpans.push((call.1.max(1), call.2.max(1), window.size.0, call.3));
+            }
+            drawCalls.append(&mut closures);
+        } drawCalls
+    }
+
     // Renders all the active windows to the consol
     // It also clears the screen from previous writing
     pub fn Render (&mut self, terminalSize: Option <(u16, u16)>) -> usize {
@@ -1213,47 +1737,49 @@ impl App {
         if !updated {  return 0;  }
         
         // stores the draw calls
-        let mut drawCalls = vec![];
-
-        // going through the sorted windows
-        for window in &mut self.activeWindows {
-            //let window = &mut self.activeWindows[*index];
-            drawCalls.append(&mut window.0.GetRenderClosure());
-        }
+        let mut drawCalls = self.GatherDrawCalls();
 
         let numCalls = drawCalls.len();
 
         let size = (self.area.width, self.area.height);
         let buffer = self.buffer.clone();
+        let grid = self.grid.clone();
+        let synchronized = self.capabilities.synchronizedOutput;
+        let styled = !self.capabilities.dumb;
         //println!("Num calls: {}", drawCalls.len());
         self.renderHandle = Some(std::thread::spawn(move || {
-            // the buffer for the render string
+            // sorting the calls by depth, then row (and left to right for same row calls)
+            // drawCall.3 is the depth; higher numbers will be painted last thus being on top (each depth is a unique layer)
+            drawCalls.sort_by_key(|drawCall| (drawCall.3, drawCall.2, drawCall.1));
 
-            // sorting the calls by action row (and left to right for same row calls)
-            // drawCall.3 is the depth; higher numbers will be rendered last thus being on top (each depth is a unique layer)
-            drawCalls.sort_by_key(|drawCall| drawCall.2 * size.0 + drawCall.1 + drawCall.3 * size.0 * size.1);
+            // painting into the back buffer (consuming drawCalls)
+            let grid = &mut *grid.write();
+            for call in drawCalls {
+                let output = call.0();
+                grid.Paint(call.1, call.2, &output);
+            }
 
-            // iterating through the calls (consuming drawCalls)
+            // only the cells which changed get written
             let writeBuffer = &mut *buffer.write();
-            for call in drawCalls {
-                // moving the cursor into position
-                // ESC[{line};{column}H
+            if synchronized {
+                writeBuffer.push_str(BEGIN_SYNCHRONIZED_UPDATE);
+            }
+            let start = writeBuffer.len();
+            if styled {  grid.Flush(writeBuffer);  }
+            else {  grid.FlushLines(writeBuffer);  }
+            if writeBuffer.len() == start {  return;  }
+
+            // moving the cursor to the bottom right
+            if styled {
                 writeBuffer.push_str("\x1b[");
-                App::PushU16(writeBuffer, call.2);
+                App::PushU16(writeBuffer, size.1);
                 writeBuffer.push(';');
-                App::PushU16(writeBuffer, call.1);
+                App::PushU16(writeBuffer, size.0);
                 writeBuffer.push('H');
-
-                let output = call.0();
-                writeBuffer.push_str(&output);
             }
-
-            // moving the cursor to the bottom right
-            writeBuffer.push_str("\x1b[");
-            App::PushU16(writeBuffer, size.1);
-            writeBuffer.push(';');
-            App::PushU16(writeBuffer, size.0);
-            writeBuffer.push_str("H ");
+            if synchronized {
+                writeBuffer.push_str(END_SYNCHRONIZED_UPDATE);
+            }
 
             // rendering the buffer
             let mut out = std::io::stdout().lock();
@@ -1409,3 +1935,27 @@ impl App {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn DumbFlushesArePlainLines () {
+        let mut grid = CellGrid::new((6, 2));
+        grid.ClearBack();
+        grid.Paint(1, 1, "\x1b[0;31mab\x1b[0m c");
+        grid.Paint(3, 2, "xy");
+        let mut buffer = String::new();
+        grid.FlushLines(&mut buffer);
+        assert_eq!(buffer, "\r\nab c\r\n  xy");
+
+        // nothing's written again until something changes
+        buffer.clear();
+        grid.ClearBack();
+        grid.Paint(1, 1, "\x1b[0;31mab\x1b[0m c");
+        grid.Paint(3, 2, "xy");
+        grid.FlushLines(&mut buffer);
+        assert!(buffer.is_empty());
+    }
+}
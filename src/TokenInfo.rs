@@ -2,10 +2,65 @@ use proc_macros::load_language_types;
 use mlua::{Error, FromLua, Lua, Value};
 use parking_lot::RwLock;
 use std::sync::Arc;
+use crate::Snippets::Snippet;
 
 // loads all the languages from the provided file
 load_language_types!("data/syntaxHighlighting.json");
 
+impl Languages {
+    // anything without a language of its own goes through the null script
+    pub fn FromExtension (extension: &str) -> Languages {
+        LANGS.iter()
+            .find(|(_, languageExtension)| *languageExtension == extension)
+            .map(|(language, _)| *language)
+            .unwrap_or(Languages::Null)
+    }
+}
+
+/// The functions a language's Lua script provides. Only GetTokens is required; the rest fall back
+/// to the editor's own behaviour when a script leaves them out.
+#[derive(Debug)]
+pub struct LanguageScript {
+    pub getTokens: mlua::Function,
+    // GetIndentChange(previousLine, nextLine) -> the number of levels the next line indents by (nil for the bracket rules)
+    pub getIndentChange: Option <mlua::Function>,
+    // the autoPairs table, e.g. {"()", "[]", "\"\""} (the opening and closing characters that get typed together)
+    pub pairs: Vec <(char, char)>,
+    // lineComment = "//" and blockComment = {"/*", "*/"} (what the comment toggle puts in; either can be left out)
+    pub lineComment: Option <String>,
+    pub blockComment: Option <(String, String)>,
+    // the snippets table, e.g. {fn = "fn ${1:name}() {\n\t$0\n}"} (see Snippets.rs for the syntax)
+    pub snippets: Vec <Snippet>,
+}
+
+// the pairs for a script without an autoPairs table
+pub const DEFAULT_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+impl LanguageScript {
+    pub fn Load (lua: &Lua) -> LanguageScript {
+        let globals = lua.globals();
+        LanguageScript {
+            getTokens: globals.get("GetTokens").unwrap(),
+            getIndentChange: globals.get::<Option <mlua::Function>>("GetIndentChange").ok().flatten(),
+            pairs: globals.get::<Option <Vec <String>>>("autoPairs").ok().flatten()
+                .map(|pairs| pairs.iter().filter_map(|pair| {
+                    let mut chars = pair.chars();
+                    Some((chars.next()?, chars.next()?))
+                }).collect())
+                .unwrap_or(DEFAULT_PAIRS.to_vec()),
+            lineComment: globals.get::<Option <String>>("lineComment").ok().flatten(),
+            blockComment: globals.get::<Option <Vec <String>>>("blockComment").ok().flatten()
+                .and_then(|block| match block.as_slice() {
+                    [open, close] => Some((open.clone(), close.clone())),
+                    _ => None,
+                }),
+            snippets: globals.get::<Option <mlua::Table>>("snippets").ok().flatten()
+                .map(Snippet::FromLua)
+                .unwrap_or_default(),
+        }
+    }
+}
+
 
 /// Use the following to create a valid interface. Additionally, link the script which implements this
 /// trait in syntaxHighlighting.json. There has to be a wrapper method labeled GenerateScopes that
@@ -142,6 +197,41 @@ pub enum TokenType {
     Grayed,
 }
 
+impl TokenType {
+    // Gets the token type from its name (used when loading themes)
+    // The references are static so they can be used as keys for the color bindings
+    pub fn FromName (name: &str) -> Option <&'static TokenType> {
+        Some(match name {
+            "Bracket" => &TokenType::Bracket,
+            "SquirlyBracket" => &TokenType::SquirlyBracket,
+            "Parentheses" => &TokenType::Parentheses,
+            "Variable" => &TokenType::Variable,
+            "Member" => &TokenType::Member,
+            "Object" => &TokenType::Object,
+            "Function" => &TokenType::Function,
+            "Method" => &TokenType::Method,
+            "Number" => &TokenType::Number,
+            "Logic" => &TokenType::Logic,
+            "Math" => &TokenType::Math,
+            "Assignment" => &TokenType::Assignment,
+            "Endl" => &TokenType::Endl,
+            "Macro" => &TokenType::Macro,
+            "Const" => &TokenType::Const,
+            "Barrow" => &TokenType::Barrow,
+            "Lifetime" => &TokenType::Lifetime,
+            "String" => &TokenType::String,
+            "Comment" => &TokenType::Comment,
+            "Null" => &TokenType::Null,
+            "Primitive" => &TokenType::Primitive,
+            "Keyword" => &TokenType::Keyword,
+            "CommentLong" => &TokenType::CommentLong,
+            "Unsafe" => &TokenType::Unsafe,
+            "Grayed" => &TokenType::Grayed,
+            _ => return None,
+        })
+    }
+}
+
 
 // tracking both the next line flags, but also individual token flags for variable/outline generation (auto complete stuff ig)
 #[derive(Debug, Clone, PartialEq)]
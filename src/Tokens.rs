@@ -149,21 +149,15 @@ pub async fn GenerateTokens (
     let tokenStrs = GenerateTokenStrs(text.as_str());
 
     // start a lua execution thread here
-    let mut language = Languages::Null;  // the default
-    for (lang, extension) in LANGS.iter() {
-        if *extension == fileType {
-            language = *lang;
-            break;
-        }
-    }
+    let language = Languages::FromExtension(fileType);
 
     // calling the lua script (dealing with annoying async stuff)
     let highlightingScript = luaSyntaxHighlightScripts.lock();
     let script: Arc <&mlua::Function> =
         if highlightingScript.contains_key(&language) {
-            Arc::clone(&Arc::from(&highlightingScript[&language]))
+            Arc::clone(&Arc::from(&highlightingScript[&language].getTokens))
         } else {
-            Arc::clone(&Arc::from(&highlightingScript[&Languages::Null]))
+            Arc::clone(&Arc::from(&highlightingScript[&Languages::Null].getTokens))
     };
 
     // spawning the threads
@@ -0,0 +1,1074 @@
+// the optional vim style modal layer that sits on top of the code tabs (toggled with -vim)
+// normal and visual mode keys get parsed here, insert mode just falls through to the normal typing
+
+use std::collections::HashMap;
+use arboard::Clipboard;
+
+use crate::App as MainApp;
+use crate::{AppState, RustAnalyzerLsp};
+use crate::CodeTabs::CodeTab;
+use crate::Macros;
+use crate::eventHandler::{KeyCode, KeyFrame, KeyModifiers, KeyParser};
+
+// the keys the normal mode parser cares about (the escape codes for these come in as key codes, not chars)
+const VIM_KEY_CODES: [KeyCode; 10] = [
+    KeyCode::Escape, KeyCode::Return, KeyCode::Delete, KeyCode::ForwardDelete,
+    KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down, KeyCode::Home, KeyCode::End,
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VimMode {
+    #[default] Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+impl VimMode {
+    pub fn Name (&self) -> &'static str {
+        match self {
+            VimMode::Normal => "NORMAL",
+            VimMode::Insert => "INSERT",
+            VimMode::Visual => "VISUAL",
+            VimMode::VisualLine => "V-LINE",
+        }
+    }
+
+    fn IsVisual (&self) -> bool {
+        matches!(self, VimMode::Visual | VimMode::VisualLine)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimKey {
+    Char (char),
+    Control (char),
+    Code (KeyCode),
+}
+
+impl VimKey {
+    // anything held with command/option (or control, other than ctrl+r) is left to the keymap
+    pub fn FromEvents (keyEvents: &KeyParser) -> Vec <VimKey> {
+        if keyEvents.ContainsModifier(&KeyModifiers::Command) ||
+            keyEvents.ContainsModifier(&KeyModifiers::Option) {  return vec![];  }
+
+        if keyEvents.ContainsModifier(&KeyModifiers::Control) {
+            return keyEvents.charEvents.iter()
+                .filter(|chr| chr.eq_ignore_ascii_case(&'r'))
+                .map(|_| VimKey::Control('r'))
+                .collect();
+        }
+
+        let mut keys: Vec <VimKey> = VIM_KEY_CODES.iter()
+            .filter(|key| keyEvents.ContainsKeyCode(**key))
+            .map(|key| VimKey::Code(*key))
+            .collect();
+        keys.extend(keyEvents.charEvents.iter().map(|chr| VimKey::Char(*chr)));
+        keys
+    }
+
+    fn Display (&self) -> String {
+        match self {
+            VimKey::Char(chr) => chr.to_string(),
+            VimKey::Control(chr) => format!("^{}", chr.to_ascii_uppercase()),
+            VimKey::Code(key) => format!("<{:?}>", key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Unindent,
+}
+
+impl Operator {
+    fn FromChar (chr: char) -> Option <Operator> {
+        match chr {
+            'd' => Some(Operator::Delete),
+            'c' => Some(Operator::Change),
+            'y' => Some(Operator::Yank),
+            '>' => Some(Operator::Indent),
+            '<' => Some(Operator::Unindent),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextObject {
+    Word,
+    Pair (u8, u8),
+    Paragraph,
+}
+
+impl TextObject {
+    fn FromChar (chr: char) -> Option <TextObject> {
+        match chr {
+            'w' => Some(TextObject::Word),
+            '(' | ')' | 'b' => Some(TextObject::Pair(b'(', b')')),
+            '{' | '}' | 'B' => Some(TextObject::Pair(b'{', b'}')),
+            '[' | ']' => Some(TextObject::Pair(b'[', b']')),
+            'p' => Some(TextObject::Paragraph),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordBack,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FileStart,
+    FileEnd,
+    FindChar { target: u8, forward: bool, till: bool },
+    RepeatFind { reverse: bool },
+    MatchPair,
+}
+
+impl Motion {
+    fn IsLinewise (&self) -> bool {
+        matches!(self, Motion::Up | Motion::Down | Motion::FileStart | Motion::FileEnd)
+    }
+
+    // inclusive motions take the character they land on with them when operated on
+    fn IsInclusive (&self) -> bool {
+        matches!(self, Motion::WordEnd | Motion::LineEnd | Motion::MatchPair |
+            Motion::FindChar { forward: true, .. })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Motion (Motion),
+    Object (TextObject),
+    Lines,
+    Selection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InsertAt {
+    Cursor,
+    After,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Move (Motion),
+    Operate (Operator, Target),
+    SelectObject (TextObject),
+    Insert (InsertAt),
+    Visual (VimMode),
+    SwapEnds,
+    Put { before: bool },
+    DeleteChar { before: bool },
+    Undo,
+    Redo,
+    Repeat,
+    RecordMacro (char),
+    PlayMacro (Option <char>),  // none plays the last macro again
+    OpenPrompt,
+}
+
+impl Command {
+    // the commands that '.' can repeat
+    fn IsChange (&self) -> bool {
+        match self {
+            Command::Operate(Operator::Yank, _) => false,
+            Command::Operate(..) | Command::Insert(_) | Command::Put { .. } |
+            Command::DeleteChar { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+enum Parsed <T> {
+    Pending,
+    Invalid,
+    Done (T),
+}
+
+struct ParsedCommand {
+    register: Option <char>,
+    count: Option <usize>,
+    command: Command,
+}
+
+// a span of text; the end is exclusive unless it's linewise (then only the lines matter)
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    start: (usize, usize),
+    end: (usize, usize),
+    linewise: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+// the keys of the last change along with whatever got typed if it went into insert mode
+#[derive(Debug, Clone, Default)]
+struct Change {
+    keys: Vec <VimKey>,
+    frames: Vec <KeyFrame>,
+}
+
+#[derive(Debug, Default)]
+pub struct VimState {
+    pub mode: VimMode,
+    pending: Vec <VimKey>,
+    registers: HashMap <char, Register>,
+    lastFind: Option <Motion>,
+    visualAnchor: (usize, usize),
+    visualCursor: (usize, usize),
+    insertUndoDepth: usize,
+    recording: Option <Change>,
+    lastChange: Option <Change>,
+    repeating: bool,
+}
+
+impl VimState {
+    // the mode and any half typed command, for the status area
+    pub fn StatusText (&self) -> String {
+        let pending: String = self.pending.iter().map(|key| key.Display()).collect();
+        if pending.is_empty() {  format!("-- {} --", self.mode.Name())  }
+        else {  format!("-- {} -- {}", self.mode.Name(), pending)  }
+    }
+
+    // escape backs out of a mode or command before it goes to switching panels
+    pub fn TakesEscape (&self) -> bool {
+        self.mode != VimMode::Normal || !self.pending.is_empty()
+    }
+
+    fn SetRegister (&mut self, name: Option <char>, register: Register, yanked: bool, clipBoard: &mut Clipboard) {
+        match name {
+            Some('_') => return,
+            Some('+' | '*') => {  let _ = clipBoard.set_text(register.text.clone());  },
+            Some(name) if name.is_ascii_uppercase() => {
+                let named = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                named.text.push_str(&register.text);
+                named.linewise |= register.linewise;
+            },
+            Some(name) if name.is_ascii_alphanumeric() => {
+                self.registers.insert(name, register.clone());
+            },
+            _ => {},
+        }
+
+        if yanked {
+            self.registers.insert('0', register.clone());
+        } else {
+            // the older deletes get pushed back through 1-9
+            for index in (1..9u32).rev() {
+                let from = char::from_digit(index, 10).unwrap_or('1');
+                let to = char::from_digit(index + 1, 10).unwrap_or('9');
+                if let Some(older) = self.registers.remove(&from) {
+                    self.registers.insert(to, older);
+                }
+            }
+            self.registers.insert('1', register.clone());
+        }
+        self.registers.insert('"', register);
+    }
+
+    fn GetRegister (&self, name: Option <char>, clipBoard: &mut Clipboard) -> Option <Register> {
+        match name {
+            Some('+' | '*') => {
+                let text = clipBoard.get_text().ok()?;
+                Some(Register {  linewise: text.ends_with('\n'), text  })
+            },
+            Some(name) => self.registers.get(&name.to_ascii_lowercase()).cloned(),
+            None => self.registers.get(&'"').cloned(),
+        }
+    }
+}
+
+// reads a count (a leading zero is the line start motion, not a count)
+fn ParseCount (keys: &[VimKey], index: &mut usize) -> Option <usize> {
+    let mut count: Option <usize> = None;
+    while let Some(VimKey::Char(chr)) = keys.get(*index) {
+        let Some(digit) = chr.to_digit(10) else {  break;  };
+        if digit == 0 && count.is_none() {  break;  }
+        count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit as usize));
+        *index += 1;
+    }
+    count
+}
+
+fn ParseMotion (keys: &[VimKey]) -> Parsed <Motion> {
+    let Some(key) = keys.first() else {  return Parsed::Pending;  };
+    let motion = match key {
+        VimKey::Char('h') | VimKey::Code(KeyCode::Left) | VimKey::Code(KeyCode::Delete) => Motion::Left,
+        VimKey::Char('l' | ' ') | VimKey::Code(KeyCode::Right) => Motion::Right,
+        VimKey::Char('k') | VimKey::Code(KeyCode::Up) => Motion::Up,
+        VimKey::Char('j') | VimKey::Code(KeyCode::Down) | VimKey::Code(KeyCode::Return) => Motion::Down,
+        VimKey::Char('w') => Motion::WordStart,
+        VimKey::Char('b') => Motion::WordBack,
+        VimKey::Char('e') => Motion::WordEnd,
+        VimKey::Char('0') | VimKey::Code(KeyCode::Home) => Motion::LineStart,
+        VimKey::Char('^') => Motion::FirstNonBlank,
+        VimKey::Char('$') | VimKey::Code(KeyCode::End) => Motion::LineEnd,
+        VimKey::Char('G') => Motion::FileEnd,
+        VimKey::Char('%') => Motion::MatchPair,
+        VimKey::Char(';') => Motion::RepeatFind { reverse: false },
+        VimKey::Char(',') => Motion::RepeatFind { reverse: true },
+        VimKey::Char('g') => match keys.get(1) {
+            None => return Parsed::Pending,
+            Some(VimKey::Char('g')) => Motion::FileStart,
+            _ => return Parsed::Invalid,
+        },
+        VimKey::Char(find @ ('f' | 'F' | 't' | 'T')) => match keys.get(1) {
+            None => return Parsed::Pending,
+            Some(VimKey::Char(target)) if target.is_ascii() => Motion::FindChar {
+                target: *target as u8,
+                forward: find.is_ascii_lowercase(),
+                till: matches!(find, 't' | 'T'),
+            },
+            _ => return Parsed::Invalid,
+        },
+        _ => return Parsed::Invalid,
+    };
+    Parsed::Done(motion)
+}
+
+// the text object after an 'i' (only the inner objects are supported)
+fn ParseObject (keys: &[VimKey]) -> Parsed <TextObject> {
+    match keys.first() {
+        None => Parsed::Pending,
+        Some(VimKey::Char(chr)) => match TextObject::FromChar(*chr) {
+            Some(object) => Parsed::Done(object),
+            None => Parsed::Invalid,
+        },
+        _ => Parsed::Invalid,
+    }
+}
+
+fn ParseCommand (keys: &[VimKey], mode: VimMode) -> Parsed <ParsedCommand> {
+    let mut index = 0;
+    let mut register = None;
+    if keys.first() == Some(&VimKey::Char('"')) {
+        match keys.get(1) {
+            None => return Parsed::Pending,
+            Some(VimKey::Char(name)) => register = Some(*name),
+            _ => return Parsed::Invalid,
+        }
+        index = 2;
+    }
+
+    let mut count = ParseCount(keys, &mut index);
+    let Some(key) = keys.get(index) else {  return Parsed::Pending;  };
+    let visual = mode.IsVisual();
+    let command = match key {
+        VimKey::Char(chr) if let Some(operator) = Operator::FromChar(*chr) => {
+            if visual {
+                Command::Operate(operator, Target::Selection)
+            } else {
+                index += 1;
+                // counts multiply, so 2d3w deletes six words
+                if let Some(inner) = ParseCount(keys, &mut index) {
+                    count = Some(count.unwrap_or(1).saturating_mul(inner));
+                }
+                let target = match keys.get(index) {
+                    None => return Parsed::Pending,
+                    Some(VimKey::Char(other)) if other == chr => Target::Lines,
+                    Some(VimKey::Char('i')) => match ParseObject(&keys[index + 1..]) {
+                        Parsed::Done(object) => Target::Object(object),
+                        Parsed::Pending => return Parsed::Pending,
+                        Parsed::Invalid => return Parsed::Invalid,
+                    },
+                    _ => match ParseMotion(&keys[index..]) {
+                        // cw acts like ce (the spaces after the word are left alone)
+                        Parsed::Done(Motion::WordStart) if operator == Operator::Change => Target::Motion(Motion::WordEnd),
+                        Parsed::Done(motion) => Target::Motion(motion),
+                        Parsed::Pending => return Parsed::Pending,
+                        Parsed::Invalid => return Parsed::Invalid,
+                    },
+                };
+                Command::Operate(operator, target)
+            }
+        },
+        VimKey::Char('D') if !visual => Command::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+        VimKey::Char('C') if !visual => Command::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+        VimKey::Char('Y') if !visual => Command::Operate(Operator::Yank, Target::Lines),
+        VimKey::Char('x') | VimKey::Code(KeyCode::ForwardDelete) => {
+            if visual {  Command::Operate(Operator::Delete, Target::Selection)  }
+            else {  Command::DeleteChar { before: false }  }
+        },
+        VimKey::Char('X') if !visual => Command::DeleteChar { before: true },
+        VimKey::Char('i') if visual => match ParseObject(&keys[index + 1..]) {
+            Parsed::Done(object) => Command::SelectObject(object),
+            Parsed::Pending => return Parsed::Pending,
+            Parsed::Invalid => return Parsed::Invalid,
+        },
+        VimKey::Char('o') if visual => Command::SwapEnds,
+        VimKey::Char('i') if !visual => Command::Insert(InsertAt::Cursor),
+        VimKey::Char('a') if !visual => Command::Insert(InsertAt::After),
+        VimKey::Char('I') if !visual => Command::Insert(InsertAt::LineStart),
+        VimKey::Char('A') if !visual => Command::Insert(InsertAt::LineEnd),
+        VimKey::Char('o') => Command::Insert(InsertAt::LineBelow),
+        VimKey::Char('O') if !visual => Command::Insert(InsertAt::LineAbove),
+        VimKey::Char('v') => Command::Visual(VimMode::Visual),
+        VimKey::Char('V') => Command::Visual(VimMode::VisualLine),
+        VimKey::Char('p') if !visual => Command::Put { before: false },
+        VimKey::Char('P') if !visual => Command::Put { before: true },
+        VimKey::Char('u') if !visual => Command::Undo,
+        VimKey::Control('r') => Command::Redo,
+        VimKey::Char('.') if !visual => Command::Repeat,
+        VimKey::Char('q') if !visual => match keys.get(index + 1) {
+            None => return Parsed::Pending,
+            Some(VimKey::Char(register)) if register.is_ascii_alphanumeric() => Command::RecordMacro(*register),
+            _ => return Parsed::Invalid,
+        },
+        VimKey::Char('@') if !visual => match keys.get(index + 1) {
+            None => return Parsed::Pending,
+            Some(VimKey::Char('@')) => Command::PlayMacro(None),
+            Some(VimKey::Char(register)) if register.is_ascii_alphanumeric() => Command::PlayMacro(Some(*register)),
+            _ => return Parsed::Invalid,
+        },
+        VimKey::Char(':') => Command::OpenPrompt,
+        _ => match ParseMotion(&keys[index..]) {
+            Parsed::Done(motion) => Command::Move(motion),
+            Parsed::Pending => return Parsed::Pending,
+            Parsed::Invalid => return Parsed::Invalid,
+        },
+    };
+
+    Parsed::Done(ParsedCommand {  register, count, command  })
+}
+
+fn FirstNonBlank (line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn Ordered (first: (usize, usize), second: (usize, usize)) -> ((usize, usize), (usize, usize)) {
+    if first <= second {  (first, second)  }
+    else {  (second, first)  }
+}
+
+// the start of the next word (stopping on empty lines like vim does)
+fn NextWordStart (tab: &CodeTab, position: (usize, usize)) -> (usize, usize) {
+    let line = tab.lines[position.0].as_bytes();
+    let mut column = tab.TokenRange(position)
+        .map(|(_, end)| end)
+        .unwrap_or(position.1 + 1);
+    while column < line.len() && line[column] == b' ' {  column += 1;  }
+    if column < line.len() || position.0 + 1 >= tab.lines.len() {
+        return (position.0, std::cmp::min(column, line.len()));
+    }
+
+    let next = position.0 + 1;
+    (next, FirstNonBlank(&tab.lines[next]))
+}
+
+// the start of the previous word, going off of FindTokenPosLeft
+fn PreviousWordStart (tab: &mut CodeTab, position: (usize, usize)) -> (usize, usize) {
+    let mut position = position;
+    if position.1 == 0 || tab.lines[position.0][..position.1].trim().is_empty() {
+        if position.0 == 0 {  return (0, 0);  }
+        position = (position.0 - 1, tab.lines[position.0 - 1].len());
+        if tab.lines[position.0].trim().is_empty() {  return (position.0, 0);  }
+    }
+
+    tab.cursor = position;
+    let distance = tab.FindTokenPosLeft();
+    (position.0, position.1 - distance)
+}
+
+// the last character of the current or next word, going off of FindTokenPosRight
+fn NextWordEnd (tab: &mut CodeTab, position: (usize, usize)) -> (usize, usize) {
+    let mut position = (position.0, position.1 + 1);
+    loop {
+        let line = &tab.lines[position.0];
+        if position.1 < line.len() && !line[position.1..].trim().is_empty() {  break;  }
+        if position.0 + 1 >= tab.lines.len() {
+            return (position.0, tab.lines[position.0].len().saturating_sub(1));
+        }
+        position = (position.0 + 1, 0);
+    }
+
+    tab.cursor = position;
+    let distance = tab.FindTokenPosRight();
+    (position.0, (position.1 + distance).saturating_sub(1))
+}
+
+// where the motion lands, or none if it can't be made (f without a match)
+fn MotionTarget (tab: &mut CodeTab, motion: Motion, count: Option <usize>, lastFind: Option <Motion>) -> Option <(usize, usize)> {
+    let start = tab.cursor;
+    let repeat = count.unwrap_or(1).max(1);
+    let lastLine = tab.lines.len() - 1;
+    let target = match motion {
+        Motion::Left => (start.0, start.1.saturating_sub(repeat)),
+        Motion::Right => (start.0, std::cmp::min(start.1 + repeat, tab.lines[start.0].len())),
+        Motion::Up | Motion::Down => {
+            // a folded range counts as a single line
+            let line = tab.StepVisibleLines(start.0, repeat, motion == Motion::Up);
+            (line, std::cmp::min(start.1, tab.lines[line].len()))
+        },
+        Motion::WordStart => (0..repeat).fold(start, |position, _| NextWordStart(tab, position)),
+        Motion::WordBack => (0..repeat).fold(start, |position, _| PreviousWordStart(tab, position)),
+        Motion::WordEnd => (0..repeat).fold(start, |position, _| NextWordEnd(tab, position)),
+        Motion::LineStart => (start.0, 0),
+        Motion::FirstNonBlank => (start.0, FirstNonBlank(&tab.lines[start.0])),
+        Motion::LineEnd => {
+            let line = std::cmp::min(start.0 + repeat - 1, lastLine);
+            (line, tab.lines[line].len().saturating_sub(1))
+        },
+        Motion::FileStart | Motion::FileEnd => {
+            let line = match count {
+                Some(line) => std::cmp::min(line.saturating_sub(1), lastLine),
+                None if motion == Motion::FileStart => 0,
+                None => lastLine,
+            };
+            (line, FirstNonBlank(&tab.lines[line]))
+        },
+        Motion::FindChar { target, forward, till } => {
+            let line = tab.lines[start.0].as_bytes();
+            let column = if forward {
+                (start.1 + 1..line.len()).filter(|column| line[*column] == target).nth(repeat - 1)?
+            } else {
+                (0..start.1).rev().filter(|column| line[*column] == target).nth(repeat - 1)?
+            };
+            match (till, forward) {
+                (true, true) => (start.0, column - 1),
+                (true, false) => (start.0, column + 1),
+                _ => (start.0, column),
+            }
+        },
+        Motion::RepeatFind { reverse } => {
+            let Some(Motion::FindChar { target, forward, till }) = lastFind else {  return None;  };
+            let find = Motion::FindChar {  target, forward: forward != reverse, till  };
+            return MotionTarget(tab, find, count, None);
+        },
+        Motion::MatchPair => tab.FindMatchingBracket(start)?,
+    };
+    tab.cursor = start;
+    Some(target)
+}
+
+// the bracket that opens the pair the position is sitting in
+fn FindEnclosingOpen (lines: &[String], position: (usize, usize), open: u8, close: u8) -> Option <(usize, usize)> {
+    let at = |(line, column): (usize, usize)| lines[line].as_bytes().get(column).copied();
+    if at(position) == Some(open) {  return Some(position);  }
+
+    // a closing bracket under the cursor is part of the pair being looked for
+    let mut depth = 0usize;
+    let mut current = position;
+    loop {
+        let column = std::cmp::min(current.1, lines[current.0].len());
+        if column > 0 {
+            current.1 = column - 1;
+        } else {
+            if current.0 == 0 {  return None;  }
+            current.0 -= 1;
+            current.1 = lines[current.0].len();
+            continue;
+        }
+
+        let chr = at(current);
+        if chr == Some(close) {
+            depth += 1;
+        } else if chr == Some(open) {
+            if depth == 0 {  return Some(current);  }
+            depth -= 1;
+        }
+    }
+}
+
+fn ObjectRange (tab: &CodeTab, object: TextObject) -> Option <Range> {
+    let cursor = tab.cursor;
+    match object {
+        TextObject::Word => {
+            let (start, end) = tab.TokenRange(cursor)?;
+            Some(Range {  start: (cursor.0, start), end: (cursor.0, end), linewise: false  })
+        },
+        TextObject::Pair(open, close) => {
+            let opening = FindEnclosingOpen(&tab.lines, cursor, open, close)?;
+            let closing = tab.FindMatchingBracket(opening)?;
+            // a block split over lines only takes the lines inside it
+            let openLine = &tab.lines[opening.0];
+            if closing.0 > opening.0 && opening.1 + 1 == openLine.len() &&
+                tab.lines[closing.0][..closing.1].trim().is_empty()
+            {
+                if closing.0 == opening.0 + 1 {  return None;  }
+                return Some(Range {
+                    start: (opening.0 + 1, 0),
+                    end: (closing.0 - 1, 0),
+                    linewise: true,
+                });
+            }
+            Some(Range {  start: (opening.0, opening.1 + 1), end: closing, linewise: false  })
+        },
+        TextObject::Paragraph => {
+            let blank = |line: usize| tab.lines[line].trim().is_empty();
+            let kind = blank(cursor.0);
+            let mut start = cursor.0;
+            while start > 0 && blank(start - 1) == kind {  start -= 1;  }
+            let mut end = cursor.0;
+            while end + 1 < tab.lines.len() && blank(end + 1) == kind {  end += 1;  }
+            Some(Range {  start: (start, 0), end: (end, 0), linewise: true  })
+        },
+    }
+}
+
+fn RangeText (tab: &CodeTab, range: Range) -> String {
+    if range.linewise {
+        let mut text = tab.lines[range.start.0..=range.end.0].join("\n");
+        text.push('\n');
+        return text;
+    }
+
+    if range.start.0 == range.end.0 {
+        return tab.lines[range.start.0][range.start.1..range.end.1].to_string();
+    }
+    let mut text = tab.lines[range.start.0][range.start.1..].to_string();
+    for line in &tab.lines[range.start.0 + 1..range.end.0] {
+        text.push('\n');
+        text.push_str(line);
+    }
+    text.push('\n');
+    text.push_str(&tab.lines[range.end.0][..range.end.1]);
+    text
+}
+
+// moves the cursor with the view following it
+fn SetCursor (tab: &mut CodeTab, position: (usize, usize)) {
+    tab.scrolled = std::cmp::max(
+        tab.mouseScrolledFlt as isize + tab.scrolled as isize,
+        0
+    ) as usize;
+    tab.mouseScrolled = 0;
+    tab.mouseScrolledFlt = 0.0;
+    tab.cursor = position;
+}
+
+impl <'a> MainApp <'a> {
+    pub(crate) fn ToggleVim (&mut self) {
+        if self.vim.take().is_none() {
+            self.vim = Some(VimState::default());
+        }
+    }
+
+    // returns true when vim handled the keys (otherwise they go through the keymap)
+    pub(crate) async fn HandleVimKeyEvents<'b> (&mut self,
+                                               keyEvents: &KeyParser,
+                                               clipBoard: &mut Clipboard,
+                                               rustAnalyzer: RustAnalyzerLsp<'b>
+    ) -> bool {
+        let Some(vim) = &mut self.vim else {  return false;  };
+        if vim.mode == VimMode::Insert {
+            if keyEvents.ContainsKeyCode(KeyCode::Escape) {
+                self.LeaveInsert();
+                return true;
+            }
+            let frame = keyEvents.Snapshot();
+            if let Some(change) = &mut vim.recording && !frame.IsEmpty() {
+                change.frames.push(frame);
+            }
+            return false;
+        }
+
+        let keys = VimKey::FromEvents(keyEvents);
+        if keys.is_empty() {  return false;  }
+        self.RunVimKeys(keys, clipBoard, rustAnalyzer).await;
+        true
+    }
+
+    async fn RunVimKeys<'b> (&mut self, keys: Vec <VimKey>, clipBoard: &mut Clipboard, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        for key in keys {
+            let Some(vim) = &mut self.vim else {  return;  };
+            if vim.mode == VimMode::Insert {  break;  }
+            if key == VimKey::Code(KeyCode::Escape) {
+                vim.pending.clear();
+                if vim.mode.IsVisual() {  self.LeaveVisual();  }
+                continue;
+            }
+            // q by itself stops the macro being recorded
+            if key == VimKey::Char('q') && vim.pending.is_empty() && self.macros.IsRecording() {
+                self.ToggleMacroRecording(Macros::DEFAULT_MACRO_REGISTER);
+                continue;
+            }
+
+            vim.pending.push(key);
+            match ParseCommand(&vim.pending, vim.mode) {
+                Parsed::Pending => {},
+                Parsed::Invalid => {
+                    vim.pending.clear();
+                    self.macros.failed = true;
+                },
+                Parsed::Done(command) => {
+                    let keys = std::mem::take(&mut vim.pending);
+                    self.RunVimCommand(command, keys, clipBoard, rustAnalyzer).await;
+                },
+            }
+        }
+    }
+
+    async fn RunVimCommand<'b> (&mut self,
+                               parsed: ParsedCommand,
+                               keys: Vec <VimKey>,
+                               clipBoard: &mut Clipboard,
+                               rustAnalyzer: RustAnalyzerLsp<'b>
+    ) {
+        let ParsedCommand {  register, count, command  } = parsed;
+        let undoDepth = self.codeTabs.tabs[self.lastTab].changeBuffer.len();
+        if let Some(vim) = &mut self.vim && command.IsChange() && !vim.repeating {
+            vim.recording = Some(Change {  keys, frames: vec![]  });
+        }
+
+        match command {
+            Command::Move(motion) => self.VimMove(motion, count),
+            Command::Operate(operator, target) => {
+                match self.TargetRange(target, count) {
+                    Some(range) => self.VimOperate(operator, range, register, count, clipBoard, rustAnalyzer).await,
+                    None => self.macros.failed = true,
+                }
+            },
+            Command::SelectObject(object) => self.SelectObject(object),
+            Command::Insert(at) => self.VimInsert(at, rustAnalyzer).await,
+            Command::Visual(mode) => self.EnterVisual(mode),
+            Command::SwapEnds => {
+                if let Some(vim) = &mut self.vim {
+                    (vim.visualAnchor, vim.visualCursor) = (vim.visualCursor, vim.visualAnchor);
+                }
+                self.ApplyVisual();
+            },
+            Command::Put { before } => self.VimPut(before, register, count, clipBoard, rustAnalyzer).await,
+            Command::DeleteChar { before } => {
+                let tab = &self.codeTabs.tabs[self.lastTab];
+                let (line, column) = tab.cursor;
+                let repeat = count.unwrap_or(1);
+                let range =
+                    if before {  ((line, column.saturating_sub(repeat)), (line, column))  }
+                    else {  ((line, column), (line, std::cmp::min(column + repeat, tab.lines[line].len())))  };
+                let range = Range {  start: range.0, end: range.1, linewise: false  };
+                if range.start == range.end {  return;  }
+                self.VimOperate(Operator::Delete, range, register, None, clipBoard, rustAnalyzer).await;
+            },
+            Command::Undo | Command::Redo => {
+                for _ in 0..count.unwrap_or(1) {
+                    let tab = &mut self.codeTabs.tabs[self.lastTab];
+                    if command == Command::Undo {
+                        tab.Undo(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+                    } else {
+                        tab.Redo(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+                    }
+                }
+            },
+            Command::Repeat => {
+                let Some(change) = self.vim.as_ref().and_then(|vim| vim.lastChange.clone()) else {  return;  };
+                if let Some(vim) = &mut self.vim {  vim.repeating = true;  }
+                for _ in 0..count.unwrap_or(1) {
+                    Box::pin(self.RunVimKeys(change.keys.clone(), clipBoard, rustAnalyzer)).await;
+                    if self.vim.as_ref().is_some_and(|vim| vim.mode == VimMode::Insert) {
+                        for frame in &change.frames {
+                            let keyEvents = KeyParser::FromFrame(frame);
+                            Box::pin(self.HandleCodeKeyEvents(&keyEvents, clipBoard, rustAnalyzer)).await;
+                        }
+                        self.LeaveInsert();
+                    }
+                }
+                if let Some(vim) = &mut self.vim {  vim.repeating = false;  }
+            },
+            Command::RecordMacro(register) => self.ToggleMacroRecording(register),
+            Command::PlayMacro(register) => self.macros.Queue(register, count.unwrap_or(1)),
+            Command::OpenPrompt => {
+                if let Some(vim) = &mut self.vim && vim.mode.IsVisual() {
+                    self.LeaveVisual();
+                }
+                self.appState = AppState::CommandPrompt;
+            },
+        }
+
+        let Some(vim) = &mut self.vim else {  return;  };
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        if vim.mode == VimMode::Insert {
+            // the change and the typing that follows it get undone together
+            vim.insertUndoDepth = undoDepth;
+            return;
+        }
+
+        tab.MergeChanges(undoDepth);
+        if let Some(change) = vim.recording.take() {
+            vim.lastChange = Some(change);
+        }
+        if vim.mode == VimMode::Normal {
+            let length = tab.lines[tab.cursor.0].len();
+            tab.cursor.1 = std::cmp::min(tab.cursor.1, length.saturating_sub(1));
+        }
+    }
+
+    fn LeaveInsert (&mut self) {
+        let Some(vim) = &mut self.vim else {  return;  };
+        vim.mode = VimMode::Normal;
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        tab.highlighting = false;
+        tab.MergeChanges(vim.insertUndoDepth);
+        tab.cursor.1 = std::cmp::min(tab.cursor.1, tab.lines[tab.cursor.0].len()).saturating_sub(1);
+        if let Some(change) = vim.recording.take() {
+            vim.lastChange = Some(change);
+        }
+    }
+
+    fn VimMove (&mut self, motion: Motion, count: Option <usize>) {
+        let Some(vim) = &mut self.vim else {  return;  };
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        if vim.mode.IsVisual() {  tab.cursor = vim.visualCursor;  }
+        if matches!(motion, Motion::FindChar { .. }) {  vim.lastFind = Some(motion);  }
+
+        let Some(target) = MotionTarget(tab, motion, count, vim.lastFind) else {
+            self.macros.failed = true;
+            return;
+        };
+        if vim.mode.IsVisual() {
+            vim.visualCursor = target;
+            self.ApplyVisual();
+        } else {
+            tab.highlighting = false;
+            SetCursor(tab, target);
+        }
+    }
+
+    fn TargetRange (&mut self, target: Target, count: Option <usize>) -> Option <Range> {
+        let vim = self.vim.as_mut()?;
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let cursor = tab.cursor;
+        match target {
+            Target::Lines => {
+                let end = std::cmp::min(cursor.0 + count.unwrap_or(1) - 1, tab.lines.len() - 1);
+                Some(Range {  start: (cursor.0, 0), end: (end, 0), linewise: true  })
+            },
+            Target::Object(object) => ObjectRange(tab, object),
+            Target::Selection => {
+                let (start, end) = Ordered(vim.visualAnchor, vim.visualCursor);
+                let linewise = vim.mode == VimMode::VisualLine;
+                let end = (end.0, std::cmp::min(end.1 + 1, tab.lines[end.0].len()));
+                self.LeaveVisual();
+                if linewise {
+                    return Some(Range {  start: (start.0, 0), end: (end.0, 0), linewise  });
+                }
+                Some(Range {  start, end, linewise  })
+            },
+            Target::Motion(motion) => {
+                if matches!(motion, Motion::FindChar { .. }) {  vim.lastFind = Some(motion);  }
+                let mut target = MotionTarget(tab, motion, count, vim.lastFind)?;
+                // dw on the last word of a line stops at the line's end instead of eating the break
+                if motion == Motion::WordStart && target.0 > cursor.0 {
+                    target = (cursor.0, tab.lines[cursor.0].len());
+                }
+
+                let (start, mut end) = Ordered(cursor, target);
+                if motion.IsLinewise() {
+                    return Some(Range {  start: (start.0, 0), end: (end.0, 0), linewise: true  });
+                }
+                if motion.IsInclusive() {
+                    end.1 = std::cmp::min(end.1 + 1, tab.lines[end.0].len());
+                }
+                Some(Range {  start, end, linewise: false  })
+            },
+        }
+    }
+
+    async fn VimOperate<'b> (&mut self,
+                            operator: Operator,
+                            range: Range,
+                            register: Option <char>,
+                            count: Option <usize>,
+                            clipBoard: &mut Clipboard,
+                            rustAnalyzer: RustAnalyzerLsp<'b>
+    ) {
+        let Some(vim) = &mut self.vim else {  return;  };
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let scripts = &self.luaSyntaxHighlightScripts;
+        tab.highlighting = false;
+
+        if matches!(operator, Operator::Indent | Operator::Unindent) {
+            // a count on a visual selection indents it that many times
+            let times = if range.linewise || count.is_none() {  1  } else {  count.unwrap_or(1)  };
+            for _ in 0..times {
+                tab.IndentLines(range.start.0, range.end.0, operator == Operator::Unindent, scripts, rustAnalyzer).await;
+            }
+            SetCursor(tab, (range.start.0, FirstNonBlank(&tab.lines[range.start.0])));
+            return;
+        }
+
+        let text = RangeText(tab, range);
+        vim.SetRegister(register, Register {  text, linewise: range.linewise  }, operator == Operator::Yank, clipBoard);
+        if operator == Operator::Yank {
+            SetCursor(tab, if range.linewise {  (range.start.0, tab.cursor.1)  } else {  range.start  });
+            return;
+        }
+
+        // working out what to select so the deletion goes through the usual highlight path
+        let (start, end) = if !range.linewise {
+            (range.start, range.end)
+        } else if operator == Operator::Change {
+            (range.start, (range.end.0, tab.lines[range.end.0].len()))
+        } else if range.end.0 + 1 < tab.lines.len() {
+            (range.start, (range.end.0 + 1, 0))
+        } else if range.start.0 > 0 {
+            ((range.start.0 - 1, tab.lines[range.start.0 - 1].len()), (range.end.0, tab.lines[range.end.0].len()))
+        } else {
+            (range.start, (range.end.0, tab.lines[range.end.0].len()))
+        };
+
+        if start != end {
+            tab.cursorEnd = start;
+            SetCursor(tab, end);
+            tab.highlighting = true;
+            tab.DelChars(0, 0, scripts, rustAnalyzer).await;
+            tab.highlighting = false;
+        }
+        SetCursor(tab, start);
+        if operator == Operator::Change {
+            vim.mode = VimMode::Insert;
+        } else if range.linewise {
+            let line = std::cmp::min(range.start.0, tab.lines.len() - 1);
+            SetCursor(tab, (line, FirstNonBlank(&tab.lines[line])));
+        }
+    }
+
+    async fn VimInsert<'b> (&mut self, at: InsertAt, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let Some(vim) = &mut self.vim else {  return;  };
+        if vim.mode.IsVisual() {
+            let cursor = vim.visualCursor;
+            self.LeaveVisual();
+            self.codeTabs.tabs[self.lastTab].cursor = cursor;
+        }
+        let Some(vim) = &mut self.vim else {  return;  };
+        vim.mode = VimMode::Insert;
+
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let (line, column) = tab.cursor;
+        let length = tab.lines[line].len();
+        let indent = " ".repeat(FirstNonBlank(&tab.lines[line]));
+        match at {
+            InsertAt::Cursor => {},
+            InsertAt::After => tab.cursor.1 = std::cmp::min(column + 1, length),
+            InsertAt::LineStart => tab.cursor.1 = FirstNonBlank(&tab.lines[line]),
+            InsertAt::LineEnd => tab.cursor.1 = length,
+            // the line below is indented like pressing return at the end of the line
+            InsertAt::LineBelow => {
+                tab.cursor.1 = length;
+                tab.LineBreakIndented(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+            },
+            // the new line above keeps the indentation of the one it was opened from
+            InsertAt::LineAbove => {
+                tab.cursor.1 = 0;
+                tab.LineBreakIn(false, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+                tab.cursor = (line, 0);
+                if !indent.is_empty() {
+                    tab.InsertChars(indent, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+                }
+            },
+        }
+        let cursor = tab.cursor;
+        SetCursor(tab, cursor);
+    }
+
+    async fn VimPut<'b> (&mut self,
+                        before: bool,
+                        register: Option <char>,
+                        count: Option <usize>,
+                        clipBoard: &mut Clipboard,
+                        rustAnalyzer: RustAnalyzerLsp<'b>
+    ) {
+        let Some(vim) = &self.vim else {  return;  };
+        let Some(put) = vim.GetRegister(register, clipBoard) else {  return;  };
+        let text = put.text.repeat(count.unwrap_or(1));
+
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        tab.highlighting = false;
+        let (line, column) = tab.cursor;
+        if put.linewise {
+            let body = text.strip_suffix('\n').unwrap_or(&text);
+            if before {
+                tab.cursor = (line, 0);
+                self.PasteText(&format!("{}\n", body), rustAnalyzer).await;
+            } else {
+                tab.cursor = (line, tab.lines[line].len());
+                self.PasteText(&format!("\n{}", body), rustAnalyzer).await;
+            }
+            let tab = &mut self.codeTabs.tabs[self.lastTab];
+            let target = if before {  line  } else {  line + 1  };
+            SetCursor(tab, (target, FirstNonBlank(&tab.lines[target])));
+        } else {
+            if !before {  tab.cursor.1 = std::cmp::min(column + 1, tab.lines[line].len());  }
+            self.PasteText(&text, rustAnalyzer).await;
+            // landing on the last character that was put
+            let tab = &mut self.codeTabs.tabs[self.lastTab];
+            let cursor = (tab.cursor.0, tab.cursor.1.saturating_sub(1));
+            SetCursor(tab, cursor);
+        }
+    }
+
+    fn EnterVisual (&mut self, mode: VimMode) {
+        let Some(vim) = &mut self.vim else {  return;  };
+        if vim.mode == mode {
+            self.LeaveVisual();
+            return;
+        }
+        if !vim.mode.IsVisual() {
+            let cursor = self.codeTabs.tabs[self.lastTab].cursor;
+            vim.visualAnchor = cursor;
+            vim.visualCursor = cursor;
+        }
+        vim.mode = mode;
+        self.ApplyVisual();
+    }
+
+    fn SelectObject (&mut self, object: TextObject) {
+        let Some(range) = ObjectRange(&self.codeTabs.tabs[self.lastTab], object) else {  return;  };
+        let Some(vim) = &mut self.vim else {  return;  };
+        vim.visualAnchor = range.start;
+        if range.linewise {
+            vim.mode = VimMode::VisualLine;
+            vim.visualCursor = range.end;
+        } else {
+            vim.visualCursor = (range.end.0, range.end.1.saturating_sub(1));
+        }
+        self.ApplyVisual();
+    }
+
+    // shows the visual selection through the tab's highlighting (which doesn't include the end character)
+    fn ApplyVisual (&mut self) {
+        let Some(vim) = &self.vim else {  return;  };
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let (anchor, cursor) = (vim.visualAnchor, vim.visualCursor);
+        let lineEnd = |line: usize| tab.lines[line].len();
+        let (start, end) = match (vim.mode, anchor <= cursor) {
+            (VimMode::VisualLine, true) => ((anchor.0, 0), (cursor.0, lineEnd(cursor.0))),
+            (VimMode::VisualLine, false) => ((anchor.0, lineEnd(anchor.0)), (cursor.0, 0)),
+            (_, true) => (anchor, (cursor.0, std::cmp::min(cursor.1 + 1, lineEnd(cursor.0)))),
+            (_, false) => ((anchor.0, std::cmp::min(anchor.1 + 1, lineEnd(anchor.0))), cursor),
+        };
+        tab.cursorEnd = start;
+        SetCursor(tab, end);
+        tab.highlighting = true;
+    }
+
+    fn LeaveVisual (&mut self) {
+        let Some(vim) = &mut self.vim else {  return;  };
+        vim.mode = VimMode::Normal;
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        tab.highlighting = false;
+        let cursor = (vim.visualCursor.0, std::cmp::min(vim.visualCursor.1, tab.lines[vim.visualCursor.0].len()));
+        SetCursor(tab, cursor);
+    }
+}
@@ -0,0 +1,230 @@
+// soft wrapping; a line too long for the pane is drawn over several rows, broken after the last space that
+// fits, and the rows after the first are indented a little past the line's own indentation. The lines
+// themselves are never changed, a row is just a range of its line
+
+use crate::App as MainApp;
+use crate::CodeTab;
+use crate::TermRender::Colored;
+
+// how much further in a continued row starts than its line
+const WRAP_INDENT: usize = 2;
+
+// cuts a drawn line up at the columns each row after the first starts at
+pub(crate) fn SplitRows (tokens: Vec <Colored>, boundaries: &[usize]) -> Vec <Vec <Colored>> {
+    let mut rows = vec![vec![]];
+    let mut column = 0;
+    for mut token in tokens {
+        while let Some(&boundary) = boundaries.get(rows.len() - 1) && column + token.GetSize() > boundary {
+            let (left, right) = token.SplitChars(boundary.saturating_sub(column));
+            column += left.GetSize();
+            if left.GetSize() > 0 {  rows.last_mut().unwrap().push(left);  }
+            rows.push(vec![]);
+            token = right;
+        }
+        column += token.GetSize();
+        rows.last_mut().unwrap().push(token);
+    }
+    rows.resize_with(boundaries.len() + 1, Vec::new);
+    rows
+}
+
+impl CodeTab {
+    // the column the continued rows of a line start at (kept to half the width so deep indents still leave room)
+    pub fn WrapIndent (&self, line: usize) -> usize {
+        let indent = self.DisplayColumn(line, self.lines[line].len() - self.lines[line].trim_start().len());
+        std::cmp::min(indent + WRAP_INDENT, self.wrapWidth / 2)
+    }
+
+    // where each row of a line starts (the first is always 0, and it's the only one when wrapping is off)
+    // it's needed for every row drawn and every line scrolled past, so it's only worked out again after an edit
+    pub fn WrapPoints (&self, line: usize) -> Vec <usize> {
+        if !self.softWrap || self.wrapWidth == 0 || line >= self.lines.len() {  return vec![0];  }
+        if let Some((wrapWidth, tabWidth, starts)) = self.wrapCache.lock().get(&line) &&
+            (*wrapWidth, *tabWidth) == (self.wrapWidth, self.tabWidth)
+        {
+            return starts.clone();
+        }
+        let starts = self.FindWrapPoints(line);
+        self.wrapCache.lock().insert(line, (self.wrapWidth, self.tabWidth, starts.clone()));
+        starts
+    }
+
+    fn FindWrapPoints (&self, line: usize) -> Vec <usize> {
+        let mut starts = vec![0];
+        let text = &self.lines[line];
+        let indentEnd = text.len() - text.trim_start().len();  // the indentation isn't somewhere to break
+        let continuedLimit = self.wrapWidth - self.WrapIndent(line);
+        let (mut start, mut limit) = (0, self.wrapWidth);
+        loop {
+            let rowColumn = self.DisplayColumn(line, start);
+            let (mut column, mut lastSpace, mut end) = (rowColumn, None, None);
+            for (index, chr) in text[start..].char_indices() {
+                let index = start + index;
+                let next = column + if chr == '\t' {  self.tabWidth - column % self.tabWidth  } else {  1  };
+                // a space can hang off the end (into the cursor's column), and a word longer than a row is broken in the middle
+                if next - rowColumn > limit + (chr == ' ') as usize && index > start {
+                    end = Some(lastSpace.unwrap_or(index));
+                    break;
+                }
+                if chr.is_whitespace() && index >= indentEnd {  lastSpace = Some(index + chr.len_utf8());  }
+                column = next;
+            }
+            let Some(end) = end else {  return starts;  };
+            starts.push(end);
+            (start, limit) = (end, continuedLimit);
+        }
+    }
+
+    pub fn RowCount (&self, line: usize) -> usize {
+        self.WrapPoints(line).len()
+    }
+
+    // the row of its line a position is drawn on (a position right at a break goes on the row after it)
+    pub fn RowOf (&self, position: (usize, usize)) -> usize {
+        self.WrapPoints(position.0).iter().rposition(|start| *start <= position.1).unwrap_or(0)
+    }
+
+    // the column a position is drawn at within its row (counting the indent of a continued row)
+    pub fn RowColumn (&self, position: (usize, usize)) -> usize {
+        let starts = self.WrapPoints(position.0);
+        let row = self.RowOf(position);
+        let indent = if row > 0 {  self.WrapIndent(position.0)  } else {  0  };
+        self.DisplayColumn(position.0, position.1) - self.DisplayColumn(position.0, starts[row]) + indent
+    }
+
+    // the position on a row of a line that's drawn at (or closest to) a column
+    pub fn PositionAtRow (&self, line: usize, row: usize, column: usize) -> usize {
+        let starts = self.WrapPoints(line);
+        let row = std::cmp::min(row, starts.len() - 1);
+        let indent = if row > 0 {  self.WrapIndent(line)  } else {  0  };
+        let index = self.IndexAtColumn(line, self.DisplayColumn(line, starts[row]) + column.saturating_sub(indent));
+        match starts.get(row + 1) {
+            // staying before the break, otherwise it'd be drawn on the next row
+            Some(&next) if index >= next => self.lines[line][..next].char_indices().last().map_or(0, |(index, _)| index),
+            _ => index,
+        }
+    }
+
+    // the position a row up or down (for the arrow keys while wrapping, so they move the way the text is drawn)
+    pub fn StepRow (&self, position: (usize, usize), up: bool) -> (usize, usize) {
+        let (row, column) = (self.RowOf(position), self.RowColumn(position));
+        let (line, row) =
+            if up && row > 0 {  (position.0, row - 1)  }
+            else if up {
+                if position.0 == 0 {  return position;  }
+                let line = self.PreviousVisibleLine(position.0);
+                (line, self.RowCount(line) - 1)
+            } else if row + 1 < self.RowCount(position.0) {  (position.0, row + 1)  }
+            else {
+                let line = self.NextVisibleLine(position.0);
+                if line >= self.lines.len() {  return position;  }
+                (line, 0)
+            };
+        (line, self.PositionAtRow(line, row, column))
+    }
+
+    // the number of rows drawn from the start of one line to the start of another
+    pub fn RowsBetween (&self, from: usize, to: usize) -> usize {
+        if !self.softWrap {  return self.VisibleRowsBetween(from, to);  }
+        let (mut line, to) = (self.VisibleLine(from), self.VisibleLine(to));
+        let mut rows = 0;
+        while line < to {
+            rows += self.RowCount(line);
+            line = self.NextVisibleLine(line);
+        } rows
+    }
+
+    // the line (and its row) drawn on a row of the view (the line can be past the end of the file)
+    pub fn RowAt (&self, top: usize, row: usize) -> (usize, usize) {
+        if !self.softWrap {  return (self.LineAtRow(top, row), 0);  }
+        let (mut line, mut remaining) = (self.VisibleLine(top), row);
+        while line < self.lines.len() {
+            let rows = self.RowCount(line);
+            if remaining < rows {  return (line, remaining);  }
+            remaining -= rows;
+            line = self.NextVisibleLine(line);
+        }
+        (line + remaining, 0)
+    }
+
+    // the line to scroll to so a position is drawn some number of rows below the top
+    pub fn TopLineFor (&self, position: (usize, usize), rows: usize) -> usize {
+        let (mut line, mut above) = (position.0, self.RowOf(position));
+        while line > 0 {
+            let previous = self.PreviousVisibleLine(line);
+            let count = self.RowCount(previous);
+            if above + count > rows {  break;  }
+            (line, above) = (previous, above + count);
+        } line
+    }
+
+    // draws one row of a wrapped line, handing back its text and width
+    pub fn WrappedRow (&self, rendered: Vec <Colored>, line: usize, row: usize, editingCode: bool) -> (Vec <Colored>, usize) {
+        let starts = self.WrapPoints(line);
+        // the cursor takes up a column, pushing everything after it over by one
+        let cursorColumn =
+            if editingCode && line == self.cursor.0 {  Some(self.DisplayColumn(line, self.cursor.1))  }
+            else {  None  };
+        let boundaries: Vec <usize> = starts[1..].iter()
+            .map(|start| self.DisplayColumn(line, *start))
+            .map(|column| column + cursorColumn.is_some_and(|cursor| cursor < column) as usize)
+            .collect();
+        let mut text = SplitRows(rendered, &boundaries).swap_remove(std::cmp::min(row, boundaries.len()));
+        if row > 0 {  text.insert(0, Colored::new(" ".repeat(self.WrapIndent(line))));  }
+
+        // anything tacked onto the end (like a suggestion) is cut off at the edge
+        let mut width = text.iter().map(|token| token.GetSize()).sum::<usize>();
+        while width > self.wrapWidth + 1 && let Some(token) = text.pop() {
+            width -= token.GetSize();
+        }
+        (text, width)
+    }
+
+    pub fn ToggleWrap (&mut self) {
+        self.softWrap = !self.softWrap;
+        (self.horizontalScroll, self.horizontalScrollFlt) = (0, 0.0);
+        self.FollowCursorSideways();
+        self.ClearRenderCache();
+    }
+}
+
+impl <'a> MainApp <'a> {
+    pub(crate) fn ToggleWrap (&mut self) {
+        self.codeTabs.tabs[self.lastTab].ToggleWrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LuaScripts;
+    use crate::TokenInfo::{Languages, LanguageScript};
+    use proc_macros::load_lua_script;
+
+    #[test]
+    fn WrapPointsFollowEdits () -> Result <(), mlua::Error> {
+        let scripts: LuaScripts = std::sync::Arc::new(parking_lot::Mutex::new(std::collections::HashMap::new()));
+        load_lua_script!(scripts, Languages::Null, "assets/nullSyntaxHighlighting.lua",);
+        let rustAnalyzer = None;
+        let mut tab = CodeTab {
+            lines: vec![String::from("one two three four")],
+            fileName: "test.txt".into(),
+            softWrap: true,
+            wrapWidth: 10,
+            ..Default::default()
+        };
+        tab.lineTokens.write().push(vec![]);
+        tab.lineTokenFlags.write().push(vec![]);
+        assert_eq!(tab.WrapPoints(0), [0, 8, 14]);
+        futures::executor::block_on(async {
+            // the cached rows have to be redone for the edited line
+            tab.cursor = (0, 3);
+            tab.InsertChars(String::from(" and"), &scripts, &rustAnalyzer).await;
+            assert_eq!(tab.WrapPoints(0), [0, 8, 12, 18]);
+        });
+        // and for a different width
+        tab.wrapWidth = 40;
+        assert_eq!(tab.WrapPoints(0), [0]);
+        Ok(())
+    }
+}
@@ -1,11 +1,12 @@
 use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
 use vte::Perform;
 
 // constants for tracking mouse scrolling
 const SCROLL_SENSITIVITY: f64 = 0.05;
 const SCROLL_LOG_TIME: f64 = 0.75;
 
-#[derive(PartialEq, Eq, Debug, Default)]
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
 pub enum KeyModifiers {
     Shift,
     #[default] Command,
@@ -13,7 +14,7 @@ pub enum KeyModifiers {
     Control,
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum KeyCode {
     Delete,
     Tab,
@@ -23,6 +24,90 @@ pub enum KeyCode {
     Down,
     Return,
     Escape,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    ForwardDelete,
+    Insert,
+    Function (u8),  // F1 through F12
+    BackTab,  // shift + tab (also sends shift as a modifier)
+}
+
+impl KeyCode {
+    // the names used in keymaps and saved macros (the aliases are only read, never written)
+    pub fn FromName (name: &str) -> Option <KeyCode> {
+        Some(match name {
+            "delete" | "backspace" => KeyCode::Delete,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "return" | "enter" => KeyCode::Return,
+            "escape" | "esc" => KeyCode::Escape,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "forward-delete" | "del" => KeyCode::ForwardDelete,
+            "insert" | "ins" => KeyCode::Insert,
+            name => match name.strip_prefix('f').and_then(|number| number.parse::<u8>().ok()) {
+                Some(number @ 1..=12) => KeyCode::Function(number),
+                _ => return None,
+            },
+        })
+    }
+
+    pub fn Name (&self) -> String {
+        match self {
+            KeyCode::Delete => "delete",
+            KeyCode::Tab => "tab",
+            KeyCode::BackTab => "backtab",
+            KeyCode::Left => "left",
+            KeyCode::Right => "right",
+            KeyCode::Up => "up",
+            KeyCode::Down => "down",
+            KeyCode::Return => "return",
+            KeyCode::Escape => "escape",
+            KeyCode::Home => "home",
+            KeyCode::End => "end",
+            KeyCode::PageUp => "pageup",
+            KeyCode::PageDown => "pagedown",
+            KeyCode::ForwardDelete => "forward-delete",
+            KeyCode::Insert => "insert",
+            KeyCode::Function(number) => return format!("f{number}"),
+        }.to_string()
+    }
+}
+
+impl KeyModifiers {
+    pub fn FromName (name: &str) -> Option <KeyModifiers> {
+        match name {
+            "shift" => Some(KeyModifiers::Shift),
+            "cmd" | "command" => Some(KeyModifiers::Command),
+            "alt" | "opt" | "option" => Some(KeyModifiers::Option),
+            "ctrl" | "control" => Some(KeyModifiers::Control),
+            _ => None,
+        }
+    }
+
+    pub fn Name (&self) -> &'static str {
+        match self {
+            KeyModifiers::Shift => "shift",
+            KeyModifiers::Command => "cmd",
+            KeyModifiers::Option => "alt",
+            KeyModifiers::Control => "ctrl",
+        }
+    }
+}
+
+// A single key; either one of the named keys or a character
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Key {
+    Code (KeyCode),
+    Char (char),
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, Default)]
@@ -33,6 +118,8 @@ pub enum MouseEventType {
     Middle,
     Down,
     Up,
+    ScrollLeft,  // shift+scrolling (or a sideways wheel)
+    ScrollRight,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, Default)]
@@ -50,6 +137,32 @@ pub struct MouseEvent {
     pub state: MouseState,
 }
 
+// Replies to the terminal capability queries (these come in through stdin like any other input)
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum TerminalReply {
+    ModeReport (u16, u16),  // DECRPM; (mode, state)
+    Capability (String, bool),  // XTGETTCAP; (name, supported)
+    BackgroundColor (u8, u8, u8),  // OSC 11
+    KeyboardFlags (u16),  // the kitty keyboard protocol's current flags (only sent if it's supported)
+    DeviceAttributes,  // DA1; always answered, so it comes after every other reply
+}
+
+// The keyboard input from a single frame, without any of the parsing state
+// This is what gets recorded and played back (by itself it isn't tied to the terminal)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyFrame {
+    pub keys: Vec <KeyCode>,
+    pub chars: Vec <char>,
+    pub modifiers: Vec <KeyModifiers>,
+    pub pastes: Vec <String>,
+}
+
+impl KeyFrame {
+    pub fn IsEmpty (&self) -> bool {
+        self.keys.is_empty() && self.chars.is_empty() && self.pastes.is_empty()
+    }
+}
+
 #[derive(Default)]
 pub struct KeyParser {
     pub keyModifiers: Vec <KeyModifiers>,
@@ -62,6 +175,12 @@ pub struct KeyParser {
     pub lastPress: u128,
     pub scrollEvents: Vec <(std::time::SystemTime, i8)>,  // the sign is the direction
     pub scrollAccumulate: f64,
+    pub terminalReplies: Vec <TerminalReply>,
+    pub keyReleases: Vec <Key>,  // only the kitty keyboard protocol reports keys being let go of
+    pub pastes: Vec <String>,  // text pasted through the terminal (bracketed paste)
+    pasteBuffer: Option <String>,  // the paste being read in (it can be split across multiple reads)
+    singleShift: bool,  // an ESC O was read, so the next char is an SS3 key (F1-F4, home/end, or arrows)
+    dcsBuffer: Option <(bool, Vec <u8>)>,  // (valid, data) for the XTGETTCAP replies
 }
 
 impl KeyParser {
@@ -76,6 +195,13 @@ impl KeyParser {
                 (KeyCode::Down, false),
                 (KeyCode::Return, false),
                 (KeyCode::Escape, false),
+                (KeyCode::Home, false),
+                (KeyCode::End, false),
+                (KeyCode::PageUp, false),
+                (KeyCode::PageDown, false),
+                (KeyCode::ForwardDelete, false),
+                (KeyCode::Insert, false),
+                (KeyCode::BackTab, false),
             ]),
             keyModifiers: vec!(),
             charEvents: vec!(),
@@ -86,7 +212,38 @@ impl KeyParser {
             lastPress: 0,
             scrollEvents: vec![],
             scrollAccumulate: 0.0,
+            terminalReplies: vec![],
+            keyReleases: vec![],
+            pastes: vec![],
+            pasteBuffer: None,
+            singleShift: false,
+            dcsBuffer: None,
+        }
+    }
+
+    // the key presses from this frame
+    pub fn Snapshot (&self) -> KeyFrame {
+        KeyFrame {
+            keys: self.keyEvents.iter()
+                .filter(|(_, pressed)| **pressed)
+                .map(|(key, _)| *key)
+                .collect(),
+            chars: self.charEvents.clone(),
+            modifiers: self.keyModifiers.clone(),
+            pastes: self.pastes.clone(),
+        }
+    }
+
+    // a parser holding only the given frame's key presses (for playing input back)
+    pub fn FromFrame (frame: &KeyFrame) -> KeyParser {
+        let mut parser = KeyParser::new();
+        for key in &frame.keys {
+            parser.keyEvents.insert(*key, true);
         }
+        parser.charEvents = frame.chars.clone();
+        parser.keyModifiers = frame.modifiers.clone();
+        parser.pastes = frame.pastes.clone();
+        parser
     }
 
     // tracking a log of scroll events to average them out over a duration of time
@@ -120,7 +277,10 @@ impl KeyParser {
         self.keyModifiers.clear();
         self.mouseModifiers.clear();
         self.keyEvents.clear();
+        self.keyReleases.clear();
+        self.pastes.clear();
         self.inEscapeSeq = false;
+        self.singleShift = false;
         self.UpdateScroll();
 
         if let Some(event) = &mut self.mouseEvent {
@@ -128,7 +288,9 @@ impl KeyParser {
                 MouseState::Press => {
                     event.state = MouseState::Hold;
                 },
-                MouseState::Hold if matches!(event.eventType, MouseEventType::Down | MouseEventType::Up) => {
+                MouseState::Hold if matches!(event.eventType,
+                    MouseEventType::Down | MouseEventType::Up | MouseEventType::ScrollLeft | MouseEventType::ScrollRight
+                ) => {
                     event.state = MouseState::Release;
                 },
                 MouseState::Release => {
@@ -142,10 +304,6 @@ impl KeyParser {
         }
     }
 
-    pub fn ContainsChar (&self, chr: char) -> bool {
-        self.charEvents.contains(&chr)
-    }
-
     pub fn ContainsModifier (&self, modifier: &KeyModifiers) -> bool {
         self.keyModifiers.contains(modifier)
     }
@@ -177,14 +335,16 @@ impl KeyParser {
             //println!("Code: {:?} / {}", numbers, c);
 
             let isScroll = (byte & 64) != 0;
+            // holding shift turns the wheel sideways (buttons 2 and 3 are a sideways wheel)
+            let sideways = byte & 4 != 0 || button >= 2;
             let eventType = match (isScroll, button) {
-                (true, 0) => {
+                (true, 0 | 2) => {
                     self.Scroll(-1i8);
-                    MouseEventType::Up
+                    if sideways {  MouseEventType::ScrollLeft  } else {  MouseEventType::Up  }
                 },
-                (true, 1) => {
+                (true, 1 | 3) => {
                     self.Scroll(1i8);
-                    MouseEventType::Down
+                    if sideways {  MouseEventType::ScrollRight  } else {  MouseEventType::Down  }
                 },
                 (false, 0) => MouseEventType::Left,
                 (false, 1) => MouseEventType::Middle,
@@ -192,7 +352,7 @@ impl KeyParser {
                 _ => MouseEventType::Null
             };
 
-            if matches!(eventType, MouseEventType::Left) && numbers[0] == 4 {
+            if matches!(eventType, MouseEventType::Left) && byte & 4 != 0 {  // shift (also held through a drag)
                 self.mouseModifiers.push(KeyModifiers::Shift);
             }
 
@@ -327,82 +487,127 @@ impl KeyParser {
         }
     }
 
-    fn HandleControlArrows (&mut self, _numbers: &[u16], c: char) {
+    // DECRPM (CSI ? mode ; state $ y), the kitty keyboard flags (CSI ? flags u), and DA1 (CSI ? ... c)
+    fn HandleTerminalReply (&mut self, numbers: &[u16], intermediates: &[u8], c: char) {
+        if c == 'y' && intermediates.contains(&b'$') && numbers.len() == 2 {
+            self.terminalReplies.push(TerminalReply::ModeReport(numbers[0], numbers[1]));
+        } else if c == 'u' {
+            self.terminalReplies.push(TerminalReply::KeyboardFlags(numbers.first().copied().unwrap_or(0)));
+        } else if c == 'c' {
+            self.terminalReplies.push(TerminalReply::DeviceAttributes);
+        }
+    }
+
+    // the XTGETTCAP reply is the hex encoded name, optionally followed by '=' and the hex encoded value
+    fn HandleCapabilityReply (&mut self, valid: bool, data: &[u8]) {
+        let text = String::from_utf8_lossy(data);
+        let hexName = text.split('=').next().unwrap_or_default();
+        let name: String = hexName.as_bytes()
+            .chunks(2)
+            .filter_map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+            .map(|byte| byte as char)
+            .collect();
+        self.terminalReplies.push(TerminalReply::Capability(name, valid));
+    }
+
+    // the OSC 11 reply is rgb:RRRR/GGGG/BBBB (each channel can have 1 to 4 hex digits)
+    fn HandleBackgroundReply (&mut self, color: &[u8]) {
+        let text = String::from_utf8_lossy(color);
+        let Some(channels) = text.strip_prefix("rgb:") else {  return;  };
+        let channels: Vec <u8> = channels.split('/').filter_map(|channel| {
+            // only the top 4 digits matter for a byte (and more would overflow)
+            let channel: String = channel.chars().take(4).collect();
+            let value = u32::from_str_radix(&channel, 16).ok()?;
+            let maximum = 16u32.checked_pow(channel.len() as u32)? - 1;
+            Some((value * 255 / maximum) as u8)
+        }).collect();
+        if let [r, g, b] = channels[..] {
+            self.terminalReplies.push(TerminalReply::BackgroundColor(r, g, b));
+        }
+    }
+
+    // The modifier parameter is 1 + a bit field (shift 1, alt 2, ctrl 4, super 8)
+    // This is the same for the standard xterm codes, modifyOtherKeys, and the kitty protocol
+    fn PushModifiers (&mut self, modifiers: u16) {
+        let bits = modifiers.saturating_sub(1);
+        if bits & 1 != 0 {  self.keyModifiers.push(KeyModifiers::Shift);  }
+        if bits & 2 != 0 {  self.keyModifiers.push(KeyModifiers::Option);  }
+        if bits & 4 != 0 {  self.keyModifiers.push(KeyModifiers::Control);  }
+        if bits & 8 != 0 {  self.keyModifiers.push(KeyModifiers::Command);  }
+    }
+
+    // The key numbers used by CSI key ; mods u (kitty) and CSI 27 ; mods ; key ~ (modifyOtherKeys)
+    fn KeyFromNumber (number: u16) -> Option <Key> {
+        match number {
+            9 => Some(Key::Code(KeyCode::Tab)),
+            13 => Some(Key::Code(KeyCode::Return)),
+            27 => Some(Key::Code(KeyCode::Escape)),
+            8 | 127 => Some(Key::Code(KeyCode::Delete)),
+            57344.. => None,  // kitty's private use keys (the keypad, media keys, and lone modifiers)
+            _ => char::from_u32(number as u32).map(Key::Char),
+        }
+    }
+
+    // the keys sent as CSI 1 ; mods <final> (and SS3 <final>)
+    fn KeyFromFinal (c: char) -> Option <Key> {
         match c {
-            'D' => {
-                self.keyModifiers.push(KeyModifiers::Control);
-                self.keyEvents.insert(KeyCode::Left, true);
-            },
-            'C' => {
-                self.keyModifiers.push(KeyModifiers::Control);
-                self.keyEvents.insert(KeyCode::Right, true);
-            },
-            'A' => {
-                self.keyModifiers.push(KeyModifiers::Control);
-                self.keyEvents.insert(KeyCode::Up, true);
-            },
-            'B' => {
-                self.keyModifiers.push(KeyModifiers::Control);
-                self.keyEvents.insert(KeyCode::Down, true);
-            },
-            _ => {}  // control + arrows
+            'A' => Some(Key::Code(KeyCode::Up)),
+            'B' => Some(Key::Code(KeyCode::Down)),
+            'C' => Some(Key::Code(KeyCode::Right)),
+            'D' => Some(Key::Code(KeyCode::Left)),
+            'H' => Some(Key::Code(KeyCode::Home)),
+            'F' => Some(Key::Code(KeyCode::End)),
+            'P' => Some(Key::Code(KeyCode::Function(1))),
+            'Q' => Some(Key::Code(KeyCode::Function(2))),
+            'R' => Some(Key::Code(KeyCode::Function(3))),
+            'S' => Some(Key::Code(KeyCode::Function(4))),
+            'Z' => Some(Key::Code(KeyCode::BackTab)),
+            _ => None,
         }
     }
 
-    fn HandleStandardEscapeCodes (&mut self, numbers: &Vec <u16>, c: char) {
-        match c as u8 {
-            0x5A => {
-                self.keyEvents.insert(KeyCode::Tab, true);
-                self.keyModifiers.push(KeyModifiers::Shift);
-            },
-            0x44 => {
-                self.keyEvents.insert(KeyCode::Left, true);
-                if *numbers == [1, 3] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                } else if *numbers == [1, 2] {
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                } else if *numbers == [1, 4] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                }
-            },
-            0x43 => {
-                self.keyEvents.insert(KeyCode::Right, true);
-                if *numbers == [1, 3] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                } else if *numbers == [1, 2] {
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                } else if *numbers == [1, 4] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                }
-            },
-            0x41 => {
-                self.keyEvents.insert(KeyCode::Up, true);
-                if *numbers == [1, 3] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                } else if *numbers == [1, 2] {
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                } else if *numbers == [1, 4] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                }
-            },
-            0x42 => {
-                self.keyEvents.insert(KeyCode::Down, true);
-                if *numbers == [1, 3] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                } else if *numbers == [1, 2] {
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                } else if *numbers == [1, 4] {
-                    self.keyModifiers.push(KeyModifiers::Option);
-                    self.keyModifiers.push(KeyModifiers::Shift);
-                }
-            },
-            _ => {},
+    // the keys sent as CSI number ; mods ~ (vt220 style)
+    // 1/7 and 4/8 are home and end depending on the terminal; the f-key numbers skip 16 and 22
+    fn KeyFromTilde (number: u16) -> Option <Key> {
+        match number {
+            1 | 7 => Some(Key::Code(KeyCode::Home)),
+            4 | 8 => Some(Key::Code(KeyCode::End)),
+            2 => Some(Key::Code(KeyCode::Insert)),
+            3 => Some(Key::Code(KeyCode::ForwardDelete)),
+            5 => Some(Key::Code(KeyCode::PageUp)),
+            6 => Some(Key::Code(KeyCode::PageDown)),
+            11..=15 => Some(Key::Code(KeyCode::Function((number - 10) as u8))),
+            17..=21 => Some(Key::Code(KeyCode::Function((number - 11) as u8))),
+            23 | 24 => Some(Key::Code(KeyCode::Function((number - 12) as u8))),
+            _ => None,
         }
     }
 
+    // A key with its modifiers from the kitty protocol or modifyOtherKeys
+    fn HandleModifiedKey (&mut self, number: u16, modifiers: u16) {
+        let Some(key) = KeyParser::KeyFromNumber(number) else {  return;  };
+        self.PushModifiers(modifiers);
+        match key {
+            Key::Code(code) => {  self.keyEvents.insert(code, true);  },
+            // shift on its own is just typing a capital (the key number is always the unshifted key)
+            Key::Char(chr) if modifiers == 2 => self.charEvents.push(chr.to_ascii_uppercase()),
+            Key::Char(chr) => self.charEvents.push(chr),
+        }
+    }
+
+    fn HandleStandardEscapeCodes (&mut self, numbers: &[u16], c: char) {
+        let key = match c {
+            '~' => KeyParser::KeyFromTilde(numbers.first().copied().unwrap_or_default()),
+            _ => KeyParser::KeyFromFinal(c),
+        };
+        let Some(Key::Code(code)) = key else {  return;  };
+        self.keyEvents.insert(code, true);
+        if code == KeyCode::BackTab {
+            self.keyModifiers.push(KeyModifiers::Shift);
+        } else {
+            self.PushModifiers(numbers.get(1).copied().unwrap_or(1));
+        }
+    }
 }
 
 pub async fn enableMouseCapture() {
@@ -430,6 +635,16 @@ impl KeyParser {
 impl Perform for KeyParser {
     fn print(&mut self, chr: char) {
         //println!("char {}: '{}'", chr as u8, chr);
+        if let Some(paste) = &mut self.pasteBuffer {
+            paste.push(chr);
+            return;
+        }
+        if self.singleShift {
+            self.singleShift = false;
+            self.SetPressTime();
+            self.HandleStandardEscapeCodes(&[], chr);
+            return;
+        }
         if self.inEscapeSeq || self.bytes > 1 {
             match chr as u8 {
                 17 => {
@@ -454,6 +669,14 @@ impl Perform for KeyParser {
 
     #[inline(always)]
     fn execute(&mut self, byte: u8) {
+        // line breaks and tabs inside a paste are just text
+        if let Some(paste) = &mut self.pasteBuffer {
+            match byte {
+                b'\r' | b'\n' | b'\t' => paste.push(byte as char),
+                _ => {},
+            }
+            return;
+        }
         self.SetPressTime();
 
         // control + ...
@@ -517,18 +740,82 @@ impl Perform for KeyParser {
                 self.charEvents.push('a');
                 self.keyModifiers.push(KeyModifiers::Control);
             },
+            // ctrl + / comes through as the unit separator
+            0x1F => {
+                self.keyModifiers.push(KeyModifiers::Control);
+                self.charEvents.push('/');
+            },
+            // any other control + letter (so the keymap can bind them)
+            _ if (1..=26).contains(&byte) => {
+                self.keyModifiers.push(KeyModifiers::Control);
+                self.charEvents.push((b'a' + byte - 1) as char);
+            },
             _ => {},
         }
         //println!("byte {}: '{}'", byte, byte as char);
     }
 
+    fn hook(&mut self, params: &vte::Params, intermediates: &[u8], _: bool, c: char) {
+        // DCS 1 + r ... ST (or 0 for unknown capabilities)
+        if c == 'r' && intermediates == b"+" {
+            let valid = params.iter().next().map(|p| p[0]) == Some(1);
+            self.dcsBuffer = Some((valid, vec![]));
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        if let Some((_, data)) = &mut self.dcsBuffer {
+            data.push(byte);
+        }
+    }
+
+    fn unhook(&mut self) {
+        self.inEscapeSeq = false;
+        if let Some((valid, data)) = self.dcsBuffer.take() {
+            self.HandleCapabilityReply(valid, &data);
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _: bool, byte: u8) {
+        self.inEscapeSeq = false;
+        // SS3; the key itself comes through as the next printed char
+        if byte == b'O' && intermediates.is_empty() {
+            self.singleShift = true;
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _: bool) {
+        self.inEscapeSeq = false;
+        if let [b"11", color] = params {
+            self.HandleBackgroundReply(color);
+        }
+    }
+
     #[inline(always)]
-    fn csi_dispatch(&mut self, params: &vte::Params, _: &[u8], _: bool, c: char) {
+    fn csi_dispatch(&mut self, params: &vte::Params, intermediates: &[u8], _: bool, c: char) {
         self.inEscapeSeq = false;  // resetting the escape sequence
-        self.SetPressTime();
 
         let numbers: Vec <u16> = params.iter().map(|p| p[0]).collect();
 
+        // replies to the capability queries aren't key presses
+        if intermediates.contains(&b'?') && matches!(c, 'y' | 'c' | 'u') {
+            self.HandleTerminalReply(&numbers, intermediates, c);
+            return;
+        }
+
+        // the kitty protocol puts the event type after the modifiers (1 = press, 2 = repeat, 3 = release)
+        let eventType = params.iter().nth(1).and_then(|p| p.get(1).copied()).unwrap_or(1);
+        if eventType == 3 {
+            let key = match c {
+                'u' => KeyParser::KeyFromNumber(numbers[0]),
+                '~' => KeyParser::KeyFromTilde(numbers[0]),
+                _ => KeyParser::KeyFromFinal(c),
+            };
+            self.keyReleases.extend(key);
+            return;
+        }
+        self.SetPressTime();
+
         // mouse handling
         if c == 'M' || c == 'm' {
             self.HandleMouseEscapeCodes(&numbers, c);
@@ -536,19 +823,98 @@ impl Perform for KeyParser {
         }
 
         //for number in &numbers {println!("{}", number);}
-        if c == '~' && numbers.len() == 2 && numbers[0] == 3 {  // this section is for custom escape codes
+        if c == '~' && numbers == [200] {  // the start of a bracketed paste
+            self.pasteBuffer = Some(String::new());
+        } else if c == '~' && numbers == [201] {  // and the end
+            // terminals tend to send line breaks as \r, but crlf and lf both show up too
+            let paste = self.pasteBuffer.take().unwrap_or_default().replace("\r\n", "\n").replace('\r', "\n");
+            self.pastes.push(paste);
+        } else if c == 'u' && intermediates.is_empty() {  // kitty keyboard protocol
+            self.HandleModifiedKey(numbers[0], numbers.get(1).copied().unwrap_or(1));
+        } else if c == '~' && numbers.len() == 3 && numbers[0] == 27 {  // modifyOtherKeys
+            self.HandleModifiedKey(numbers[2], numbers[1]);
+        } else if c == '~' && numbers.len() == 2 && numbers[0] == 3 {  // this section is for custom escape codes
+            // (these shadow xterm's modified forward delete, so only a plain forward delete gets through)
             self.HandleCustomEscapeCodes(&numbers);
-        } else if numbers.len() == 2 && numbers[0] == 1 && numbers[1] == 5 {
-            // control + ...
-            // 3 = c; 22 = v; 26 = z; 6 = f; 1 = a; 24 = x; 19 = s; 21 = u; r = 18
-            // left ^[[1;5D right ^[[1;5C up ^[[1;5A down ^[[1;5B
-            // control u and control r and necessary for undo and redo bc/
-            // control + key and control + shift + key don't send unique
-            // escape codes for some odd reason
-            self.HandleControlArrows(&numbers, c);
         } else {  // this checks existing escape codes of 1 parameter/ending code (they don't end with ~)
             self.HandleStandardEscapeCodes(&numbers, c);
         }
     }
 }
 
+// Pasted text gets wrapped in CSI 200 ~ and CSI 201 ~ rather than coming in as typed keys
+pub fn enableBracketedPaste() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x1B[?2004h");
+    let _ = stdout.flush();
+}
+
+pub fn disableBracketedPaste() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x1B[?2004l");
+    let _ = stdout.flush();
+}
+
+// which keyboard modes are switched on, so whatever's on can be put back however the editor exits
+static KITTY_KEYBOARD_ON: AtomicBool = AtomicBool::new(false);
+static MODIFY_OTHER_KEYS_ON: AtomicBool = AtomicBool::new(false);
+
+// Asks for the kitty keyboard protocol: disambiguate escape codes (1) and report event types (2)
+// The flags get pushed onto a stack in the terminal, and popped again on exit
+pub fn enableKittyKeyboard() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x1B[>3u");
+    let _ = stdout.flush();
+    KITTY_KEYBOARD_ON.store(true, Ordering::SeqCst);
+}
+
+pub fn disableKittyKeyboard() {
+    if !KITTY_KEYBOARD_ON.swap(false, Ordering::SeqCst) {  return;  }
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x1B[<u");
+    let _ = stdout.flush();
+}
+
+// The xterm fallback; modified keys get sent as CSI 27 ; mods ; key ~
+pub fn enableModifyOtherKeys() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x1B[>4;2m");
+    let _ = stdout.flush();
+    MODIFY_OTHER_KEYS_ON.store(true, Ordering::SeqCst);
+}
+
+pub fn disableModifyOtherKeys() {
+    if !MODIFY_OTHER_KEYS_ON.swap(false, Ordering::SeqCst) {  return;  }
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x1B[>4m");
+    let _ = stdout.flush();
+}
+
+// Turns off whichever keyboard mode is on; held by the app's task so it runs when the task finishes,
+// panics, or gets dropped by the runtime's shutdown (otherwise the shell would get the extended codes)
+pub struct KeyboardModesGuard;
+
+impl Drop for KeyboardModesGuard {
+    fn drop (&mut self) {
+        disableKittyKeyboard();
+        disableModifyOtherKeys();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ReadsBackgroundColors () {
+        let mut parser = KeyParser::new();
+        parser.HandleBackgroundReply(b"rgb:ffff/8080/0");
+        // channels longer than 4 digits are cut down to their top 4
+        parser.HandleBackgroundReply(b"rgb:fffffff/0000000/8000000");
+        parser.HandleBackgroundReply(b"rgb:ff/ff");
+        assert_eq!(parser.terminalReplies, [
+            TerminalReply::BackgroundColor(255, 128, 0),
+            TerminalReply::BackgroundColor(255, 0, 127),
+        ]);
+    }
+}
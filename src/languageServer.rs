@@ -1,6 +1,7 @@
 use std::io::{BufRead, BufReader, Read, Write};
 use std::process::{Command, Stdio};
 use crossterm::tty::IsTty;
+use crate::Snippets::Snippet;
 
 /// the number of checks before a complete refresh is called to
 /// ensure proper synchronization of files (incase the differed
@@ -29,8 +30,9 @@ pub enum RustEvents {
     /// requests updates for the entire code file to ensure all changes are
     /// properly synchronized.
     Synchronize,
-    /// the line, char_index to suggest completion options for
-    Completion (usize, usize),
+    /// the line, char_index (in utf-16 units) to suggest completion options for, and the
+    /// file's current text (the lsp only knows the saved one otherwise)
+    Completion (usize, usize, String),
     /// goes to the definition based on the line, char_index of the event/action
     GotoDefinition (usize, usize),
     OpenedFile (String),
@@ -42,6 +44,8 @@ pub enum RustResponse {
     /// contains the types along with the range to update with them
     /// file, line start, line end, all types (this may need updating)
     UpdateTypeHints (String, usize, usize, Vec <String>),
+    /// the file and its completion items (the ones with insertTextFormat 2 keep their snippet syntax)
+    Completion (String, Vec <Snippet>),
     // other responses
 }
 
@@ -83,6 +87,9 @@ pub struct RustAnalyzer {
     backgroundThreadStatus: std::sync::Arc <parking_lot::RwLock <ThreadStatus>>,
     pub filePath: (String, std::sync::Arc <parking_lot::RwLock <String>>),
     dropped: bool,
+    /// the files the lsp's been sent the text of, and the version last sent
+    openedFiles: std::collections::HashMap <String, usize>,
+    requestId: usize,
 }
 
 impl Drop for RustAnalyzer {
@@ -149,7 +156,7 @@ impl RustAnalyzer {
     pub fn Initialize (&mut self, filePath: String) -> Result <(), std::io::Error> {
         // initializing the lsp
         let msg = format!(
-            "{}{}{}", r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params": {"capabilities": {},"rootUri": "file://"#,
+            "{}{}{}", r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params": {"capabilities": {"textDocument": {"completion": {"completionItem": {"snippetSupport": true}}}},"rootUri": "file://"#,
             filePath, r#""}}"#
         );
         self.PromptLsp(&msg)?;
@@ -231,6 +238,8 @@ impl RustAnalyzer {
             backgroundThreadStatus: status,
             filePath: (String::new(), filePathSendSync),
             dropped: false,
+            openedFiles: std::collections::HashMap::new(),
+            requestId: 2,  // 1 and 2 are the initialize and shutdown requests
         };
         let status = instance.Initialize(filePath);
         if status.is_err() {  return None;  }  // something went wrong somewhere
@@ -271,9 +280,17 @@ impl RustAnalyzer {
                 // todo!
                 String::from("")
             },
-            RustEvents::Completion (line, charIndex) => {
-                // todo!
-                String::from("")
+            RustEvents::Completion (line, charIndex, _text) => {
+                self.requestId += 1;
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": self.requestId,
+                    "method": "textDocument/completion",
+                    "params": {
+                        "textDocument": {"uri": format!("file://{}", self.filePath.1.read())},
+                        "position": {"line": line, "character": charIndex},
+                    },
+                }).to_string()
             },
             RustEvents::GotoDefinition (line, charIndex) => {
                 // todo!
@@ -286,6 +303,35 @@ impl RustAnalyzer {
         }
     }
 
+    // the notification giving the lsp the file's text; it's opened the first time and changed after that
+    fn SyncRequest (&mut self, text: &str) -> String {
+        let path = self.filePath.1.read().clone();
+        let uri = format!("file://{path}");
+        match self.openedFiles.get_mut(&path) {
+            Some(version) => {
+                *version += 1;
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didChange",
+                    "params": {
+                        "textDocument": {"uri": uri, "version": *version},
+                        "contentChanges": [{"text": text}],
+                    },
+                }).to_string()
+            },
+            None => {
+                self.openedFiles.insert(path, 1);
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": {
+                        "textDocument": {"uri": uri, "languageId": "rust", "version": 1, "text": text},
+                    },
+                }).to_string()
+            },
+        }
+    }
+
     // parses the response
     fn ParseResponse (event: &RustEvents, response: String, filePath: &str) -> Option <RustResponse> {
         // first check if the response is a diagnostic instead of an event response
@@ -312,9 +358,18 @@ impl RustAnalyzer {
                 // todo!
                 None
             },
-            RustEvents::Completion (line, charIndex) => {
-                // todo!
-                None
+            RustEvents::Completion (..) => {
+                // notifications (and the server's own requests) have a method; the answer doesn't
+                let json = serde_json::from_str::<serde_json::Value>(&response).ok()?;
+                if json.get("id").is_none() || json.get("method").is_some() {  return None;  }
+                // the result is either the items or a list holding them (or null, or an error, when there's none)
+                let result = json.get("result").unwrap_or(&serde_json::Value::Null);
+                let mut items = result.get("items").unwrap_or(result).as_array().cloned().unwrap_or_default();
+                items.sort_by_key(|item| item.get("sortText").or(item.get("label")).and_then(|text| text.as_str()).map(String::from));
+                Some(RustResponse::Completion(
+                    filePath.to_string(),
+                    items.iter().filter_map(Snippet::FromCompletionItem).collect()
+                ))
             },
             RustEvents::GotoDefinition (line, charIndex) => {
                 // todo!
@@ -411,34 +466,48 @@ impl RustAnalyzer {
                 Ok(_) => {},
             }
 
+            // only completions are sent for now; the others are still todo and wouldn't get an answer
+            let RustEvents::Completion (_, _, text) = &event else {  continue;  };
+            let sync = self.SyncRequest(text);
+            if self.PromptLsp(&sync).is_err() {  return ExitStatus::Error;  }
+
             // sending the request
             let request = self.GetRequest(&event);
             //println!("Request: <    :{}:    >", request);
-            //let status = self.PromptLsp(&request);  // sending the request
-            //if status.is_err() {  return ExitStatus::Error;  }
-
-            // waits for a response unless there's a timeout
-            // this occasionally yields to the executor giving it a chance to check its exit status
-            let responses = match self.ListenForResponse().await {
-                Ok(responses) => responses,
-                Err(exitStatus) => return exitStatus
-            };
-            if responses.is_empty() {  return ExitStatus::ResponseTimeOut(event);  }  // checking if a time-out occurred
-
-            // handling/parsing the responses
-            for response in responses {
-                //println!("Response: <    :{}:    >", response);
-                let response = Self::ParseResponse(&event, response, &filePath);
-                // adding the response
-                if let Some(response) = response {
-                    self.responses.push(response);
+            let status = self.PromptLsp(&request);  // sending the request
+            if status.is_err() {  return ExitStatus::Error;  }
+
+            // notifications can come in first, so this keeps listening till the answer's been parsed
+            let mut answered = false;
+            while !answered {
+                // waits for a response unless there's a timeout
+                // this occasionally yields to the executor giving it a chance to check its exit status
+                let responses = match self.ListenForResponse().await {
+                    Ok(responses) => responses,
+                    Err(exitStatus) => return exitStatus
+                };
+                if responses.is_empty() {  return ExitStatus::ResponseTimeOut(event);  }  // checking if a time-out occurred
+
+                // handling/parsing the responses
+                for response in responses {
+                    //println!("Response: <    :{}:    >", response);
+                    let response = Self::ParseResponse(&event, response, &filePath);
+                    // adding the response
+                    if let Some(response) = response {
+                        self.responses.push(response);
+                        answered = true;
+                    }
                 }
             }
         } ExitStatus::Valid
     }
 
     /// Adds a new event to be handled when this instance is polled.
+    /// A completion replaces any that's still queued, since only the latest one matters.
     pub fn NewEvent (&mut self, event: RustEvents) {
+        if matches!(event, RustEvents::Completion (..)) {
+            self.events.retain(|queued| !matches!(queued, RustEvents::Completion (..)));
+        }
         self.events.push(event);
     }
 
@@ -448,3 +517,33 @@ impl RustAnalyzer {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ParsesCompletionItems () {
+        let event = RustEvents::Completion(0, 3, String::from("let"));
+        // notifications aren't the answer
+        let notification = r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{}}"#;
+        assert_eq!(RustAnalyzer::ParseResponse(&event, notification.to_string(), "/a.rs"), None);
+
+        // the items come back in their sort order, and only the snippet keeps its $'s
+        let response = r#"{"jsonrpc":"2.0","id":3,"result":{"isIncomplete":false,"items":[
+            {"label":"letm","sortText":"2","insertText":"let mut $1 = $0;","insertTextFormat":2},
+            {"label":"len","sortText":"1","insertText":"len($x)","detail":"fn"}
+        ]}}"#;
+        let Some(RustResponse::Completion(path, items)) = RustAnalyzer::ParseResponse(&event, response.to_string(), "/a.rs")
+            else {  panic!("the completion wasn't parsed");  };
+        assert_eq!(path, "/a.rs");
+        assert_eq!(items, [
+            Snippet {  prefix: String::from("len"), body: String::from("len(\\$x)"), description: String::from("fn")  },
+            Snippet {  prefix: String::from("letm"), body: String::from("let mut $1 = $0;"), description: String::new()  },
+        ]);
+
+        // no result still answers it
+        let empty = r#"{"jsonrpc":"2.0","id":4,"result":null}"#;
+        assert_eq!(RustAnalyzer::ParseResponse(&event, empty.to_string(), "/a.rs"), Some(RustResponse::Completion(String::from("/a.rs"), vec![])));
+    }
+}
@@ -26,6 +26,16 @@ mod DataManager;
 mod RuntimeScheduler;
 mod TokenInfo;
 mod languageServer;
+mod Keymap;
+mod Vim;
+mod Macros;
+mod Commands;
+mod Settings;
+mod Folding;
+mod LineOperations;
+mod WordWrap;
+mod HorizontalScroll;
+mod Snippets;
 
 use StringPatternMatching::*;
 use Colors::*;
@@ -38,10 +48,29 @@ use CodeTabs::CodeTab;
 use eventHandler::{KeyCode, KeyModifiers, KeyParser, MouseEventType};
 use TermRender::{Colorize, Span, ColorType};
 use FileManager::*;
+use Keymap::{Action, KeyContext};
 
 use RuntimeScheduler::Runtime;
 use crate::languageServer::RustAnalyzer;
 
+// how close together clicks have to be (in milliseconds) to count as a double or triple click
+const MULTI_CLICK_TIME: u128 = 400;
+// how often a selection dragged past the top or bottom of a pane scrolls it by a line (in milliseconds)
+const EDGE_SCROLL_DELAY: u128 = 40;
+// the right click menu; the entries just run the matching action
+const CONTEXT_MENU: [(&str, Action); 5] = [
+    ("Cut", Action::Cut),
+    ("Copy", Action::Copy),
+    ("Paste", Action::Paste),
+    ("Go to Definition", Action::GoToDefinition),
+    ("Find References", Action::FindReferences),
+];
+const CONTEXT_MENU_WIDTH: u16 = 20;
+
+// the command palette (the list of commands shown while typing one out)
+const COMMAND_PALETTE_WIDTH: u16 = 72;
+const COMMAND_PALETTE_ROWS: u16 = 8;
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub enum FileTabs {
     Outline,
@@ -68,7 +97,7 @@ pub enum MenuState {
     Settings
 }
 
-type LuaScripts = std::sync::Arc<parking_lot::Mutex<std::collections::HashMap <Languages, mlua::Function>>>;
+type LuaScripts = std::sync::Arc<parking_lot::Mutex<std::collections::HashMap <Languages, LanguageScript>>>;
 pub type RustAnalyzerLsp<'a> = &'a Option <std::sync::Arc <parking_lot::RwLock <RustAnalyzer>>>;
 
 #[derive(Debug, Default)]
@@ -78,14 +107,24 @@ pub struct App <'a> {
     tabState: TabState,
     codeTabs: CodeTabs::CodeTabs,
     currentCommand: String,
+    commandError: Option <String>,  // why the last command didn't run (shown until something else is typed)
+    commandHistory: Vec <String>,
+    historyIndex: Option <usize>,  // where up/down are in the history (none while typing out a new command)
+    historyDraft: String,  // what was being typed before going back through the history
     fileBrowser: FileBrowser,
     area: TermRender::Rect,
     lastScrolled: u128,
+    lastClick: (u128, (u16, u16), usize),  // (time, position, click count)
+    codeDrag: bool,  // whether a selection is being dragged out in the code
+    lastEdgeScroll: u128,
+    contextMenu: Option <(u16, u16)>,  // where the right click menu is (if it's open)
+    vim: Option <Vim::VimState>,  // the modal layer (only there when it's turned on)
+    macros: Macros::Macros,
 
     debugInfo: String,
     suggested: String,
 
-    preferredCommandKeybind: KeyModifiers,
+    keymap: Keymap::Keymap,
     colorMode: ColorMode <'a>,
 
     menuState: MenuState,
@@ -94,6 +133,13 @@ pub struct App <'a> {
     dirFiles: Vec<String>,
 
     currentMenuSettingBox: usize,
+    tabWidth: usize,  // what newly opened tabs indent by
+    useTabs: bool,
+    detectIndent: bool,  // files keep the indent they already have
+    modifyOtherKeys: bool,  // asks terminals without the kitty protocol for xterm's modified key codes
+    languageIndents: std::collections::HashMap <String, Settings::IndentOverride>,  // by file extension
+    configuredSettings: Vec <Settings::Setting>,  // the settings a config file picked (detection leaves them alone)
+    projectOverrides: Settings::ProjectOverrides,  // what the open project's config replaced
 
     lastTab: usize,
 
@@ -117,6 +163,13 @@ impl <'a> App <'a> {
     ) -> Result<(), std::io::Error> {
         enable_raw_mode()?; // Enable raw mode for direct input handling
 
+        // starting from whatever the environment claims; the query replies can upgrade this later
+        self.colorMode.colorType = ColorTypes::FromCapabilities(app.GetCapabilities());
+        app.GetCapabilities().Query();
+        self.tabWidth = CodeTabs::DEFAULT_TAB_WIDTH;
+        self.detectIndent = true;
+        self.modifyOtherKeys = true;
+
         // making sure the lsp can immediately be connected without having to wait
         let mut lastPolled = Instant::now() - Duration::new(30,0);
         let mut rustAnalyzerInstance = None;
@@ -137,6 +190,10 @@ impl <'a> App <'a> {
             self.luaSyntaxHighlightScripts,
             "data/syntaxHighlighting.json",
         );
+        self.macros = Macros::Macros::Load();
+        if let Some(path) = Settings::ConfigPath() {
+            let _ = self.LoadSettings(&path);
+        }
 
         let mut stdout = std::io::stdout();
         crossterm::execute!(stdout, crossterm::terminal::Clear(crossterm::terminal::ClearType::All))?;
@@ -181,8 +238,13 @@ impl <'a> App <'a> {
             // the .read is ugly, but whatever. It's probably fine if polling stops while
             // processing the events
             self.HandleKeyEvents(&keyParser.read(), &mut clipboard, &rustAnalyzerInstance).await;
-            self.HandleMouseEvents(&keyParser.read()).await;  // not sure if this will be delayed, but I think it should work? idk
+            self.macros.Record(keyParser.read().Snapshot());
+            self.PlayQueuedMacro(&mut clipboard, &rustAnalyzerInstance).await;
+            self.HandleMouseEvents(&keyParser.read(), &mut clipboard, &rustAnalyzerInstance).await;  // not sure if this will be delayed, but I think it should work? idk
             self.HandleMixedEvents(&keyParser.read(), &rustAnalyzerInstance).await;
+            let replies = std::mem::take(&mut keyParser.write().terminalReplies);
+            self.HandleTerminalReplies(app, replies);
+            self.UpdateKeyboardProtocol(app);
             keyParser.write().ClearEvents();
 
             let end = SystemTime::now();
@@ -234,8 +296,13 @@ impl <'a> App <'a> {
             let analyzer = rustAnalyzer.as_mut().unwrap().try_write_for(Duration::from_millis(25));
             if analyzer.is_none() {  return;  }  // incase a timeout happens to prevent stalling
             let mut analyzer = analyzer.unwrap();
-            while let Some(_event) = analyzer.PopResponse() {
-                // todo!   handle the event
+            while let Some(event) = analyzer.PopResponse() {
+                if let languageServer::RustResponse::Completion(filePath, items) = event {
+                    for tab in self.codeTabs.tabs.iter_mut().filter(|tab| !tab.path.is_empty() && filePath.ends_with(&tab.path)) {
+                        tab.completions = items.clone();
+                    }
+                }
+                // todo!   handle the other events
             }
             // updating the filepath to ensure events are correctly handled
             if self.codeTabs.tabs.is_empty() {  return;  }
@@ -243,6 +310,65 @@ impl <'a> App <'a> {
         }
     }
 
+    fn HandleTerminalReplies (&mut self, app: &mut TermRender::App, replies: Vec <TerminalReply>) {
+        if replies.is_empty() {  return;  }
+        let detected = ColorTypes::FromCapabilities(app.GetCapabilities());
+
+        let capabilities = app.GetCapabilitiesMut();
+        for reply in replies {
+            match reply {
+                // 1, 2, and 3 are set, reset, and permanently set; 0 and 4 mean it's unsupported
+                TerminalReply::ModeReport(2026, state) => {
+                    capabilities.synchronizedOutput = matches!(state, 1..=3);
+                },
+                TerminalReply::Capability(name, true) if matches!(name.as_str(), "RGB" | "Tc") => {
+                    capabilities.trueColor = true;
+                    capabilities.colors256 = true;
+                },
+                TerminalReply::BackgroundColor(r, g, b) => {
+                    capabilities.background = Some((r, g, b));
+                    self.colorMode.background = TermRender::ColorMode::FromBackground((r, g, b));
+                },
+                TerminalReply::KeyboardFlags(_) => {
+                    capabilities.kittyKeyboard = true;
+                },
+                TerminalReply::DeviceAttributes => {
+                    capabilities.answered = true;
+                    // every other reply is in by now, so the kitty protocol can be picked if it's there
+                    // (without it, modifyOtherKeys gets switched on unless the setting turned it off)
+                    if capabilities.keyboardProtocol == TermRender::KeyboardProtocol::Legacy && capabilities.kittyKeyboard {
+                        enableKittyKeyboard();
+                        capabilities.keyboardProtocol = TermRender::KeyboardProtocol::Kitty;
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        // only following the detection if the color mode wasn't changed by hand (or picked in the config)
+        if self.colorMode.colorType == detected && !self.configuredSettings.contains(&Settings::Setting::ColorType) {
+            self.colorMode.colorType = ColorTypes::FromCapabilities(app.GetCapabilities());
+        }
+    }
+
+    // switches xterm's modifyOtherKeys on (or back off if the setting's turned off) once the terminal has
+    // answered the queries without a reply to the kitty flags query
+    fn UpdateKeyboardProtocol (&mut self, app: &mut TermRender::App) {
+        let capabilities = app.GetCapabilitiesMut();
+        if !capabilities.answered || capabilities.dumb {  return;  }
+        match (self.modifyOtherKeys, capabilities.keyboardProtocol) {
+            (true, TermRender::KeyboardProtocol::Legacy) => {
+                enableModifyOtherKeys();
+                capabilities.keyboardProtocol = TermRender::KeyboardProtocol::ModifyOtherKeys;
+            },
+            (false, TermRender::KeyboardProtocol::ModifyOtherKeys) => {
+                disableModifyOtherKeys();
+                capabilities.keyboardProtocol = TermRender::KeyboardProtocol::Legacy;
+            },
+            _ => {},
+        }
+    }
+
     async fn HandleMixedEvents<'b> (&mut self, keyEvents: &KeyParser, rustAnalyzer: RustAnalyzerLsp<'b>) {
         self.PressedLoadFile(keyEvents, rustAnalyzer).await;
     }
@@ -413,7 +539,12 @@ impl <'a> App <'a> {
                 &mut tabIndex
             );
 
-            self.codeTabs.tabs[tabIndex].UpdateScroll(events.scrollAccumulate * self.dtScalar);
+            let acceleration = events.scrollAccumulate * self.dtScalar;
+            if matches!(event.eventType, MouseEventType::ScrollLeft | MouseEventType::ScrollRight) {
+                self.codeTabs.tabs[tabIndex].ScrollSideways(acceleration);
+            } else {
+                self.codeTabs.tabs[tabIndex].UpdateScroll(acceleration);
+            }
             let currentTime = SystemTime::now()
                 .duration_since(std::time::SystemTime::UNIX_EPOCH)
                 .expect("Time went backwards...")
@@ -453,24 +584,27 @@ impl <'a> App <'a> {
 
         let tab = &mut self.codeTabs.tabs[self.lastTab];
         let lineSize = tab.lines.len().to_string().len();  // account for the length of the total lines
-        let linePos = (std::cmp::max(tab.scrolled as isize + tab.mouseScrolled, 0) as usize +
-                           position.1.saturating_sub(3) as usize,
-                       position.0.saturating_sub(lineSize as u16) as usize);
-        tab.cursor = (
-            std::cmp::min(
-                linePos.0,
-                tab.lines.len() - 1
-            ),
-            linePos.1.saturating_sub( {
-                if linePos.0 == tab.cursor.0 && linePos.1 > tab.cursor.1 {
-                    1
-                } else {  0  }
-            } )
-        );
-        tab.cursor.1 = std::cmp::min(
-            tab.cursor.1,
-            tab.lines[tab.cursor.0].len()
+        let top = std::cmp::max(tab.scrolled as isize + tab.mouseScrolled, 0) as usize;
+        let (clickedLine, wrapRow) = tab.RowAt(top, position.1.saturating_sub(3) as usize);
+        let linePos = (clickedLine, position.0.saturating_sub(lineSize as u16) as usize + tab.horizontalScroll);
+        // clicking the gutter of a foldable line folds or unfolds it
+        if position.0 < lineSize as u16 && !events.ContainsMouseModifier(KeyModifiers::Shift) &&
+            (tab.FoldedLength(linePos.0).is_some() || tab.FoldRanges().iter().any(|(start, ..)| *start == linePos.0))
+        {
+            tab.ToggleFold(linePos.0);
+            return;
+        }
+        // the columns past the cursor are shifted over by it
+        let column = linePos.1.saturating_sub( {
+            if linePos.0 == tab.cursor.0 && wrapRow == tab.RowOf(tab.cursor) && linePos.1 > tab.RowColumn(tab.cursor) {
+                1
+            } else {  0  }
+        } );
+        let line = std::cmp::min(
+            linePos.0,
+            tab.lines.len() - 1
         );
+        tab.cursor = (line, tab.PositionAtRow(line, wrapRow, column));
         tab.scrolled = std::cmp::max(tab.mouseScrolledFlt as isize + tab.scrolled as isize, 0) as usize;
         tab.mouseScrolled = 0;
         tab.mouseScrolledFlt = 0.0;
@@ -519,13 +653,10 @@ impl <'a> App <'a> {
         }
     }
 
-    async fn HandlePress (&mut self, events: &KeyParser, event: &MouseEvent, padding: u16) {
-        if  event.position.0 > padding &&
-            event.position.1 < self.area.height - 8 &&
-            event.position.1 > 2 &&
-            !self.codeTabs.tabs.is_empty()
-        {
+    async fn HandlePress (&mut self, events: &KeyParser, event: &MouseEvent, padding: u16, clipBoard: &mut Clipboard) {
+        if self.IsOverCode(event, padding) {
             self.PressedCode(events, event, padding as usize);
+            self.HandleMultiClick(events, event, clipBoard);
         } else if
         event.position.0 <= 29 &&
             event.position.1 < self.area.height - 10 &&
@@ -544,40 +675,54 @@ impl <'a> App <'a> {
     }
 
     fn HighlightLeftClick (&mut self, _events: &KeyParser, event: &MouseEvent, padding: usize) {
-        // updating the highlighting position
-        self.lastTab = self.codeTabs.GetTabNumber(
-            &self.area, padding,
-            event.position.0 as usize,
-            &mut self.lastTab
-        );
-        // adjusting the position
+        // the drag stays in the pane it started in, so the position gets clamped into the code
+        let bottom = self.area.height.saturating_sub(9);
         let position = (
-            self.codeTabs.GetRelativeTabPosition(event.position.0, &self.area, padding as u16 + 4),
-            event.position.1
+            self.codeTabs.GetRelativeTabPosition(
+                std::cmp::max(event.position.0, padding as u16 + 1), &self.area, padding as u16 + 4
+            ),
+            event.position.1.clamp(3, bottom)
         );
 
         let cursorEnding = self.codeTabs.tabs[self.lastTab].cursor;
 
+        let currentTime = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("Time went backwards...")
+            .as_millis();
         let tab = &mut self.codeTabs.tabs[self.lastTab];
+        // the cursor following scroll would fight the edge scrolling
+        tab.pauseScroll = currentTime;
+        tab.scrolled = std::cmp::max(tab.mouseScrolledFlt as isize + tab.scrolled as isize, 0) as usize;
+        tab.mouseScrolled = 0;
+        tab.mouseScrolledFlt = 0.0;
+
+        // dragging past the top or bottom of the pane scrolls it along
+        if currentTime.saturating_sub(self.lastEdgeScroll) >= EDGE_SCROLL_DELAY {
+            if event.position.1 <= 3 && tab.scrolled > 0 {
+                tab.scrolled -= 1;
+                self.lastEdgeScroll = currentTime;
+            } else if event.position.1 >= bottom && tab.scrolled + 1 < tab.lines.len() {
+                tab.scrolled += 1;
+                self.lastEdgeScroll = currentTime;
+            }
+        }
+
         let lineSize = tab.lines.len().to_string().len();  // account for the length of the total lines
-        let linePos = (std::cmp::max(tab.scrolled as isize + tab.mouseScrolled, 0) as usize +
-                           position.1.saturating_sub(3) as usize,
-                       position.0.saturating_sub(lineSize as u16) as usize);
-        tab.cursor = (
-            std::cmp::min(
-                linePos.0,
-                tab.lines.len() - 1
-            ),
-            linePos.1.saturating_sub( {
-                if linePos.0 == tab.cursor.0 && linePos.1 > tab.cursor.1 {
-                    1
-                } else {  0  }
-            } )
-        );
-        tab.cursor.1 = std::cmp::min(
-            tab.cursor.1,
-            tab.lines[tab.cursor.0].len()
+        let top = std::cmp::max(tab.scrolled as isize + tab.mouseScrolled, 0) as usize;
+        let (clickedLine, wrapRow) = tab.RowAt(top, position.1.saturating_sub(3) as usize);
+        let linePos = (clickedLine, position.0.saturating_sub(lineSize as u16) as usize + tab.horizontalScroll);
+        // the columns past the cursor are shifted over by it
+        let column = linePos.1.saturating_sub( {
+            if linePos.0 == tab.cursor.0 && wrapRow == tab.RowOf(tab.cursor) && linePos.1 > tab.RowColumn(tab.cursor) {
+                1
+            } else {  0  }
+        } );
+        let line = std::cmp::min(
+            linePos.0,
+            tab.lines.len() - 1
         );
+        tab.cursor = (line, tab.PositionAtRow(line, wrapRow, column));
         tab.mouseScrolled = 0;
         tab.mouseScrolledFlt = 0.0;
         self.appState = AppState::Tabs;
@@ -594,20 +739,111 @@ impl <'a> App <'a> {
         }
     }
 
-    async fn HandleLeftClick (&mut self, events: &KeyParser, event: &MouseEvent) {
+    async fn HandleLeftClick (&mut self, events: &KeyParser, event: &MouseEvent, clipBoard: &mut Clipboard) {
         let padding =
             if self.appState == AppState::CommandPrompt {  29  }
             else {  0  };
         // checking for code selection
         if matches!(event.state, MouseState::Release | MouseState::Hold) {
-            if event.position.0 > padding && event.position.1 < self.area.height - 8 &&
-                event.position.1 > 2 &&
-                !self.codeTabs.tabs.is_empty()
-            {
+            if self.codeDrag && !self.codeTabs.tabs.is_empty() {
                 self.HighlightLeftClick(events, event, padding as usize);
+                let tab = &self.codeTabs.tabs[self.lastTab];
+                if event.state == MouseState::Release && tab.highlighting && tab.cursor != tab.cursorEnd {
+                    SetPrimarySelection(clipBoard, tab.GetSelection());
+                }
             }
+            if event.state == MouseState::Release {  self.codeDrag = false;  }
         } else if event.state == MouseState::Press {
-            self.HandlePress(events, event, padding).await;
+            self.HandlePress(events, event, padding, clipBoard).await;
+        }
+    }
+
+    fn IsOverCode (&self, event: &MouseEvent, padding: u16) -> bool {
+        event.position.0 > padding &&
+            event.position.1 < self.area.height - 8 &&
+            event.position.1 > 2 &&
+            !self.codeTabs.tabs.is_empty()
+    }
+
+    // double clicks select a token and triple clicks the whole line (anything else starts a drag)
+    fn HandleMultiClick (&mut self, events: &KeyParser, event: &MouseEvent, clipBoard: &mut Clipboard) {
+        let currentTime = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("Time went backwards...")
+            .as_millis();
+        let (lastTime, lastPosition, clicks) = self.lastClick;
+        let clicks =
+            if currentTime.saturating_sub(lastTime) <= MULTI_CLICK_TIME && lastPosition == event.position &&
+                !events.ContainsMouseModifier(KeyModifiers::Shift)
+            {  clicks % 3 + 1  }
+            else {  1  };
+        self.lastClick = (currentTime, event.position, clicks);
+
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        match clicks {
+            2 => tab.SelectToken(),
+            3 => tab.SelectLine(),
+            _ => {},
+        }
+        if clicks > 1 {
+            SetPrimarySelection(clipBoard, tab.GetSelection());
+        }
+        self.codeDrag = clicks == 1;
+    }
+
+    // middle clicking pastes at the mouse; on linux this is the primary selection (whatever was last selected)
+    async fn HandleMiddleClick<'b> (&mut self,
+                                    events: &KeyParser,
+                                    event: &MouseEvent,
+                                    clipBoard: &mut Clipboard,
+                                    rustAnalyzer: RustAnalyzerLsp<'b>
+    ) {
+        let padding =
+            if self.appState == AppState::CommandPrompt {  29  }
+            else {  0  };
+        if !self.IsOverCode(event, padding) {  return;  }
+
+        let text = GetPrimarySelection(clipBoard).or_else(|| clipBoard.get_text().ok());
+        self.PressedCode(events, event, padding as usize);
+        if let Some(text) = text {
+            self.PasteText(&text, rustAnalyzer).await;
+        }
+    }
+
+    // opens the context menu at the mouse (keeping the selection if there is one)
+    fn HandleRightClick (&mut self, events: &KeyParser, event: &MouseEvent) {
+        let padding =
+            if self.appState == AppState::CommandPrompt {  29  }
+            else {  0  };
+        if !self.IsOverCode(event, padding) {  return;  }
+
+        if !self.codeTabs.tabs[self.lastTab].highlighting {
+            self.PressedCode(events, event, padding as usize);
+        }
+        // keeping it on screen and above the error bar
+        let height = CONTEXT_MENU.len() as u16 + 2;
+        self.contextMenu = Some((
+            std::cmp::min(event.position.0, self.area.width.saturating_sub(CONTEXT_MENU_WIDTH)),
+            std::cmp::min(event.position.1, self.area.height.saturating_sub(8 + height)),
+        ));
+    }
+
+    // any click while the context menu is open lands here; clicking off of it closes it
+    async fn PressedContextMenu<'b> (&mut self,
+                                     events: &KeyParser,
+                                     event: &MouseEvent,
+                                     clipBoard: &mut Clipboard,
+                                     rustAnalyzer: RustAnalyzerLsp<'b>
+    ) {
+        let Some((x, y)) = self.contextMenu.take() else {  return;  };
+        if event.eventType != MouseEventType::Left || self.codeTabs.tabs.is_empty() {  return;  }
+
+        // the border takes up the first row and column
+        let inside = event.position.0 > x && event.position.0 < x + CONTEXT_MENU_WIDTH - 1;
+        let row = event.position.1.saturating_sub(y + 1) as usize;
+        if inside && event.position.1 > y && row < CONTEXT_MENU.len() {
+            let (_, action) = CONTEXT_MENU[row];
+            self.HandleCodeCommands(action, events, clipBoard, rustAnalyzer).await;
         }
     }
 
@@ -615,13 +851,24 @@ impl <'a> App <'a> {
         // todo!
     }
 
-    async fn HandleMouseEvents (&mut self, events: &KeyParser) {
+    async fn HandleMouseEvents<'b> (&mut self,
+                                    events: &KeyParser,
+                                    clipBoard: &mut Clipboard,
+                                    rustAnalyzer: RustAnalyzerLsp<'b>
+    ) {
         if let Some(event) = &events.mouseEvent {
             if self.appState == AppState::Menu {
                 self.HandleMenuMouseEvents(events, event);
                 return;
             }
 
+            if self.contextMenu.is_some() && event.state == MouseState::Press &&
+                matches!(event.eventType, MouseEventType::Left | MouseEventType::Right | MouseEventType::Middle)
+            {
+                self.PressedContextMenu(events, event, clipBoard, rustAnalyzer).await;
+                return;
+            }
+
             match event.eventType {
                 MouseEventType::Down if !self.codeTabs.tabs.is_empty() => {
                     let currentTime = SystemTime::now()
@@ -632,7 +879,7 @@ impl <'a> App <'a> {
                         {  return;  }
                     self.HandleScrollEvent(event, events);
                 },
-                MouseEventType::Up if !self.codeTabs.tabs.is_empty() => {
+                MouseEventType::Up | MouseEventType::ScrollLeft | MouseEventType::ScrollRight if !self.codeTabs.tabs.is_empty() => {
                     let currentTime = SystemTime::now()
                         .duration_since(SystemTime::UNIX_EPOCH)
                         .expect("Time went backwards...")
@@ -642,120 +889,110 @@ impl <'a> App <'a> {
                     self.HandleScrollEvent(event, events);
                 },
                 MouseEventType::Left => {
-                    self.HandleLeftClick(events, event).await;
+                    self.HandleLeftClick(events, event, clipBoard).await;
+                },
+                MouseEventType::Middle if event.state == MouseState::Press => {
+                    self.HandleMiddleClick(events, event, clipBoard, rustAnalyzer).await;
+                },
+                MouseEventType::Right if event.state == MouseState::Press => {
+                    self.HandleRightClick(events, event);
                 },
-                MouseEventType::Middle => {},
-                MouseEventType::Right => {},
                 _ => {},
             }
         }
     }
 
-    fn HandleTabViewTabPress (&mut self, keyEvents: &KeyParser) {
-        if keyEvents.ContainsKeyCode(KeyCode::Tab) {
-            if keyEvents.ContainsModifier(&KeyModifiers::Shift) &&
-                self.tabState == TabState::Files {
-
-                self.fileBrowser.fileTab = match self.fileBrowser.fileTab {
-                    FileTabs::Files => FileTabs::Outline,
-                    FileTabs::Outline => FileTabs::Files,
-                }
-            } else {
-                self.tabState = match self.tabState {
-                    TabState::Code => TabState::Files,
-                    TabState::Files => TabState::Tabs,
-                    TabState::Tabs => TabState::Code,
-                }
+    fn HandleTabViewTabPress (&mut self, action: Action) {
+        if action == Action::SwitchFileView && self.tabState == TabState::Files {
+            self.fileBrowser.fileTab = match self.fileBrowser.fileTab {
+                FileTabs::Files => FileTabs::Outline,
+                FileTabs::Outline => FileTabs::Files,
+            }
+        } else {
+            self.tabState = match self.tabState {
+                TabState::Code => TabState::Files,
+                TabState::Files => TabState::Tabs,
+                TabState::Tabs => TabState::Code,
             }
         }
     }
 
-    fn HandleCommandPromptKeyEvents (&mut self, keyEvents: &KeyParser) {
-        if keyEvents.ContainsModifier(&KeyModifiers::Option) {  return;  }
-        for chr in &keyEvents.charEvents {
-            self.currentCommand.push(*chr);
+    async fn HandleCommandPromptKeyEvents<'b> (&mut self, keyEvents: &KeyParser, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        if !keyEvents.ContainsModifier(&KeyModifiers::Option) {
+            for chr in &keyEvents.charEvents {
+                self.currentCommand.push(*chr);
+                self.commandError = None;
+
+                // the files being suggested follow the path
+                if matches!(*chr, '/' | ' ') {
+                    self.UpdateCommandDirectory();
+                }
+            }
+        }
+        // commands are a single line
+        for text in &keyEvents.pastes {
+            self.currentCommand.push_str(&text.replace('\n', " "));
         }
 
-        self.HandleTabViewTabPress(keyEvents);
-        self.HandleCodeTabviewKeyEvents(keyEvents);
+        // the focused panel gets the keys first, unless a command is being typed
+        let panelContext = match self.tabState {
+            TabState::Code => None,
+            TabState::Files => Some(KeyContext::Files),
+            TabState::Tabs => Some(KeyContext::Tabs),
+        };
+        let panelAction = panelContext
+            .filter(|_| self.currentCommand.is_empty())
+            .and_then(|context| self.keymap.GetAction(context, keyEvents));
+        let Some(action) = panelAction.or_else(|| self.keymap.GetAction(KeyContext::Prompt, keyEvents)) else {
+            return;
+        };
 
-        match self.tabState {
-            TabState::Code => {
-                // nothing so far ig
-            },
-            TabState::Files => {
-                self.HandleFilebrowserKeyEvents(keyEvents);
+        match action {
+            Action::NextPanel if !self.currentCommand.is_empty() => self.CompleteCommand(),
+            Action::NextPanel | Action::SwitchFileView => self.HandleTabViewTabPress(action),
+            Action::Confirm if !self.currentCommand.is_empty() => self.RunCommand(rustAnalyzer).await,
+            Action::Confirm => self.HandleCodeTabviewKeyEvents(),
+            Action::DeleteChar => {  self.currentCommand.pop();  },
+            Action::HistoryUp => self.CommandHistoryUp(),
+            Action::HistoryDown => self.CommandHistoryDown(),
+            Action::OutlineUp | Action::OutlineDown | Action::OutlineJump => self.HandleFilebrowserKeyEvents(action),
+            _ => self.HandleTabsKeyEvents(action),
+        }
+    }
+
+    // Swaps out the color bindings for the named theme (the old theme is kept if it fails to load)
+    fn ChangeTheme (&mut self, name: &str) {
+        match ColorBindings::LoadTheme(name) {
+            Ok(bindings) => {
+                self.colorMode.colorBindings = bindings;
+                // the cached lines still have the old colors
+                for tab in self.codeTabs.tabs.iter_mut() {
+                    tab.ClearRenderCache();
+                }
             },
-            TabState::Tabs => {
-                self.HandleTabsKeyEvents(keyEvents);
+            Err(error) => {
+                self.debugInfo = error;
             },
         }
-
-        if !self.currentCommand.is_empty() {
-            self.HandleCommands(keyEvents);
-        }
     }
 
-    fn JumpLineUp (&mut self) {
-        // jumping up
-        if let Some(numberString) = self.currentCommand.get(1..) {
-            let number = numberString.parse:: <usize>();
-            if number.is_ok() {
-                let cursor = self.codeTabs.tabs[self.lastTab].cursor.0;
-                self.codeTabs.tabs[self.lastTab].JumpCursor(
-                    cursor.saturating_sub(number.unwrap_or(0)), 1
-                );
-            }
+    // Swaps out the keymap (the old one is kept if the new one fails to load or has conflicts)
+    fn ChangeKeymap (&mut self, name: &str) {
+        match Keymap::Keymap::LoadKeymap(name) {
+            Ok(keymap) => {  self.keymap = keymap;  },
+            Err(error) => {  self.debugInfo = error;  },
         }
     }
 
-    fn JumpLineDown (&mut self) {
-        // jumping down
-        if let Some(numberString) = self.currentCommand.get(1..) {
-            let number = numberString.parse:: <usize>();
-            if number.is_ok() {
-                let cursor = self.codeTabs.tabs[self.lastTab].cursor.0;
-                self.codeTabs.tabs[self.lastTab].JumpCursor(
-                    cursor.saturating_add(number.unwrap_or(0)), 1
-                );
-            }
-        }
-    }
-
-    fn HandleCommands (&mut self, keyEvents: &KeyParser) {
-        if keyEvents.ContainsKeyCode(KeyCode::Return) {
-            if self.currentCommand == "q" {
-                self.Exit();
-            }
-
-            // jumping to, command
-            if self.currentCommand.starts_with('[') {
-                self.JumpLineUp();
-            } else if self.currentCommand.starts_with(']') {
-                self.JumpLineDown();
-            } else if self.currentCommand == *"gd" {
-                // todo!
-            } else if self.currentCommand == *"-light" {
-                TermRender::ColorMode::ToLight();
-            } else if self.currentCommand == *"-dark" {
-                TermRender::ColorMode::ToDark();
-            }
-
-            self.currentCommand.clear();
-        } else if keyEvents.ContainsKeyCode(KeyCode::Delete) {
-            self.currentCommand.pop();
-        }
-    }
-
-    fn HandleCodeTabviewKeyEvents (&mut self, keyEvents: &KeyParser) {
-        if keyEvents.ContainsKeyCode(KeyCode::Return) && self.currentCommand.is_empty() && !self.codeTabs.tabs.is_empty() {
+    fn HandleCodeTabviewKeyEvents (&mut self) {
+        if !self.codeTabs.tabs.is_empty() {
             self.appState = AppState::Tabs;
         }
     }
 
-    fn HandleFilebrowserKeyEvents (&mut self, keyEvents: &KeyParser) {
+    fn HandleFilebrowserKeyEvents (&mut self, action: Action) {
         if self.fileBrowser.fileTab == FileTabs::Outline {
-            if keyEvents.ContainsKeyCode(KeyCode::Return) && self.currentCommand.is_empty() && !self.codeTabs.tabs.is_empty() {
+            if action == Action::OutlineJump && !self.codeTabs.tabs.is_empty() {
                 let mut nodePath = self.codeTabs.tabs[self.lastTab].linearScopes.read()[
                     self.fileBrowser.outlineCursor].clone();
                 nodePath.reverse();
@@ -767,33 +1004,33 @@ impl <'a> App <'a> {
                 }
                 drop(scopesRead);  // dropped the read
                 self.codeTabs.tabs[self.lastTab].JumpCursor(start, 1);
-            } else if keyEvents.ContainsKeyCode(KeyCode::Up) {
+            } else if action == Action::OutlineUp {
                 self.fileBrowser.MoveCursorUp();
-            } else if keyEvents.ContainsKeyCode(KeyCode::Down) {
+            } else if action == Action::OutlineDown {
                 self.fileBrowser.MoveCursorDown(
                     &self.codeTabs.tabs[self.lastTab].linearScopes.read(),
                     &self.codeTabs.tabs[self.lastTab].scopes.read());
             }
         }
+        // jumping to a scope also moves over to the code
+        if action == Action::OutlineJump {
+            self.HandleCodeTabviewKeyEvents();
+        }
     }
 
-    fn HandleTabsKeyEvents (&mut self, keyEvents: &KeyParser) {
-        if keyEvents.ContainsKeyCode(KeyCode::Left) {
-            if keyEvents.ContainsModifier(&KeyModifiers::Option) {
-                self.codeTabs.MoveTabLeft()
-            } else {
-                self.codeTabs.TabLeft();
-            }
-        } else if keyEvents.ContainsKeyCode(KeyCode::Right) {
-            if keyEvents.ContainsModifier(&KeyModifiers::Option) {
-                self.codeTabs.MoveTabRight()
-            } else {
-                self.codeTabs.TabRight();
-            }
-        } else if keyEvents.ContainsKeyCode(KeyCode::Return) && !self.codeTabs.tabs.is_empty() {
+    fn HandleTabsKeyEvents (&mut self, action: Action) {
+        match action {
+            Action::MoveTabLeft => self.codeTabs.MoveTabLeft(),
+            Action::TabLeft => self.codeTabs.TabLeft(),
+            Action::MoveTabRight => self.codeTabs.MoveTabRight(),
+            Action::TabRight => self.codeTabs.TabRight(),
+            _ => {},
+        }
+
+        if action == Action::OpenTab && !self.codeTabs.tabs.is_empty() {
             self.appState = AppState::Tabs;
             self.tabState = TabState::Code;
-        } else if keyEvents.ContainsKeyCode(KeyCode::Delete) {
+        } else if action == Action::CloseTab && !self.codeTabs.tabs.is_empty() {
             self.codeTabs.tabs.remove(self.codeTabs.currentTab);
             self.codeTabs.tabFileNames.remove(self.codeTabs.currentTab);
             self.codeTabs.currentTab = self.codeTabs.currentTab.saturating_sub(1);
@@ -814,56 +1051,57 @@ impl <'a> App <'a> {
             keyEvents.ContainsModifier(&KeyModifiers::Option)
         ) {
             for chr in &keyEvents.charEvents {
-                if *chr == '(' {
-                    self.codeTabs.tabs[self.lastTab]
-                        .InsertChars("()".to_string(), &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
-                    self.codeTabs.tabs[self.lastTab].cursor.1 -= 1;
-                } else if *chr == '{' {
-                    self.codeTabs.tabs[self.lastTab]
-                        .InsertChars("{}".to_string(), &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
-                    self.codeTabs.tabs[self.lastTab].cursor.1 -= 1;
-                } else if *chr == '[' {
-                    self.codeTabs.tabs[self.lastTab]
-                        .InsertChars("[]".to_string(), &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
-                    self.codeTabs.tabs[self.lastTab].cursor.1 -= 1;
-                } else if *chr == '\"' {
-                    self.codeTabs.tabs[self.lastTab]
-                        .InsertChars("\"\"".to_string(), &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
-                    self.codeTabs.tabs[self.lastTab].cursor.1 -= 1;
-                } else {
-                    self.codeTabs.tabs[self.lastTab]
-                        .InsertChars(chr.to_string(), &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
-                }
+                self.codeTabs.tabs[self.lastTab]
+                    .TypeChar(*chr, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
             }
         }
     }
 
     async fn DeleteCode<'b> (&mut self,
-                             keyEvents: &KeyParser,
+                             action: Action,
                              _clipBoard: &mut Clipboard,
                              rustAnalyzer: RustAnalyzerLsp<'b>
     ) {
         let mut numDel = 1;
         let mut offset = 0;
 
-        if keyEvents.keyModifiers.contains(&KeyModifiers::Option) {
-            if keyEvents.ContainsModifier(&KeyModifiers::Shift) {
+        match action {
+            Action::DeleteWordRight => {
                 numDel = self.codeTabs.tabs[self.lastTab].FindTokenPosRight();
                 offset = numDel;
-            } else {
+            },
+            Action::DeleteWordLeft => {
                 numDel = self.codeTabs.tabs[self.lastTab].FindTokenPosLeft();
-            }
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) {
-            if keyEvents.ContainsModifier(&KeyModifiers::Shift) {
+            },
+            Action::DeleteLineRight => {
                 numDel = self.codeTabs.tabs[self.lastTab].lines[
                     self.codeTabs.tabs[self.lastTab].cursor.0
                     ].len() - self.codeTabs.tabs[self.lastTab].cursor.1;
                 offset = numDel;
-            } else {
+            },
+            Action::DeleteLineLeft => {
                 numDel = self.codeTabs.tabs[self.lastTab].cursor.1;
-            }
-        } else if keyEvents.ContainsModifier(&KeyModifiers::Shift) {
-            offset = numDel;
+            },
+            Action::DeleteLeft => {
+                // backspacing the opener of an empty pair takes the closer along with it
+                let tab = &mut self.codeTabs.tabs[self.lastTab];
+                if let Some((open, close)) = tab.InEmptyPair(&self.luaSyntaxHighlightScripts) {
+                    tab.cursor.1 += close.len_utf8();
+                    numDel = open.len_utf8() + close.len_utf8();
+                }
+            },
+            Action::DeleteRight => {
+                // at the end of a line, the next line gets pulled up (the same as deleting from its start)
+                let tab = &mut self.codeTabs.tabs[self.lastTab];
+                if !tab.highlighting && tab.cursor.1 >= tab.lines[tab.cursor.0].len() &&
+                    tab.cursor.0 + 1 < tab.lines.len()
+                {
+                    tab.cursor = (tab.cursor.0 + 1, 0);
+                } else {
+                    offset = numDel;
+                }
+            },
+            _ => {},
         }
 
         self.codeTabs.tabs[
@@ -882,12 +1120,12 @@ impl <'a> App <'a> {
         }
     }
 
-    fn MoveCodeCursorLeft (&mut self, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
+    fn MoveCodeCursorLeft (&mut self, action: Action, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
         let highlight= self.HandleHighlightOnCursorMove(keyEvents);
 
-        if keyEvents.ContainsModifier(&KeyModifiers::Option) {
+        if action == Action::WordLeft {
             self.codeTabs.tabs[self.lastTab].MoveCursorLeftToken();
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) {
+        } else if action == Action::LineStart {
             self.codeTabs.tabs[self.lastTab].mouseScrolledFlt = 0.0;
             self.codeTabs.tabs[self.lastTab].mouseScrolled = 0;
             // checking if it's the true first value or not
@@ -922,12 +1160,12 @@ impl <'a> App <'a> {
         }
     }
 
-    fn MoveCodeCursorRight (&mut self, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
+    fn MoveCodeCursorRight (&mut self, action: Action, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
         let highlight = self.HandleHighlightOnCursorMove(keyEvents);
 
-        if keyEvents.ContainsModifier(&KeyModifiers::Option) {
+        if action == Action::WordRight {
             self.codeTabs.tabs[self.lastTab].MoveCursorRightToken();
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) {
+        } else if action == Action::LineEnd {
             let tab = &mut self.codeTabs.tabs[self.lastTab];
             tab.scrolled = std::cmp::max(tab.mouseScrolledFlt as isize + tab.scrolled as isize, 0) as usize;
             tab.mouseScrolledFlt = 0.0;
@@ -941,10 +1179,10 @@ impl <'a> App <'a> {
         }
     }
 
-    fn MoveCodeCursorUp (&mut self, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
+    fn MoveCodeCursorUp (&mut self, action: Action, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
         let highlight = self.HandleHighlightOnCursorMove(keyEvents);
 
-        if keyEvents.ContainsModifier(&KeyModifiers::Option) {
+        if action == Action::ScopeStart {
             let tab = &mut self.codeTabs.tabs[self.lastTab];
             let mut jumps = tab.scopeJumps.read()[tab.cursor.0].clone();
             jumps.reverse();
@@ -952,49 +1190,72 @@ impl <'a> App <'a> {
             tab.JumpCursor(
                 start, 1
             );
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) {
+        } else if action == Action::FileStart {
             let tab = &mut self.codeTabs.tabs[self.lastTab];
             tab.scrolled = std::cmp::max(tab.mouseScrolledFlt as isize + tab.scrolled as isize, 0) as usize;
             tab.mouseScrolledFlt = 0.0;
             tab.mouseScrolled = 0;
             tab.cursor.0 = 0;
+        } else if action == Action::PageUp {
+            let pageHeight = self.CodePageHeight();
+            self.codeTabs.tabs[self.lastTab].PageUp(pageHeight, highlight);
         } else {
             self.codeTabs.tabs[self.lastTab].CursorUp(highlight);
         }
     }
 
-    fn MoveCodeCursorDown (&mut self, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
+    fn MoveCodeCursorDown (&mut self, action: Action, keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
         let highlight = self.HandleHighlightOnCursorMove(keyEvents);
 
-        if keyEvents.ContainsModifier(&KeyModifiers::Option) {
+        if action == Action::ScopeEnd {
             let tab = &mut self.codeTabs.tabs[self.lastTab];
             let mut jumps = tab.scopeJumps.read()[tab.cursor.0].clone();
             jumps.reverse();
             let end = tab.scopes.read().GetNode(&mut jumps).end;
             tab.JumpCursor(end, 1);
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) {
+        } else if action == Action::FileEnd {
             let tab = &mut self.codeTabs.tabs[self.lastTab];
             tab.scrolled = std::cmp::max(tab.mouseScrolledFlt as isize + tab.scrolled as isize, 0) as usize;
             tab.mouseScrolledFlt = 0.0;
             tab.mouseScrolled = 0;
             tab.cursor.0 =
                 tab.lines.len() - 1;
+        } else if action == Action::PageDown {
+            let pageHeight = self.CodePageHeight();
+            self.codeTabs.tabs[self.lastTab].PageDown(pageHeight, highlight);
         } else {
             self.codeTabs.tabs[self.lastTab].CursorDown(highlight);
         }
     }
 
+    // the number of code lines visible in the editor
+    fn CodePageHeight (&self) -> usize {
+        CodeTabs::CodeRows(self.area.height).max(1)
+    }
+
     async fn HandleCodeTabPress<'b> (&mut self,
-                                     keyEvents: &KeyParser,
+                                     action: Action,
                                      _clipBoard: &mut Clipboard,
                                      rustAnalyzer: RustAnalyzerLsp<'b>
     ) {
-        if keyEvents.ContainsModifier(&KeyModifiers::Shift) {
-            self.codeTabs.tabs[self.lastTab].UnIndent(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        // while a snippet is being filled in, these move between its stops instead
+        if tab.NextSnippetStop(action != Action::Unindent) {  return;  }
+        if action == Action::AcceptSuggestion && tab.ExpandSnippet(&self.luaSyntaxHighlightScripts, rustAnalyzer).await {
+            return;
+        }
+        // a selection over more than one line (or any selection for unindent) shifts all of its lines
+        let wholeLines = tab.highlighting && (tab.cursor.0 != tab.cursorEnd.0 || action == Action::Unindent);
+        if wholeLines && (self.suggested.is_empty() || action != Action::AcceptSuggestion) {
+            let (start, end) = tab.SelectedLines();
+            tab.IndentLines(start, end, action == Action::Unindent, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+        } else if action == Action::Unindent {
+            tab.UnIndent(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
         } else {
-            if self.suggested.is_empty() || !keyEvents.ContainsModifier(&KeyModifiers::Option) {
+            if self.suggested.is_empty() || action != Action::AcceptSuggestion {
+                let indent = self.codeTabs.tabs[self.lastTab].Indent();
                 self.codeTabs.tabs[self.lastTab]
-                    .InsertChars("    ".to_string(), &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+                    .InsertChars(indent, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
             } else {
                 self.codeTabs.tabs[self.lastTab]
                     .RemoveCurrentToken_NonUpdate();
@@ -1025,28 +1286,6 @@ impl <'a> App <'a> {
         }
     }
 
-    async fn PasteCodeLoop<'b> (&mut self,
-                                splitLength: usize,
-                                i: usize,
-                                rustAnalyzer: RustAnalyzerLsp<'b>
-    ) {
-        let tab = &mut self.codeTabs.tabs[self.lastTab];
-
-        if i < splitLength {
-            // why does highlight need to be set to true?????? This makes noooo sense??? I give up
-            tab.LineBreakIn(true, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
-            // making sure all actions occur on the same iteration
-        }
-        if i >0 && i < splitLength {
-            if let Some(mut elements) = tab.changeBuffer.pop() {
-                while let Some(element) = elements.pop() {
-                    let size = tab.changeBuffer.len() - 1;
-                    tab.changeBuffer[size].insert(0, element);
-                }
-            }
-        }
-    }
-
     async fn PasteCode<'b> (&mut self,
                             _keyEvents: &KeyParser,
                             clipBoard: &mut Clipboard,
@@ -1054,17 +1293,28 @@ impl <'a> App <'a> {
     ) {
         // pasting in the text
         if let Ok(text) = clipBoard.get_text() {
-            let splitText = text.split('\n');
-            let splitLength = splitText.clone().count() - 1;
-            for (i, line) in splitText.enumerate() {
-                if line.is_empty() {  continue;  }
-                self.codeTabs.tabs[self.lastTab].InsertChars(
-                    line.to_string(), &self.luaSyntaxHighlightScripts,
-                    rustAnalyzer
-                ).await;
-                self.PasteCodeLoop(splitLength, i, rustAnalyzer).await;
+            self.PasteText(&text, rustAnalyzer).await;
+        }
+    }
+
+    // Inserts the text shifted to the cursor line's indent (none of the typing auto-behaviours), as a single undo group
+    async fn PasteText<'b> (&mut self, text: &str, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let undoDepth = tab.changeBuffer.len();
+        let text = tab.ReindentPaste(text);
+
+        let lineCount = text.split('\n').count();
+        for (i, line) in text.split('\n').enumerate() {
+            if !line.is_empty() {
+                tab.InsertChars(line.to_string(), &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+            }
+            if i + 1 < lineCount {
+                // why does highlight need to be set to true?????? This makes noooo sense??? I give up
+                tab.LineBreakIn(true, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;
             }
         }
+
+        tab.MergeChanges(undoDepth);
     }
 
     fn FindCodeReferenceLine (&mut self, _keyEvents: &KeyParser, _clipBoard: &mut Clipboard) {
@@ -1090,54 +1340,109 @@ impl <'a> App <'a> {
         }
     }
 
-    async fn HandleUndoRedoCode<'b> (&mut self,
+    async fn HandleCodeCommands<'b> (&mut self,
+                                     action: Action,
                                      keyEvents: &KeyParser,
-                                     _clipBoard: &mut Clipboard,
+                                     clipBoard: &mut Clipboard,
                                      rustAnalyzer: RustAnalyzerLsp<'b>
     ) {
-        if keyEvents.ContainsModifier(&KeyModifiers::Shift) ||
-            keyEvents.charEvents.contains(&'r') {  // common/control + r | z+shift = redo
-            self.codeTabs.tabs[self.lastTab].Redo(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
-        } else {
-            self.codeTabs.tabs[self.lastTab].Undo(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+        match action {
+            Action::Save => {
+                // saving the program
+                self.codeTabs.tabs[self.lastTab].Save();
+            },
+            Action::Find => self.FindCodeReferenceLine(keyEvents, clipBoard),
+            Action::Undo => {
+                self.codeTabs.tabs[self.lastTab].Undo(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+            },
+            Action::Redo => {
+                self.codeTabs.tabs[self.lastTab].Redo(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;
+            },
+            Action::Copy => {
+                // get the highlighted section of text.... or the line if none
+                let text = self.codeTabs.tabs[self.lastTab].GetSelection();
+                let _ = clipBoard.set_text(text);
+            },
+            Action::Cut => self.CutCode(keyEvents, clipBoard, rustAnalyzer).await,
+            Action::Paste => self.PasteCode(keyEvents, clipBoard, rustAnalyzer).await,
+            Action::GoToDefinition => self.GoToDefinition(),
+            Action::FindReferences => self.FindReferences(),
+            Action::MatchingBracket => self.JumpToMatchingBracket(),
+            Action::ToggleFold => {  self.ToggleFold();  },
+            Action::FoldAll => self.FoldAll(1),
+            Action::UnfoldAll => self.UnfoldAll(),
+            Action::ToggleComment => {  self.ToggleComment(None, rustAnalyzer).await;  },
+            Action::ToggleWrap => self.ToggleWrap(),
+            Action::MoveLinesUp | Action::MoveLinesDown => self.MoveLines(action == Action::MoveLinesUp, rustAnalyzer).await,
+            Action::DuplicateLines => self.DuplicateLines(rustAnalyzer).await,
+            Action::JoinLines => {  self.JoinLines(None, rustAnalyzer).await;  },
+            Action::DeleteLines => self.DeleteLines(None, rustAnalyzer).await,
+            Action::RecordMacro => self.ToggleMacroRecording(Macros::DEFAULT_MACRO_REGISTER),
+            Action::PlayMacro => self.macros.Queue(Some(Macros::DEFAULT_MACRO_REGISTER), 1),
+            _ => {},
         }
     }
 
-    async fn HandleCodeCommands<'b> (&mut self,
-                                     keyEvents: &KeyParser,
-                                     clipBoard: &mut Clipboard,
-                                     rustAnalyzer: RustAnalyzerLsp<'b>
-    ) {
-        if keyEvents.ContainsModifier(&self.preferredCommandKeybind) &&
-            keyEvents.ContainsChar('s')
-        {
-            // saving the program
-            self.codeTabs.tabs[self.lastTab].Save();
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) &&
-            keyEvents.charEvents.contains(&'f')
-        {
-            self.FindCodeReferenceLine(keyEvents, clipBoard);
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) &&
-            (keyEvents.charEvents.contains(&'z') ||  // command z = undo/redo
-                keyEvents.charEvents.contains(&'u') ||  // control/command u = undo
-                keyEvents.charEvents.contains(&'r'))  // control/command + r = redo
-        {
-            self.HandleUndoRedoCode(keyEvents, clipBoard, rustAnalyzer).await;
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) &&
-            keyEvents.charEvents.contains(&'c')
-        {
-            // get the highlighted section of text.... or the line if none
-            let text = self.codeTabs.tabs[self.lastTab].GetSelection();
-            let _ = clipBoard.set_text(text);
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) &&
-            keyEvents.charEvents.contains(&'x')
-        {
-            self.CutCode(keyEvents, clipBoard, rustAnalyzer).await;
-        } else if keyEvents.ContainsModifier(&self.preferredCommandKeybind) &&
-            keyEvents.charEvents.contains(&'v')
-        {
-            self.PasteCode(keyEvents, clipBoard, rustAnalyzer).await;
+    // jumps to where the token on the cursor is declared, going off of the outline (the innermost scope wins)
+    fn GoToDefinition (&mut self) {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let mut tokenSet = vec![];
+        tab.GetCurrentToken(&mut tokenSet);
+        let Some(token) = tokenSet.first() else {  return;  };
+        let Some(scope) = tab.scopeJumps.read().get(tab.cursor.0).cloned() else {  return;  };
+
+        let definition = OutlineKeyword::GetValidScoped(&tab.outlineKeywords, &scope)
+            .into_iter()
+            .filter(|keyword| &keyword.keyword == token)
+            .max_by_key(|keyword| keyword.scope.len())
+            .or_else(|| OutlineKeyword::TryFindKeyword(&tab.outlineKeywords, token.clone()));
+        let Some(definition) = definition else {  return;  };
+        tab.highlighting = false;
+        tab.JumpCursor(definition.lineNumber, 0);
+        // landing on the name itself when it can be found
+        if let Some(&position) = tab.FindReferences(token).iter().find(|(line, _)| *line == definition.lineNumber) {
+            tab.cursor = position;
+        }
+    }
+
+    // comments or uncomments the lines given, or else the selected ones (or just the cursor's line)
+    async fn ToggleComment<'b> (&mut self, lines: Option <(usize, usize)>, rustAnalyzer: RustAnalyzerLsp<'b>) -> bool {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let (start, end) = lines.unwrap_or(
+            if tab.highlighting {  tab.SelectedLines()  }
+            else {  (tab.cursor.0, tab.cursor.0)  }
+        );
+        tab.ToggleComment(start, end, &self.luaSyntaxHighlightScripts, rustAnalyzer).await
+    }
+
+    // jumps between the bracket by the cursor and the one it pairs with
+    fn JumpToMatchingBracket (&mut self) {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let Some((_, pair)) = tab.BracketsAtCursor() else {  return;  };
+        tab.highlighting = false;
+        tab.JumpCursor(pair.0, 0);
+        tab.cursor = pair;
+    }
+
+    // jumps to the next use of the token on the cursor (wrapping back around to the top)
+    fn FindReferences (&mut self) {
+        let tab = &mut self.codeTabs.tabs[self.lastTab];
+        let mut tokenSet = vec![];
+        tab.GetCurrentToken(&mut tokenSet);
+        let Some(token) = tokenSet.into_iter().next() else {  return;  };
+
+        let references = tab.FindReferences(&token);
+        let next = references.iter()
+            .find(|position| **position > tab.cursor)
+            .or(references.first());
+        if let Some(&position) = next {
+            tab.highlighting = false;
+            tab.mouseScrolled = 0;
+            tab.mouseScrolledFlt = 0.0;
+            tab.searchIndex = position.0;
+            tab.cursor = position;
         }
+        tab.searchTerm = token;
     }
 
     async fn HandleCodeKeyEvents<'b> (&mut self,
@@ -1145,208 +1450,145 @@ impl <'a> App <'a> {
                                       clipBoard: &mut Clipboard,
                                       rustAnalyzer: RustAnalyzerLsp<'b>
     ) {
-        self.TypeCode(keyEvents, clipBoard, rustAnalyzer).await;
+        if self.HandleVimKeyEvents(keyEvents, clipBoard, rustAnalyzer).await {  return;  }
 
-        if keyEvents.ContainsKeyCode(KeyCode::Delete) {
-            self.DeleteCode(keyEvents, clipBoard, rustAnalyzer).await;
-        } else if keyEvents.ContainsChar('w') &&
-            keyEvents.ContainsModifier(&KeyModifiers::Option)
-        {
-            self.CloseCodePane();
-        } else if keyEvents.ContainsKeyCode(KeyCode::Left) {
-            self.MoveCodeCursorLeft(keyEvents, clipBoard);
-        } else if keyEvents.ContainsKeyCode(KeyCode::Right) {
-            self.MoveCodeCursorRight(keyEvents, clipBoard);
-        } else if keyEvents.ContainsKeyCode(KeyCode::Up) {
-            self.MoveCodeCursorUp(keyEvents, clipBoard);
-        } else if keyEvents.ContainsKeyCode(KeyCode::Down) {
-            self.MoveCodeCursorDown(keyEvents, clipBoard);
-        } else if keyEvents.ContainsKeyCode(KeyCode::Tab) {
-            self.HandleCodeTabPress(keyEvents, clipBoard, rustAnalyzer).await;
-        } else if keyEvents.ContainsKeyCode(KeyCode::Return) {
-            self.codeTabs.tabs[self.lastTab]
-                .LineBreakIn(false, &self.luaSyntaxHighlightScripts, rustAnalyzer).await;  // can't be highlighting if breaking?
-        } else if
-            keyEvents.charEvents.contains(&'a') &&
-            keyEvents.ContainsModifier(&KeyModifiers::Control)
-        {
-            let tab = &mut self.codeTabs.tabs[self.lastTab];
-            let newCursor = (tab.lines.len() - 1, tab.lines[tab.lines.len() - 1].len());
-            let difference = newCursor.0 - tab.cursor.0;
-            tab.mouseScrolledFlt -= difference as f64;
-            tab.mouseScrolled -= difference as isize;
-            tab.cursorEnd = (0, 0);
-            tab.cursor = newCursor;
-            tab.highlighting = true;
-        } else {
-            self.HandleCodeCommands(keyEvents, clipBoard, rustAnalyzer).await;
+        // vim's normal and visual modes only let through the keymap (the chars are commands there)
+        if self.vim.as_ref().is_none_or(|vim| vim.mode == Vim::VimMode::Insert) {
+            self.TypeCode(keyEvents, clipBoard, rustAnalyzer).await;
         }
-    }
-
-    fn HandleMenuKeyEvents (&mut self, keyEvents: &KeyParser) {
-        if !self.currentCommand.is_empty() {
-            self.HandleMenuCommandKeyEvents(keyEvents);
+        for text in &keyEvents.pastes {
+            self.PasteText(text, rustAnalyzer).await;
         }
 
-        if self.menuState == MenuState::Settings {
-            self.HandleSettingsKeyEvents(keyEvents);
+        let Some(action) = self.keymap.GetAction(KeyContext::Code, keyEvents) else {  return;  };
+        match action {
+            Action::DeleteLeft | Action::DeleteRight | Action::DeleteWordLeft |
+            Action::DeleteWordRight | Action::DeleteLineLeft | Action::DeleteLineRight => {
+                self.DeleteCode(action, clipBoard, rustAnalyzer).await;
+            },
+            Action::ClosePane => self.CloseCodePane(),
+            Action::CursorLeft | Action::WordLeft | Action::LineStart => {
+                self.MoveCodeCursorLeft(action, keyEvents, clipBoard);
+            },
+            Action::CursorRight | Action::WordRight | Action::LineEnd => {
+                self.MoveCodeCursorRight(action, keyEvents, clipBoard);
+            },
+            Action::CursorUp | Action::ScopeStart | Action::FileStart | Action::PageUp => {
+                self.MoveCodeCursorUp(action, keyEvents, clipBoard);
+            },
+            Action::CursorDown | Action::ScopeEnd | Action::FileEnd | Action::PageDown => {
+                self.MoveCodeCursorDown(action, keyEvents, clipBoard);
+            },
+            Action::Indent | Action::Unindent | Action::AcceptSuggestion => {
+                self.HandleCodeTabPress(action, clipBoard, rustAnalyzer).await;
+            },
+            Action::NewLine => {
+                self.codeTabs.tabs[self.lastTab]
+                    .LineBreakIndented(&self.luaSyntaxHighlightScripts, rustAnalyzer).await;  // can't be highlighting if breaking?
+            },
+            Action::SelectAll => {
+                let tab = &mut self.codeTabs.tabs[self.lastTab];
+                let newCursor = (tab.lines.len() - 1, tab.lines[tab.lines.len() - 1].len());
+                let difference = newCursor.0 - tab.cursor.0;
+                tab.mouseScrolledFlt -= difference as f64;
+                tab.mouseScrolled -= difference as isize;
+                tab.cursorEnd = (0, 0);
+                tab.cursor = newCursor;
+                tab.highlighting = true;
+            },
+            _ => self.HandleCodeCommands(action, keyEvents, clipBoard, rustAnalyzer).await,
         }
+    }
 
-        if keyEvents.ContainsModifier(&KeyModifiers::Option) {  return;  }
-
-        for chr in &keyEvents.charEvents {
-            self.currentCommand.push(*chr);
-
-            if self.currentCommand.starts_with("open ") && *chr == '/' {
-                // recalculating the directories
-                self.currentDir = FileBrowser::GetPathName(
-                    self.currentCommand.get(5..)
-                        .unwrap_or("")
-                );
+    async fn HandleMenuKeyEvents<'b> (&mut self, keyEvents: &KeyParser, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        if let Some(action) = self.keymap.GetAction(KeyContext::Menu, keyEvents) {
+            if !self.currentCommand.is_empty() || self.menuState != MenuState::Settings {
+                self.HandleMenuCommandKeyEvents(action, rustAnalyzer).await;
+            }
 
-                self.dirFiles.clear();
-                FileBrowser::CalculateDirectories(&self.currentDir, &mut self.dirFiles);
+            if self.menuState == MenuState::Settings {
+                self.HandleSettingsKeyEvents(action);
             }
         }
-    }
-
-    fn HandleMenuCommandKeyEvents (&mut self, keyEvents: &KeyParser) {
-        // quiting
-        if keyEvents.ContainsKeyCode(KeyCode::Return) {
-            match self.currentCommand.as_str() {
-                "q" => {  self.Exit();  },
-                "settings" => {  self.menuState = MenuState::Settings;  }
-                _ if self.currentCommand.starts_with("open ") => {
-                    let foundFile = self.fileBrowser
-                        .LoadFilePath(self.currentCommand
-                                          .get(5..)
-                                          .unwrap_or(""), &mut self.codeTabs);
-                    if foundFile.is_ok() {
-                        self.fileBrowser.fileCursor = 0;
-                        self.codeTabs.currentTab = 0;
 
-                        self.appState = AppState::CommandPrompt;
+        for text in &keyEvents.pastes {
+            self.currentCommand.push_str(&text.replace('\n', " "));
+        }
 
-                        self.allFiles.clear(); self.RecalcAllFiles();
-                    }
-                },
-                "-light" => {  TermRender::ColorMode::ToLight();  },
-                "-dark" => {  TermRender::ColorMode::ToDark();  },
-                _ => {}
-            }
+        if keyEvents.ContainsModifier(&KeyModifiers::Option) {  return;  }
 
-            self.currentCommand.clear();
-        } else if keyEvents.ContainsKeyCode(KeyCode::Delete) {
-            self.currentCommand.pop();
-        } else if keyEvents.ContainsKeyCode(KeyCode::Tab) &&
-            self.currentCommand.starts_with("open ")
-        {
-            self.OpenCodeProject();
-        }
-    }
-
-    fn OpenCodeProject(&mut self) {
-        // handling suggested directory auto fills
-        let currentToken = self.currentCommand
-            .split("/")
-            .last()
-            .unwrap_or("");
-
-        for path in &self.dirFiles {
-            if path.starts_with(currentToken) {
-                let pathEnding = path
-                    .get(currentToken.len()..)
-                    .unwrap_or("")
-                    .to_string();
-                self.currentCommand.push_str(&pathEnding);
-                self.currentCommand.push('/');
-
-                // recalculating the directories
-                self.currentDir = FileBrowser::GetPathName(
-                    self.currentCommand.get(5..)
-                        .unwrap_or("")
-                );
+        for chr in &keyEvents.charEvents {
+            self.currentCommand.push(*chr);
+            self.commandError = None;
 
-                self.dirFiles.clear();
-                FileBrowser::CalculateDirectories(&self.currentDir, &mut self.dirFiles);
-                break;
+            // recalculating the directories
+            if matches!(*chr, '/' | ' ') {
+                self.UpdateCommandDirectory();
             }
         }
     }
 
-    fn HandleSettingsLeft (&mut self, _keyEvents: &KeyParser) {
-        match self.currentMenuSettingBox {
-            0 => {
-                self.colorMode.colorType = match self.colorMode.colorType {
-                    ColorTypes::Basic => ColorTypes::Basic,
-                    ColorTypes::Partial => ColorTypes::Basic,
-                    ColorTypes::True => ColorTypes::Partial,
-                }
-            },
-            1 => {
-                if self.preferredCommandKeybind == KeyModifiers::Control {
-                    self.preferredCommandKeybind = KeyModifiers::Command;
-                }
-            }
+    async fn HandleMenuCommandKeyEvents<'b> (&mut self, action: Action, rustAnalyzer: RustAnalyzerLsp<'b>) {
+        match action {
+            Action::Confirm => self.RunCommand(rustAnalyzer).await,
+            Action::DeleteChar => {  self.currentCommand.pop();  },
+            Action::CompletePath => self.CompleteCommand(),
+            // there aren't any settings to move through on the welcome screen
+            Action::SettingUp if self.menuState != MenuState::Settings => self.CommandHistoryUp(),
+            Action::SettingDown if self.menuState != MenuState::Settings => self.CommandHistoryDown(),
             _ => {},
         }
     }
 
-    fn HandleSettingsRight (&mut self, _keyEvents: &KeyParser) {
-        match self.currentMenuSettingBox {
-            0 => {
-                self.colorMode.colorType = match self.colorMode.colorType {
-                    ColorTypes::Basic => ColorTypes::Partial,
-                    ColorTypes::Partial => ColorTypes::True,
-                    ColorTypes::True => ColorTypes::True,
-                }
+    fn HandleSettingsKeyEvents (&mut self, action: Action) {
+        match action {
+            Action::SettingLeft => self.StepSetting(false),
+            Action::SettingRight => self.StepSetting(true),
+            Action::SettingUp => {
+                self.currentMenuSettingBox = self.currentMenuSettingBox.saturating_sub(1);
+            },
+            Action::SettingDown => {
+                self.currentMenuSettingBox = std::cmp::min(self.currentMenuSettingBox + 1, Settings::SETTINGS.len() - 1);
             },
-            1 => {
-                if self.preferredCommandKeybind == KeyModifiers::Command {
-                    self.preferredCommandKeybind = KeyModifiers::Control;
-                }
-            }
             _ => {},
         }
     }
 
-    fn HandleSettingsKeyEvents (&mut self, keyEvents: &KeyParser) {
-        if keyEvents.ContainsKeyCode(KeyCode::Left) {
-            self.HandleSettingsLeft(keyEvents);
-        } else if keyEvents.ContainsKeyCode(KeyCode::Right) {
-            self.HandleSettingsRight(keyEvents);
-        } else if keyEvents.ContainsKeyCode(KeyCode::Up) {
-            self.currentMenuSettingBox = self.currentMenuSettingBox.saturating_sub(1);
-        } else if keyEvents.ContainsKeyCode(KeyCode::Down) {
-            self.currentMenuSettingBox += 1;
-        }
-    }
-
     async fn HandleKeyEvents<'b> (&mut self,
                                   keyEvents: &KeyParser,
                                   clipBoard: &mut Clipboard,
                                   rustAnalyzer: RustAnalyzerLsp<'b>
     ) {
+        // escape only closes the context menu when it's open
+        if self.contextMenu.is_some() && keyEvents.ContainsKeyCode(KeyCode::Escape) {
+            self.contextMenu = None;
+            return;
+        }
+        // or backs out of vim's insert/visual modes (and half typed commands)
+        let vimEscape = self.appState == AppState::Tabs &&
+            self.vim.as_ref().is_some_and(|vim| vim.TakesEscape());
+
         match self.appState {
             AppState::CommandPrompt => {
-                self.HandleCommandPromptKeyEvents(keyEvents);
+                self.HandleCommandPromptKeyEvents(keyEvents, rustAnalyzer).await;
             },
             AppState::Tabs => {
                 if !self.codeTabs.tabs.is_empty() {
                     self.HandleCodeKeyEvents(keyEvents, clipBoard, rustAnalyzer).await;
+                    self.FollowSnippet(keyEvents, rustAnalyzer).await;
                 }
                 if self.tabState != TabState::Code {
                     self.tabState = TabState::Code
                 }
             },
             AppState::Menu => {
-                self.HandleMenuKeyEvents(keyEvents);
+                self.HandleMenuKeyEvents(keyEvents, rustAnalyzer).await;
             },
             //_ => {},
         }
         
         // handling escape (switching tabs)
-        if keyEvents.ContainsKeyCode(KeyCode::Escape) &&
+        if keyEvents.ContainsKeyCode(KeyCode::Escape) && !vimEscape &&
             self.fileBrowser.fileOptions.selectedOptionsTab == OptionTabs::Null
         {
             self.appState = match self.appState {
@@ -1380,7 +1622,8 @@ impl <'a> App <'a> {
     // ============================================= file block here =============================================
     fn RenderFileBlock (&mut self, app: &mut TermRender::App){
         let coloredTabText = self.codeTabs.GetColoredNames(
-            self.appState == AppState::CommandPrompt && self.tabState == TabState::Tabs
+            self.appState == AppState::CommandPrompt && self.tabState == TabState::Tabs,
+            &self.colorMode,
         );
         let tabText = vec![
             Span::FromTokens(coloredTabText)
@@ -1408,7 +1651,7 @@ impl <'a> App <'a> {
 
         // rendering the info on the cursor's position
         let tab = &self.codeTabs.tabs[self.lastTab];
-        let text = Span::FromTokens(vec![
+        let mut tokens = vec![
             if tab.highlighting {
                 let (start, end) = (
                     std::cmp::min(tab.cursor.0, tab.cursorEnd.0),
@@ -1426,7 +1669,14 @@ impl <'a> App <'a> {
                         charCount,
                         (charCursor as f64 / charCount as f64 * 100.0) as usize
                 ), BrightBlack, Italic]
-        }]);
+        }];
+        if let Some(vim) = &self.vim {
+            tokens.insert(0, color![format!("{}   ", vim.StatusText()), BrightWhite, Bold]);
+        }
+        if let Some(recording) = self.macros.StatusText() {
+            tokens.insert(0, color![format!("{}   ", recording), Red, Bold]);
+        }
+        let text = Span::FromTokens(tokens);
         let window = app.GetWindowReferenceMut(String::from("CursorInfo"));
         window.TryUpdateLines(vec![text]);
     }
@@ -1468,7 +1718,7 @@ impl <'a> App <'a> {
         ));
         if window.Resize((
             tabSize as u16,
-            height - 9
+            CodeTabs::CodeRows(height) as u16 + 2  // the code lines and the border around them
         )) {
             self.codeTabs.tabs[trueTabIndex].ClearRenderCache();
         }
@@ -1644,11 +1894,11 @@ impl <'a> App <'a> {
         let errorText = vec![
             Span::FromTokens(vec![
                 color![format!(": {}", self.suggested), Italic]
-                    .Colorize(self.colorMode.colorBindings.suggestion),
+                    .Colorize(self.colorMode.colorBindings.suggestion.Get(&self.colorMode.colorType)),
             ]),
             Span::FromTokens(vec![
                 color![format!("Debug: {}", self.debugInfo), Bold]
-                    .Colorize(self.colorMode.colorBindings.errorCol),
+                    .Colorize(self.colorMode.colorBindings.errorCol.Get(&self.colorMode.colorType)),
                 //format!(" ; {:?}", scope).white()
             ]),
         ];
@@ -1664,50 +1914,59 @@ impl <'a> App <'a> {
         self.RenderFiles(app);
         self.RenderErrorBar(app);
         self.RenderCodeBlock(app);
+        self.RenderContextMenu(app);
     }
 
-    fn RenderSettings (&mut self, app: &mut TermRender::App) {//, area: Rect, buf: &mut Buffer) {
-        // ============================================= Color Settings =============================================
-        // the color mode setting
-        let settingsText = vec![
-            Span::FromTokens(vec![
-                color!["Color Mode: [", BrightWhite],
-                {
-                    if self.colorMode.colorType == ColorTypes::Basic {
-                        color!["Basic", Yellow, Bold, Underline]
-                    } else {
-                        color!["Basic", BrightWhite]
-                    }
-                },
-                color!["]", BrightWhite],
-                color![" [", BrightWhite],
-                {
-                    if self.colorMode.colorType == ColorTypes::Partial {
-                        color!["8-bit", Yellow, Bold, Underline]
-                    } else {
-                        color!["8-bit", BrightWhite]
-                    }
-                },
-                color!["]", BrightWhite],
-                color![" [", BrightWhite],
-                {
-                    if self.colorMode.colorType == ColorTypes::True {
-                        color!["24-bit", Yellow, Bold, Underline]
-                    } else {
-                        color!["24-bit", BrightWhite]
-                    }
-                },
-                color!["]", BrightWhite],
-            ]),
+    fn RenderContextMenu (&mut self, app: &mut TermRender::App) {
+        let window = app.GetWindowReferenceMut(String::from("ContextMenu"));
+        // hiding it blanks it out, and the renderer then redraws the code underneath
+        if self.contextMenu.is_none() {
+            window.Hide();
+            return;
+        }
+
+        window.Show();
+        let width = CONTEXT_MENU_WIDTH as usize - 2;
+        window.TryUpdateLines(CONTEXT_MENU.iter().map(|(name, _)| {
             Span::FromTokens(vec![
-                color![" * Not all terminals accept all color modes. If the colors are messed up, try lowering this",
-                    White, Dim, Italic]
-            ]),
-        ];
+                color![format!(" {name:<width$}", width = width - 1), BrightWhite, OnBrightBlack],
+            ])
+        }).collect());
+    }
 
-        {
-            let window = app.GetWindowReferenceMut(String::from("ColorSetting"));
-            if self.currentMenuSettingBox == 0 {
+    fn RenderSettings (&mut self, app: &mut TermRender::App) {//, area: Rect, buf: &mut Buffer) {
+        // ============================================= Settings =============================================
+        // one box per setting, with its value picked out among the others
+        for (index, info) in Settings::SETTINGS.iter().enumerate() {
+            let current = self.SettingValue(info.setting);
+            let mut tokens = vec![color![format!("{}: ", info.name), BrightWhite]];
+            let mut choices = info.Choices();
+            if let Settings::SettingKind::Number(..) = info.kind {
+                tokens.push(color!["< ", BrightWhite]);
+                tokens.push(color![current.clone(), Yellow, Bold, Underline]);
+                tokens.push(color![" >", BrightWhite]);
+            } else if !choices.contains(&current) {
+                choices.push(current.clone());
+            }
+            for choice in choices {
+                tokens.push(color![" [", BrightWhite]);
+                if choice == current {
+                    tokens.push(color![info.Label(&choice), Yellow, Bold, Underline]);
+                } else {
+                    tokens.push(color![info.Label(&choice), BrightWhite]);
+                }
+                tokens.push(color!["]", BrightWhite]);
+            }
+
+            let settingsText = vec![
+                Span::FromTokens(tokens),
+                Span::FromTokens(vec![
+                    color![format!(" * {}", info.description), BrightWhite, Dim, Italic]
+                ]),
+            ];
+
+            let window = app.GetWindowReferenceMut(format!("Setting:{}", info.key));
+            if self.currentMenuSettingBox == index {
                 window.TryColorize(ColorType::BrightBlue);
             } else {
                 window.ClearColors();
@@ -1715,41 +1974,50 @@ impl <'a> App <'a> {
             window.TryUpdateLines(settingsText);
         }
 
-        // ============================================= Key Settings =============================================
-        // the color mode setting
+        // ============================================= Terminal Info =============================================
+        // the detected capabilities (read only)
+        let capabilities = app.GetCapabilities().clone();
+        let YesNo = |value: bool| {
+            if value {  color!["yes", Green, Bold]  }
+            else {  color!["no", Red, Bold]  }
+        };
         let settingsText = vec![
             Span::FromTokens(vec![
-                color!["Preferred Modifier Key: [", BrightWhite],
-                {
-                    if self.preferredCommandKeybind == KeyModifiers::Command {
-                        color!["Command", Yellow, Bold, Underline]
-                    } else {
-                        color!["Command", BrightWhite]
-                    }
-                },
-                color!["]", BrightWhite],
-                color![" [", BrightWhite],
-                {
-                    if self.preferredCommandKeybind == KeyModifiers::Control {
-                        color!["Control", Yellow, Bold, Underline]
-                    } else {
-                        color!["Control", BrightWhite]
-                    }
-                },
-                color!["]", BrightWhite],
+                color!["Terminal: ", BrightWhite],
+                color![
+                    format!("{} (COLORTERM: {})",
+                        if capabilities.term.is_empty() {  "unknown"  } else {  capabilities.term.as_str()  },
+                        if capabilities.colorTerm.is_empty() {  "unset"  } else {  capabilities.colorTerm.as_str()  },
+                    ),
+                    Yellow
+                ],
+                color!["  Dumb: ", BrightWhite],
+                YesNo(capabilities.dumb),
+                color!["  Keyboard: ", BrightWhite],
+                color![format!("{:?}", capabilities.keyboardProtocol), Yellow],
             ]),
             Span::FromTokens(vec![
-                color![" * The preferred modifier key for things like ctrl/cmd 'c'", BrightWhite, Dim, Italic]
+                color!["24-bit: ", BrightWhite],
+                YesNo(capabilities.trueColor),
+                color!["  8-bit: ", BrightWhite],
+                YesNo(capabilities.colors256),
+                color!["  Synchronized Output: ", BrightWhite],
+                YesNo(capabilities.synchronizedOutput),
+                color!["  Replied to Queries: ", BrightWhite],
+                YesNo(capabilities.answered),
+                color!["  Background: ", BrightWhite],
+                match capabilities.background {
+                    Some((r, g, b)) => color![
+                        format!("#{:02x}{:02x}{:02x} ({:?})", r, g, b, TermRender::ColorMode::FromBackground((r, g, b))),
+                        Yellow
+                    ],
+                    None => color!["unknown", Red, Bold],
+                },
             ]),
         ];
 
         {
-            let window = app.GetWindowReferenceMut(String::from("KeybindSetting"));
-            if self.currentMenuSettingBox == 1 {
-                window.TryColorize(ColorType::BrightBlue);
-            } else {
-                window.ClearColors();
-            }
+            let window = app.GetWindowReferenceMut(String::from("TerminalSetting"));
             window.TryUpdateLines(settingsText);
         }
     }
@@ -1911,6 +2179,23 @@ impl <'a> App <'a> {
             ]);
         }  // */
 
+        if app.ContainsWindow(String::from("ContextMenu")) {
+            let window = app.GetWindowReferenceMut(String::from("ContextMenu"));
+            if let Some(position) = self.contextMenu {
+                window.Move(position);
+            }
+        } else {
+            let mut window = TermRender::Window::new(
+                (1, 1), 1,
+                (CONTEXT_MENU_WIDTH, CONTEXT_MENU.len() as u16 + 2)
+            );
+            window.Bordered();
+            window.Colorize(ColorType::OnBrightBlack);
+            window.Hide();
+            window.SupressUpdates();
+            app.AddWindow(window, String::from("ContextMenu"), vec![String::from("Project")]);
+        }
+
         // dealing with the annoying code tabs
         self.CheckCodeTabs(app, (self.area.width, self.area.height));
 
@@ -2033,39 +2318,45 @@ impl <'a> App <'a> {
                 }
             },
             MenuState::Settings => {
-                if app.ContainsWindow(String::from("ColorSetting")) {
-                    let window = app.GetWindowReferenceMut(String::from("ColorSetting"));
-                    window.Move((
-                        10, 2,
-                    ));
-                    window.Resize((self.area.width - 20, 4));
-                } else {
-                    let mut window = TermRender::Window::new(
-                        (10, 2), 0,
-                        (self.area.width - 20, 4)
-                    );
-                    window.Bordered();
-                    app.AddWindow(window, String::from("ColorSetting"), vec![
-                        String::from("Menu"), String::from("Settings")
-                    ]);
-                    //app.UpdateWindowLayoutOrder();  // resized windows will be moved but still ordered the same
+                for (index, info) in Settings::SETTINGS.iter().enumerate() {
+                    let name = format!("Setting:{}", info.key);
+                    let y = 2 + index as u16 * 4;
+                    if app.ContainsWindow(name.clone()) {
+                        let window = app.GetWindowReferenceMut(name);
+                        window.Move((
+                            10, y,
+                        ));
+                        window.Resize((self.area.width - 20, 4));
+                    } else {
+                        let mut window = TermRender::Window::new(
+                            (10, y), 0,
+                            (self.area.width - 20, 4)
+                        );
+                        window.Bordered();
+                        app.AddWindow(window, name, vec![
+                            String::from("Menu"), String::from("Settings")
+                        ]);
+                        //app.UpdateWindowLayoutOrder();  // resized windows will be moved but still ordered the same
+                    }
                 }
-                if app.ContainsWindow(String::from("KeybindSetting")) {
-                    let window = app.GetWindowReferenceMut(String::from("KeybindSetting"));
+
+                let terminalY = 2 + Settings::SETTINGS.len() as u16 * 4;
+                if app.ContainsWindow(String::from("TerminalSetting")) {
+                    let window = app.GetWindowReferenceMut(String::from("TerminalSetting"));
                     window.Move((
-                        10, 6,
+                        10, terminalY,
                     ));
                     window.Resize((self.area.width - 20, 4));
                 } else {
                     let mut window = TermRender::Window::new(
-                        (10, 6), 0,
+                        (10, terminalY), 0,
                         (self.area.width - 20, 4)
                     );
                     window.Bordered();
-                    app.AddWindow(window, String::from("KeybindSetting"), vec![
+                    window.Titled(String::from("Detected Terminal"));
+                    app.AddWindow(window, String::from("TerminalSetting"), vec![
                         String::from("Menu"), String::from("Settings")
                     ]);
-                    //app.UpdateWindowLayoutOrder();  // resized windows will be moved but still ordered the same
                 }
 
                 if app.ChangedWindowLayout() {
@@ -2079,6 +2370,29 @@ impl <'a> App <'a> {
         };
     }
 
+    fn RenderCommandPalette (&mut self, app: &mut TermRender::App) {
+        let width = std::cmp::min(COMMAND_PALETTE_WIDTH, self.area.width.saturating_sub(2)) as usize;
+        let lines =
+            if matches!(self.appState, AppState::CommandPrompt | AppState::Menu) {
+                self.PaletteLines(COMMAND_PALETTE_ROWS as usize, width.saturating_sub(2))
+            } else {  vec![]  };
+
+        let window = app.GetWindowReferenceMut(String::from("CommandPalette"));
+        // hiding it blanks it out, and the renderer then redraws whatever is underneath
+        if lines.is_empty() || self.currentCommand.is_empty() {
+            window.Hide();
+            return;
+        }
+
+        window.Show();
+        let blank = Span::FromTokens(vec![
+            color![" ".repeat(width.saturating_sub(2)), BrightWhite, OnBrightBlack],
+        ]);
+        let mut lines = lines;
+        lines.resize(COMMAND_PALETTE_ROWS as usize, blank);
+        window.TryUpdateLines(lines);
+    }
+
     fn CheckWindows (&mut self, app: &mut TermRender::App) {
         if app.ContainsWindow(String::from("CommandLine")) {
             let window = app.GetWindowReferenceMut(String::from("CommandLine"));
@@ -2092,9 +2406,31 @@ impl <'a> App <'a> {
             app.AddWindow(window, String::from("CommandLine"), vec![]);
             //app.UpdateWindowLayoutOrder();
         }
+
+        // the palette sits just above the command line
+        let paletteSize = (
+            std::cmp::min(COMMAND_PALETTE_WIDTH, self.area.width.saturating_sub(2)),
+            COMMAND_PALETTE_ROWS + 2
+        );
+        let palettePosition = (1, self.area.height.saturating_sub(paletteSize.1).max(1));
+        if app.ContainsWindow(String::from("CommandPalette")) {
+            let window = app.GetWindowReferenceMut(String::from("CommandPalette"));
+            window.Move(palettePosition);
+            window.Resize(paletteSize);
+        } else {
+            let mut window = TermRender::Window::new(palettePosition, 1, paletteSize);
+            window.Bordered();
+            window.Titled(String::from("Commands"));
+            window.Colorize(ColorType::OnBrightBlack);
+            window.Hide();
+            window.SupressUpdates();
+            app.AddWindow(window, String::from("CommandPalette"), vec![]);
+        }
     }
 
     fn RenderFrame (&mut self, app: &mut TermRender::App) -> usize {
+        app.SetColorMode(self.colorMode.background);
+        app.SetColorType(self.colorMode.colorType);
         self.CheckWindows(app);
         match self.appState {
             AppState::Tabs | AppState::CommandPrompt => {
@@ -2114,30 +2450,12 @@ impl <'a> App <'a> {
                 color!["/", BrightWhite, Bold],//.to_string().white().bold(),
                 color![self.currentCommand, BrightWhite, Italic],//.clone().white().italic(),
                 {
-                    if  self.appState == AppState::Menu &&
-                        self.currentCommand.starts_with("open ")
-                    {
-                        // handling suggested directory auto fills
-                        let currentToken = self.currentCommand
-                            .split("/")
-                            .last()
-                            .unwrap_or("");
-
-                        let mut validFinish = String::new();
-                        for path in &self.dirFiles {
-                            if path.starts_with(currentToken) {
-                                validFinish = path
-                                    .get(currentToken.len()..)
-                                    .unwrap_or("")
-                                    .to_string();
-                                break;
-                            }
-                        }
-
-                        color![validFinish, BrightBlack]//.white().dim()
-                    } else {
-                        color![""]//.white().dim()
-                    }
+                    // the rest of what tab would fill in
+                    let completion = self.CommandCompletion()
+                        .filter(|_| matches!(self.appState, AppState::CommandPrompt | AppState::Menu))
+                        .and_then(|completed| completed.strip_prefix(self.currentCommand.as_str()).map(str::to_string))
+                        .unwrap_or_default();
+                    color![completion.trim_end_matches('/').to_string(), BrightBlack]
                 },
                 {
                     if matches!(self.appState, AppState::CommandPrompt | AppState::Menu) {
@@ -2148,8 +2466,18 @@ impl <'a> App <'a> {
                 },
         ]);
 
+        // a failed command leaves its error up until something else is typed
+        let commandText = match &self.commandError {
+            Some(error) => Span::FromTokens(vec![
+                color!["/", BrightWhite, Bold],
+                color![error, Red, Italic],
+            ]),
+            None => commandText,
+        };
+
         let window = app.GetWindowReferenceMut(String::from("CommandLine"));
         window.TryUpdateLines(vec![commandText]);
+        self.RenderCommandPalette(app);
 
         // rendering the updated app
         app.Render(Some((
@@ -2291,19 +2619,46 @@ multi-line comments aren't updated properly when just pressing return (on empty
 
 */
 
+// x11 and wayland keep a second clipboard with the last selection (for middle clicking); other platforms don't have one
+#[cfg(target_os = "linux")]
+fn GetPrimarySelection (clipBoard: &mut Clipboard) -> Option <String> {
+    use arboard::GetExtLinux;
+    clipBoard.get().clipboard(arboard::LinuxClipboardKind::Primary).text().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn GetPrimarySelection (_clipBoard: &mut Clipboard) -> Option <String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn SetPrimarySelection (clipBoard: &mut Clipboard, text: String) {
+    use arboard::SetExtLinux;
+    if text.is_empty() {  return;  }
+    let _ = clipBoard.set().clipboard(arboard::LinuxClipboardKind::Primary).text(text);
+}
+
+#[cfg(not(target_os = "linux"))]
+fn SetPrimarySelection (_clipBoard: &mut Clipboard, _text: String) {}
+
 fn main() {
     // this runtime is implemented in a way where blocking tasks/blocking thread sleeps don't block others tasks from running
     // each task gets its own thread so blocking is safe unless the section requires a safe/soft exit instead of a hard drop
     let runtime = std::sync::Arc::new(parking_lot::RwLock::new(Runtime::default()));
     let clonedRuntime = runtime.clone();  // creating a clone of the runtime manager (each task gets a unique thread)
     runtime.write().AddTask(Box::pin(async {
+        let _keyboardModes = KeyboardModesGuard;
         let mut termApp = TermRender::App::new();
 
-        enableMouseCapture().await;
+        if !termApp.GetCapabilities().dumb {
+            enableMouseCapture().await;
+            enableBracketedPaste();
+        }
         let _app_result = App::default().Run(&mut termApp, clonedRuntime).await;
         //app_result.unwrap();  // too lazy to make the runtime actually be able to output things....
         // (unwrapping stalls the thread; the result needs to be ignored to allow proper exiting/exit handling)
         disableMouseCapture().await;
+        disableBracketedPaste();
     }));
     loop {
         let completed = runtime.write().Poll();